@@ -0,0 +1,375 @@
+// lib.rs      muon-rs-derive crate.
+//
+// Copyright (c) 2019-2020  Douglas Lau
+//
+#![forbid(unsafe_code)]
+#![deny(missing_docs)]
+//! Derive macro for [muon-rs](https://docs.rs/muon-rs) schemas.
+//!
+//! This crate should be used through the `derive` feature of `muon-rs`,
+//! which re-exports `#[derive(MuonSchema)]`.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Group, TokenStream as TokenStream2};
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::{
+    parse_macro_input, parse_quote, token, Attribute, Data, DeriveInput, Error,
+    Expr, ExprLit, Field, Fields, Lit, LitStr, Meta, Result, Token,
+};
+
+/// Attributes of a struct
+#[derive(Default)]
+struct ContainerAttrs {
+    /// Rule for renaming fields (from `#[serde(rename_all = "...")]`)
+    rename_all: Option<RenameRule>,
+    /// Flag for fields with serde defaults (from `#[serde(default)]`)
+    serde_default: bool,
+}
+
+/// Rule for renaming fields
+#[derive(Clone, Copy)]
+enum RenameRule {
+    /// `lowercase`
+    Lower,
+    /// `UPPERCASE`
+    Upper,
+    /// `PascalCase`
+    Pascal,
+    /// `camelCase`
+    Camel,
+    /// `snake_case`
+    Snake,
+    /// `SCREAMING_SNAKE_CASE`
+    ScreamingSnake,
+    /// `kebab-case`
+    Kebab,
+    /// `SCREAMING-KEBAB-CASE`
+    ScreamingKebab,
+}
+
+/// Attributes of one field
+#[derive(Default)]
+struct FieldAttrs {
+    /// Key name (from `#[serde(rename = "...")]`)
+    rename: Option<LitStr>,
+    /// Skip flag (from `#[serde(skip)]`)
+    skip: bool,
+    /// Flag for a serde default (from `#[serde(default)]`)
+    serde_default: bool,
+    /// Default value
+    default: Option<LitStr>,
+    /// Doc comment
+    doc: Option<String>,
+}
+
+/// Parse the nested meta of a `serde` attribute
+///
+/// Other serde attributes are checked by serde itself.
+fn parse_serde<F>(attr: &Attribute, mut f: F) -> Result<()>
+where
+    F: FnMut(&ParseNestedMeta) -> Result<bool>,
+{
+    attr.parse_nested_meta(|meta| {
+        if f(&meta)? {
+            return Ok(());
+        }
+        if meta.input.peek(Token![=]) {
+            meta.value()?.parse::<Lit>()?;
+        } else if meta.input.peek(token::Paren) {
+            meta.input.parse::<Group>()?;
+        }
+        Ok(())
+    })
+}
+
+/// Parse the optional function path of `#[serde(default = "...")]`
+fn parse_default_path(meta: &ParseNestedMeta) -> Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<LitStr>()?;
+    }
+    Ok(())
+}
+
+/// Make an error for an unsupported serde attribute
+fn unsupported(meta: &ParseNestedMeta, name: &str) -> Error {
+    meta.error(format!("`serde({name})` is not supported by MuonSchema"))
+}
+
+impl ContainerAttrs {
+    /// Parse attributes of a struct
+    fn parse(input: &DeriveInput) -> Result<Self> {
+        let mut attrs = ContainerAttrs::default();
+        for attr in &input.attrs {
+            if attr.path().is_ident("serde") {
+                parse_serde(attr, |meta| {
+                    if meta.path.is_ident("default") {
+                        attrs.serde_default = true;
+                        parse_default_path(meta)?;
+                        return Ok(true);
+                    }
+                    if !meta.path.is_ident("rename_all") {
+                        return Ok(false);
+                    }
+                    if !meta.input.peek(Token![=]) {
+                        return Err(unsupported(meta, "rename_all(..)"));
+                    }
+                    let rule: LitStr = meta.value()?.parse()?;
+                    attrs.rename_all = Some(RenameRule::parse(&rule)?);
+                    Ok(true)
+                })?;
+            }
+        }
+        Ok(attrs)
+    }
+}
+
+impl RenameRule {
+    /// Parse a rename rule
+    fn parse(rule: &LitStr) -> Result<Self> {
+        use RenameRule::*;
+        match rule.value().as_str() {
+            "lowercase" => Ok(Lower),
+            "UPPERCASE" => Ok(Upper),
+            "PascalCase" => Ok(Pascal),
+            "camelCase" => Ok(Camel),
+            "snake_case" => Ok(Snake),
+            "SCREAMING_SNAKE_CASE" => Ok(ScreamingSnake),
+            "kebab-case" => Ok(Kebab),
+            "SCREAMING-KEBAB-CASE" => Ok(ScreamingKebab),
+            _ => Err(Error::new_spanned(rule, "unknown rename rule")),
+        }
+    }
+
+    /// Apply the rule to a `snake_case` field name
+    fn apply(self, name: &str) -> String {
+        use RenameRule::*;
+        match self {
+            Lower | Snake => name.to_string(),
+            Upper | ScreamingSnake => name.to_ascii_uppercase(),
+            Pascal => name.split('_').map(capitalize).collect(),
+            Camel => match name.split_once('_') {
+                Some((first, rest)) => first.to_string() + &Pascal.apply(rest),
+                None => name.to_string(),
+            },
+            Kebab => name.replace('_', "-"),
+            ScreamingKebab => name.replace('_', "-").to_ascii_uppercase(),
+        }
+    }
+}
+
+/// Capitalize the first character of a word
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(c) => c.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+/// Get the doc comment from attributes, if any
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let mut lines = Vec::new();
+    for attr in attrs {
+        if let Meta::NameValue(nv) = &attr.meta {
+            if nv.path.is_ident("doc") {
+                if let Expr::Lit(ExprLit {
+                    lit: Lit::Str(doc), ..
+                }) = &nv.value
+                {
+                    let line = doc.value();
+                    let line = line.strip_prefix(' ').unwrap_or(&line);
+                    lines.push(line.trim_end().to_string());
+                }
+            }
+        }
+    }
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+impl FieldAttrs {
+    /// Parse attributes of a field
+    fn parse(field: &Field) -> Result<Self> {
+        let mut attrs = FieldAttrs {
+            doc: doc_comment(&field.attrs),
+            ..Default::default()
+        };
+        for attr in &field.attrs {
+            if attr.path().is_ident("muon") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("default") {
+                        attrs.default = Some(meta.value()?.parse()?);
+                        Ok(())
+                    } else {
+                        Err(meta.error("unknown muon attribute"))
+                    }
+                })?;
+            } else if attr.path().is_ident("serde") {
+                parse_serde(attr, |meta| attrs.parse_serde(meta))?;
+            }
+        }
+        Ok(attrs)
+    }
+
+    /// Parse one nested meta of a `serde` field attribute
+    fn parse_serde(&mut self, meta: &ParseNestedMeta) -> Result<bool> {
+        if meta.path.is_ident("rename") {
+            if meta.input.peek(Token![=]) {
+                self.rename = Some(meta.value()?.parse()?);
+            } else {
+                // Data is read back using the deserialize name
+                meta.parse_nested_meta(|meta| {
+                    let name = meta.value()?.parse()?;
+                    if meta.path.is_ident("deserialize") {
+                        self.rename = Some(name);
+                    }
+                    Ok(())
+                })?;
+            }
+            Ok(true)
+        } else if meta.path.is_ident("skip")
+            || meta.path.is_ident("skip_deserializing")
+        {
+            self.skip = true;
+            Ok(true)
+        } else if meta.path.is_ident("default") {
+            self.serde_default = true;
+            parse_default_path(meta)?;
+            Ok(true)
+        } else if meta.path.is_ident("flatten") {
+            Err(unsupported(meta, "flatten"))
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+/// Derive `MuonSchema` for a struct with named fields
+///
+/// Each field becomes a schema node, typed by its own `MuonSchema`
+/// implementation.  Field attributes:
+///
+/// * `#[muon(default = "...")]`: Default value of the node
+///
+/// Doc comments of fields are written as comments ahead of their nodes.
+///
+/// These serde attributes are also used:
+///
+/// * `#[serde(rename_all = "...")]` on the struct, and
+///   `#[serde(rename = "...")]` on fields, change key names
+/// * `#[serde(skip)]` and `#[serde(skip_deserializing)]` leave a field out
+/// * `#[serde(default)]` on the struct or a field makes nodes `optional`,
+///   unless a `muon` default value is given
+///
+/// `#[serde(flatten)]` is not supported, and causes a compile error.
+#[proc_macro_derive(MuonSchema, attributes(muon))]
+pub fn derive_muon_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Expand the derive macro
+fn expand(mut input: DeriveInput) -> Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                let msg = "MuonSchema requires named fields";
+                return Err(Error::new_spanned(&input.ident, msg));
+            }
+        },
+        _ => {
+            let msg = "MuonSchema can only be derived for structs";
+            return Err(Error::new_spanned(&input.ident, msg));
+        }
+    };
+    let container = ContainerAttrs::parse(&input)?;
+    let mut nodes = Vec::new();
+    for field in fields {
+        nodes.push(expand_field(field, &container)?);
+    }
+    let ident = &input.ident;
+    let params: Vec<_> = input.generics.type_params().cloned().collect();
+    let where_clause = input.generics.make_where_clause();
+    for param in params {
+        let ident = &param.ident;
+        where_clause
+            .predicates
+            .push(parse_quote!(#ident: ::muon_rs::MuonSchema));
+    }
+    let (impl_generics, ty_generics, where_clause) =
+        input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::muon_rs::MuonSchema for #ident #ty_generics
+        #where_clause
+        {
+            const NODE_TYPE: ::muon_rs::Type = ::muon_rs::Type::Record;
+
+            fn add_nodes(
+                schema: &mut ::muon_rs::Schema<'static>,
+                depth: usize,
+            ) -> ::std::result::Result<(), ::muon_rs::Error> {
+                #(#nodes)*
+                ::std::result::Result::Ok(())
+            }
+        }
+    })
+}
+
+/// Expand the schema node for one field
+fn expand_field(
+    field: &Field,
+    container: &ContainerAttrs,
+) -> Result<TokenStream2> {
+    let attrs = FieldAttrs::parse(field)?;
+    if attrs.skip {
+        return Ok(TokenStream2::new());
+    }
+    let name = match (attrs.rename, &field.ident) {
+        (Some(rename), _) => rename.value(),
+        (None, Some(ident)) => {
+            let name = ident.to_string();
+            let name = name.strip_prefix("r#").unwrap_or(&name);
+            match container.rename_all {
+                Some(rule) => rule.apply(name),
+                None => name.to_string(),
+            }
+        }
+        (None, None) => unreachable!("named field"),
+    };
+    let ty = &field.ty;
+    // Fields with a serde default may be left out, like a muon default
+    let modifier = if (attrs.serde_default || container.serde_default)
+        && attrs.default.is_none()
+    {
+        quote! {
+            <#ty as ::muon_rs::MuonSchema>::MODIFIER
+                .or(::std::option::Option::Some(::muon_rs::Modifier::Optional))
+        }
+    } else {
+        quote! { <#ty as ::muon_rs::MuonSchema>::MODIFIER }
+    };
+    let default = attrs.default.map(|default| {
+        quote! { let node = node.with_default(#default)?; }
+    });
+    let doc = attrs.doc.map(|doc| {
+        quote! { let node = node.with_doc(#doc); }
+    });
+    Ok(quote! {
+        let node = ::muon_rs::Node::new(
+            depth,
+            #name,
+            #modifier,
+            <#ty as ::muon_rs::MuonSchema>::NODE_TYPE,
+        );
+        #default
+        #doc
+        schema.push(node)?;
+        <#ty as ::muon_rs::MuonSchema>::add_nodes(schema, depth + 1)?;
+    })
+}
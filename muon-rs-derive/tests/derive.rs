@@ -0,0 +1,141 @@
+use muon_rs as muon;
+use muon_rs::MuonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize, MuonSchema, Serialize, PartialEq)]
+struct Config {
+    /// Server host name
+    host: String,
+    #[muon(default = "8080")]
+    port: u16,
+    /// Log level
+    /// error, warn or info
+    #[serde(rename = "log-level", default)]
+    log_level: Option<String>,
+    started: muon::DateTime,
+    ratio: f64,
+    user: Vec<User<bool>>,
+    env: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, MuonSchema, Serialize, PartialEq)]
+struct User<T> {
+    name: String,
+    admin: T,
+    groups: Option<Vec<String>>,
+    r#type: Option<char>,
+}
+
+#[test]
+fn schema() -> muon::Result<()> {
+    let schema = Config::schema()?;
+    assert_eq!(
+        schema.to_string(),
+        "# Server host name\nhost: text\nport: int 8080\n\
+         # Log level\n# error, warn or info\nlog-level: optional text\n\
+         started: datetime\nratio: number\nuser: list record\n  \
+         name: text\n  admin: bool\n  groups: list text\n  \
+         type: optional text\nenv: dictionary\n"
+    );
+    let node = schema.find(&["host"]).unwrap();
+    assert_eq!(node.doc(), Some("Server host name"));
+    let text = schema.to_string();
+    assert_eq!(muon::Schema::parse(&text)?.nodes().count(), 11);
+    let doc = format!(
+        ":::\n{text}:::\nhost: localhost\nstarted: 2020-02-20T20:20:20Z\n\
+         ratio: 0.5\nuser: one\n  admin: true\n  groups: a b\nenv:\n  HOME: /root\n"
+    );
+    assert_eq!(muon::check(&doc), []);
+    let config: Config = muon::from_str(&doc)?;
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.user[0].groups, Some(vec!["a".into(), "b".into()]));
+    Ok(())
+}
+
+#[derive(MuonSchema)]
+struct BadDefault {
+    #[muon(default = "many")]
+    _count: u32,
+}
+
+#[test]
+fn bad_default() {
+    let e = BadDefault::schema().unwrap_err();
+    assert_eq!(e.parse_error(), Some(muon::ParseError::InvalidDefault));
+}
+
+#[derive(Debug, Deserialize, MuonSchema, Serialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+struct Renamed {
+    max_count: u32,
+    #[serde(rename(serialize = "ser", deserialize = "min"))]
+    min_count: u32,
+    #[serde(skip)]
+    cache: Vec<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_seen: Option<muon::Date>,
+}
+
+#[test]
+fn renamed() -> muon::Result<()> {
+    let schema = Renamed::schema()?;
+    assert_eq!(
+        schema.to_string(),
+        "max-count: int\nmin: int\nlast-seen: optional date\n"
+    );
+    let doc = format!(":::\n{schema}:::\nmax-count: 5\nmin: 1\n");
+    assert_eq!(muon::check(&doc), []);
+    let renamed: Renamed = muon::from_str(&doc)?;
+    assert_eq!(renamed.max_count, 5);
+    Ok(())
+}
+
+#[derive(Deserialize, MuonSchema)]
+struct Limits {
+    #[serde(default)]
+    retries: u32,
+    #[serde(default = "max_size")]
+    size: u64,
+    #[serde(default)]
+    #[muon(default = "5")]
+    timeout: u32,
+    count: u32,
+}
+
+fn max_size() -> u64 {
+    4096
+}
+
+#[derive(Default, Deserialize, MuonSchema)]
+#[serde(default)]
+struct Window {
+    width: u32,
+    tags: Vec<String>,
+}
+
+#[test]
+fn serde_default() -> muon::Result<()> {
+    let schema = Limits::schema()?;
+    assert_eq!(
+        schema.to_string(),
+        "retries: optional int\nsize: optional int\ntimeout: int 5\n\
+         count: int\n"
+    );
+    let doc = format!(":::\n{schema}:::\ncount: 3\n");
+    assert_eq!(muon::check(&doc), []);
+    let doc = "retries: 1\nsize: 2\ntimeout: 3\ncount: 4\n";
+    let limits: Limits = muon::from_str(doc)?;
+    assert_eq!(
+        (limits.retries, limits.size, limits.timeout, limits.count),
+        (1, 2, 3, 4)
+    );
+    let schema = Window::schema()?;
+    assert_eq!(schema.to_string(), "width: optional int\ntags: list text\n");
+    let doc = format!(":::\n{schema}:::\nwidth: 80\n");
+    assert_eq!(muon::check(&doc), []);
+    let window: Window = muon::from_str(&doc)?;
+    assert_eq!(window.width, 80);
+    assert!(window.tags.is_empty());
+    Ok(())
+}
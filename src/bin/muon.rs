@@ -0,0 +1,293 @@
+// muon.rs      muon command-line tool.
+//
+// Copyright (c) 2019-2020  Douglas Lau
+//
+#![forbid(unsafe_code)]
+
+use muon_rs::{FormatOptions, SerializerOptions, Value};
+use serde_json::{Map, Value as Json};
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+
+/// Command usage
+const USAGE: &str = "\
+Usage: muon <COMMAND> [ARGS]
+
+Commands:
+  check FILE...      Check syntax and schema of MuON files
+  fmt [FILE]         Format MuON text
+  to-json [FILE]     Convert MuON to JSON
+  from-json [FILE]   Convert JSON to MuON
+  get PATH [FILE]    Get the value at a key path, such as `book[1].author`
+
+Input is read from stdin if FILE is `-` or missing.
+
+JSON arrays of arrays, and `null` array items, cannot be converted.  Strings
+in arrays of mixed types may be read back as another type, and keys missing
+from some objects in a list are read back as `null` (or `[]` for arrays).
+";
+
+/// Result type for commands
+type Result<T = (), E = Box<dyn Error>> = std::result::Result<T, E>;
+
+/// Read input from a file, or stdin
+fn read_input(file: Option<&str>) -> io::Result<String> {
+    match file {
+        Some(file) if file != "-" => fs::read_to_string(file),
+        _ => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            Ok(text)
+        }
+    }
+}
+
+/// Check MuON files, returning `false` if any problems are found
+fn check(files: &[String]) -> Result<bool> {
+    let mut ok = true;
+    for file in files {
+        match read_input(Some(file)) {
+            Ok(text) => {
+                for diagnostic in muon_rs::check(&text) {
+                    println!("{file}: {diagnostic}");
+                    ok = false;
+                }
+            }
+            Err(e) => {
+                eprintln!("{file}: {e}");
+                ok = false;
+            }
+        }
+    }
+    Ok(ok)
+}
+
+/// Format MuON text
+fn fmt(file: Option<&str>) -> Result {
+    let text = read_input(file)?;
+    let text = muon_rs::format(&text, FormatOptions::new())?;
+    io::stdout().write_all(text.as_bytes())?;
+    Ok(())
+}
+
+/// Convert a MuON value to JSON
+fn to_json(value: Value) -> Result<Json> {
+    Ok(match value {
+        Value::Record(record) => {
+            let mut map = Map::new();
+            for (key, value) in record {
+                let value = to_json(value)?;
+                // Repeated keys are collected into an array
+                match map.get_mut(&key) {
+                    Some(Json::Array(items)) => match value {
+                        Json::Array(more) => items.extend(more),
+                        value => items.push(value),
+                    },
+                    Some(prev) => {
+                        let items = vec![prev.take(), value];
+                        *prev = Json::Array(items);
+                    }
+                    None => {
+                        map.insert(key, value);
+                    }
+                }
+            }
+            Json::Object(map)
+        }
+        Value::Dictionary(dict) => {
+            let mut map = Map::new();
+            for (key, value) in dict {
+                let key = match to_json(key)? {
+                    Json::String(key) => key,
+                    key => key.to_string(),
+                };
+                map.insert(key, to_json(value)?);
+            }
+            Json::Object(map)
+        }
+        Value::List(list) => {
+            Json::Array(list.into_iter().map(to_json).collect::<Result<_>>()?)
+        }
+        Value::Any(value) | Value::Optional(Some(value)) => to_json(*value)?,
+        Value::Optional(None) => Json::Null,
+        value => serde_json::to_value(&value)?,
+    })
+}
+
+/// Convert MuON to JSON
+fn to_json_cmd(file: Option<&str>) -> Result {
+    let text = read_input(file)?;
+    let value: Value = muon_rs::from_str(&text)?;
+    let mut stdout = io::stdout();
+    serde_json::to_writer_pretty(&mut stdout, &to_json(value)?)?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
+/// Convert a JSON value to MuON
+///
+/// Objects are converted to records, so their keys are described by the
+/// schema.  Nested arrays and `null` array items cannot be represented.
+fn from_json(json: Json, item: bool) -> Result<Value> {
+    Ok(match json {
+        Json::Object(map) => Value::Record(
+            map.into_iter()
+                .map(|(key, value)| Ok((key, from_json(value, false)?)))
+                .collect::<Result<_>>()?,
+        ),
+        Json::Array(_) if item => {
+            return Err("nested JSON arrays cannot be converted".into())
+        }
+        Json::Array(items) => Value::List(
+            items
+                .into_iter()
+                .map(|value| from_json(value, true))
+                .collect::<Result<_>>()?,
+        ),
+        Json::Null if item => {
+            return Err("null JSON array items cannot be converted".into())
+        }
+        Json::Null => Value::Optional(None),
+        json => muon_rs::to_value(&json)?,
+    })
+}
+
+/// Convert JSON to MuON
+fn from_json_cmd(file: Option<&str>) -> Result {
+    let text = read_input(file)?;
+    let json: Json = serde_json::from_str(&text)?;
+    if !json.is_object() {
+        return Err("JSON value must be an object".into());
+    }
+    // Schema and no substitution, so types and keys are not lost
+    let options = SerializerOptions::new().schema(true).substitute(false);
+    let value = from_json(json, false)?;
+    io::stdout().write_all(options.to_string(&value)?.as_bytes())?;
+    Ok(())
+}
+
+/// Parse one key path segment, such as `book[1]`
+fn parse_segment(segment: &str) -> Result<(&str, Option<usize>)> {
+    match segment.strip_suffix(']').and_then(|s| s.split_once('[')) {
+        Some((name, index)) => Ok((name, Some(index.parse()?))),
+        None => Ok((segment, None)),
+    }
+}
+
+/// Collect the values of a key within a record (or list of records)
+fn collect(value: Value, name: &str, items: &mut Vec<Value>) {
+    match unwrap(value) {
+        Some(Value::Record(record)) => {
+            for (key, value) in record {
+                if key == name {
+                    match unwrap(value) {
+                        Some(Value::List(list)) => items.extend(list),
+                        Some(value) => items.push(value),
+                        None => (),
+                    }
+                }
+            }
+        }
+        Some(Value::List(list)) => {
+            for value in list {
+                collect(value, name, items);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Select the values of a key
+fn select(value: Value, name: &str, index: Option<usize>) -> Option<Value> {
+    let mut items = Vec::new();
+    collect(value, name, &mut items);
+    match index {
+        Some(i) => items.into_iter().nth(i),
+        None if items.len() > 1 => Some(Value::List(items)),
+        None => items.pop(),
+    }
+}
+
+/// Unwrap `any` and `optional` values
+fn unwrap(value: Value) -> Option<Value> {
+    match value {
+        Value::Any(value) | Value::Optional(Some(value)) => unwrap(*value),
+        Value::Optional(None) => None,
+        value => Some(value),
+    }
+}
+
+/// Write a value as text (scalars) or MuON (records)
+fn write_value<W: Write>(out: &mut W, value: Value) -> Result {
+    match value {
+        Value::Record(_) | Value::Dictionary(_) => {
+            out.write_all(muon_rs::to_string(&value)?.as_bytes())?
+        }
+        Value::List(list) => {
+            for value in list {
+                write_value(out, value)?;
+            }
+        }
+        value => match to_json(value)? {
+            Json::String(text) => writeln!(out, "{text}")?,
+            json => writeln!(out, "{json}")?,
+        },
+    }
+    Ok(())
+}
+
+/// Get the value at a key path, returning `false` if not found
+fn get(path: &str, file: Option<&str>) -> Result<bool> {
+    let text = read_input(file)?;
+    let mut value = Some(muon_rs::from_str(&text)?);
+    for segment in path.split('.') {
+        let (name, index) = parse_segment(segment)?;
+        value = value.and_then(|v| select(v, name, index));
+    }
+    match value {
+        Some(value) => {
+            write_value(&mut io::stdout(), value)?;
+            Ok(true)
+        }
+        None => {
+            eprintln!("muon: key path `{path}` not found");
+            Ok(false)
+        }
+    }
+}
+
+/// Run a command
+fn run(args: &[String]) -> Option<Result<bool>> {
+    let (cmd, args) = args.split_first()?;
+    let file = |n: usize| match args.len() {
+        len if len == n => Some(None),
+        len if len == n + 1 => Some(Some(args[n].as_str())),
+        _ => None,
+    };
+    Some(match cmd.as_str() {
+        "check" if !args.is_empty() => check(args),
+        "fmt" => fmt(file(0)?).map(|_| true),
+        "to-json" => to_json_cmd(file(0)?).map(|_| true),
+        "from-json" => from_json_cmd(file(0)?).map(|_| true),
+        "get" if !args.is_empty() => get(&args[0], file(1)?),
+        _ => return None,
+    })
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Some(Ok(true)) => ExitCode::SUCCESS,
+        Some(Ok(false)) => ExitCode::FAILURE,
+        Some(Err(e)) => {
+            eprintln!("muon: {e}");
+            ExitCode::FAILURE
+        }
+        None => {
+            eprint!("{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
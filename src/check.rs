@@ -0,0 +1,139 @@
+// check.rs
+//
+// Copyright (c) 2019-2020  Douglas Lau
+//
+use crate::error::{Location, ParseError};
+use crate::lines::DefIter;
+use crate::schema::{ValidationError, Validator};
+use std::fmt;
+
+/// Problem found while checking a MuON document
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// Parse error
+    error: ParseError,
+    /// Location in document
+    location: Location,
+}
+
+impl Diagnostic {
+    /// Create a new diagnostic
+    fn new(error: ParseError, location: Location) -> Self {
+        Diagnostic { error, location }
+    }
+
+    /// Get the parse error
+    pub fn error(&self) -> ParseError {
+        self.error
+    }
+
+    /// Get the location in the document
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+}
+
+impl From<ValidationError> for Diagnostic {
+    fn from(e: ValidationError) -> Self {
+        Diagnostic::new(e.error(), e.location().clone())
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{} at {}", self.error, self.location)
+    }
+}
+
+/// Check a MuON document, reporting every problem found
+///
+/// Parsing recovers at the next line after a malformed one.  If the document
+/// contains a schema, definitions are also checked against it.
+///
+/// # Example
+/// ```
+/// let muon = ":::\nname: text\nborn: int\n:::\nname Arthur\nborn: 1788s\n";
+/// let diagnostics = muon_rs::check(muon);
+/// assert_eq!(diagnostics.len(), 3);
+/// for diagnostic in diagnostics {
+///     println!("{diagnostic}");
+/// }
+/// ```
+pub fn check(text: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut defines = Vec::new();
+    let mut defs = DefIter::new(text);
+    while let Some(define) = defs.next() {
+        let position = defs.position().clone();
+        match define {
+            Ok(define) => defines.push((define, position)),
+            Err(e) => {
+                let location =
+                    position.location(position.key_offset(), "".into());
+                diagnostics.push(Diagnostic::new(e, location));
+            }
+        }
+    }
+    if let Some(schema) = defs.into_schema() {
+        let mut validator = Validator::new(&schema);
+        for (define, position) in &defines {
+            validator.check_define(define, position);
+        }
+        diagnostics
+            .extend(validator.finish().into_iter().map(Diagnostic::from));
+    }
+    diagnostics.sort_by_key(|d| d.location.line());
+    diagnostics
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn errors(text: &str) -> Vec<(usize, ParseError)> {
+        check(text)
+            .iter()
+            .map(|d| (d.location().line(), d.error()))
+            .collect()
+    }
+
+    #[test]
+    fn syntax() {
+        assert_eq!(errors("a: 1\nb: 2\n"), []);
+        assert_eq!(
+            errors("a: 1\nb 2\nc:\n     d: 3\n:::\ne:x\nf: 4"),
+            [
+                (2, ParseError::MissingSeparator),
+                (4, ParseError::InvalidIndent),
+                (5, ParseError::UnexpectedSchemaSeparator),
+                (6, ParseError::InvalidSeparator),
+                (7, ParseError::MissingLinefeed),
+            ]
+        );
+        assert_eq!(
+            errors("a: 1\n  b:\n      c: 2\n"),
+            [(3, ParseError::InvalidIndent)]
+        );
+    }
+
+    #[test]
+    fn schema() {
+        let text = ":::\nname: text\nscore: int\nitem: list record\n  \
+                    id: int\n:::\nname: first\nscore: five\nitem:\n  \
+                    id: x\n  other: 1\nitem:\n  id 5\n";
+        assert_eq!(
+            errors(text),
+            [
+                (8, ParseError::ExpectedInt),
+                (10, ParseError::ExpectedInt),
+                (11, ParseError::UnexpectedKey),
+                (12, ParseError::MissingField),
+                (13, ParseError::MissingSeparator),
+            ]
+        );
+        let diagnostics = check(text);
+        let location = diagnostics[1].location();
+        assert_eq!(location.column(), 7);
+        assert_eq!(location.key(), "item.id");
+    }
+}
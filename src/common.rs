@@ -2,10 +2,11 @@
 //
 // Copyright (c) 2019  Douglas Lau
 //
+use std::borrow::Cow;
 
 /// Key / value separator type
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub(crate) enum Separator {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Separator {
     /// Normal `: ` separator
     Normal,
     /// Text append `:>` separator
@@ -15,16 +16,16 @@ pub(crate) enum Separator {
 }
 
 /// Key / value definition
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub(crate) struct Define<'a> {
     /// Indent count
     pub(crate) indent: usize,
     /// Key for definition
-    pub(crate) key: &'a str,
+    pub(crate) key: Cow<'a, str>,
     /// Key / value separator
     pub(crate) separator: Separator,
     /// Value for definition
-    pub(crate) value: &'a str,
+    pub(crate) value: Cow<'a, str>,
 }
 
 impl Separator {
@@ -42,29 +43,37 @@ impl<'a> Define<'a> {
     /// Create a new definition
     pub(crate) fn new(
         indent: usize,
-        key: &'a str,
+        key: impl Into<Cow<'a, str>>,
         separator: Separator,
-        value: &'a str,
+        value: impl Into<Cow<'a, str>>,
     ) -> Self {
         Define {
             indent,
-            key,
+            key: key.into(),
             separator,
-            value,
+            value: value.into(),
         }
     }
 
     /// Split a definition for a list
     pub(crate) fn split_list(self) -> (Self, Option<Self>) {
-        let v: Vec<&str> = self.value.splitn(2, ' ').collect();
-        if v.len() == 1 {
-            (self, None)
-        } else {
-            (
-                Define::new(self.indent, self.key, self.separator, v[0]),
-                Some(Define::new(self.indent, self.key, self.separator, v[1])),
-            )
-        }
+        let (v0, v1): (Cow<str>, Cow<str>) = match &self.value {
+            Cow::Borrowed(value) => match value.split_once(' ') {
+                Some((v0, v1)) => (v0.into(), v1.into()),
+                None => return (self, None),
+            },
+            Cow::Owned(value) => match value.split_once(' ') {
+                Some((v0, v1)) => {
+                    (v0.to_string().into(), v1.to_string().into())
+                }
+                None => return (self, None),
+            },
+        };
+        let key = self.key.clone();
+        (
+            Define::new(self.indent, key, self.separator, v0),
+            Some(Define::new(self.indent, self.key, self.separator, v1)),
+        )
     }
 
     /// Check indent nesting
@@ -8,6 +8,15 @@ use serde::{de, ser};
 use std::fmt;
 use std::str::FromStr;
 
+/// Marker key for passing dates and times through `deserialize_any`
+pub(crate) const MARKER: &str = "$__muon_private_datetime";
+
+/// Newtype name for passing dates and times to and from a `Value`
+///
+/// Dates and times are serialized as text wrapped in this newtype, and a
+/// `Value` deserializes from it, accepting marker maps.
+pub(crate) const VALUE_NAME: &str = "$__muon_private_Value";
+
 /// Date and time with offset
 ///
 /// Formatted and validated as
@@ -19,7 +28,7 @@ use std::str::FromStr;
 /// let time = datetime.time();
 /// let offset = datetime.time_offset();
 /// ```
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct DateTime {
     date: Date,
     time: Time,
@@ -37,7 +46,7 @@ pub struct DateTime {
 /// let month = date.month();
 /// let day = date.day();
 /// ```
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Date {
     year: u16,
     month: u8,
@@ -56,7 +65,7 @@ pub struct Date {
 /// let second = time.second();
 /// let nanosecond = time.nanosecond();
 /// ```
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Time {
     hour: u8,
     minute: u8,
@@ -73,11 +82,11 @@ pub struct Time {
 /// let offset = "-05:00".parse::<TimeOffset>().unwrap();
 /// let seconds = offset.seconds();
 /// ```
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct TimeOffset(_TimeOffset);
 
 /// Private time offset
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 enum _TimeOffset {
     Z,
     Positive(u8, u8),
@@ -100,7 +109,8 @@ fn days_in_month(year: u16, month: u8) -> Option<u8> {
 
 /// Check if a year is a leap year
 fn is_leap_year(year: u16) -> bool {
-    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+    year.is_multiple_of(4)
+        && (!year.is_multiple_of(100) || year.is_multiple_of(400))
 }
 
 /// Convert ASCII digit to a number
@@ -232,7 +242,7 @@ impl ser::Serialize for DateTime {
     where
         S: ser::Serializer,
     {
-        serializer.serialize_str(&self.to_string())
+        serializer.serialize_newtype_struct(VALUE_NAME, &self.to_string())
     }
 }
 
@@ -321,7 +331,7 @@ impl ser::Serialize for Date {
     where
         S: ser::Serializer,
     {
-        serializer.serialize_str(&self.to_string())
+        serializer.serialize_newtype_struct(VALUE_NAME, &self.to_string())
     }
 }
 
@@ -413,7 +423,7 @@ impl ser::Serialize for Time {
     where
         S: ser::Serializer,
     {
-        serializer.serialize_str(&self.to_string())
+        serializer.serialize_newtype_struct(VALUE_NAME, &self.to_string())
     }
 }
 
@@ -529,7 +539,7 @@ impl TimeOffset {
     }
     /// Find possible index of a TimeOffset at the end of a byte slice
     fn rindex(bytes: &[u8]) -> usize {
-        const MAX: usize = std::usize::MAX;
+        const MAX: usize = usize::MAX;
         let len = bytes.len();
         match len {
             1..=MAX if bytes[len - 1] == b'Z' => len - 1,
@@ -3,16 +3,24 @@
 // Copyright (c) 2019-2020  Douglas Lau
 //
 use crate::common::{Define, Separator};
-use crate::error::{Error, ParseError, Result};
-use crate::lines::DefIter;
+use crate::datetime::{self, Date, DateTime, Time};
+use crate::error::{Error, Location, ParseError, Result};
+use crate::lines::{unquote, unquoted_key, DefIter, Position};
 use crate::parse::{self, Integer, Number};
+use crate::schema::{Modifier, Type, Value};
+use serde::de::value::{
+    BorrowedStrDeserializer, MapDeserializer, StringDeserializer,
+};
 use serde::de::{
-    self, Deserialize, DeserializeOwned, DeserializeSeed, MapAccess, SeqAccess,
-    Visitor,
+    self, Deserialize, DeserializeOwned, DeserializeSeed, EnumAccess,
+    IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor,
 };
 use std::borrow::Cow;
-use std::io::Read;
-use std::str;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::io::{BufReader, Read};
+use std::marker::PhantomData;
+use std::{iter, str};
 
 /// Branch state
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -27,22 +35,28 @@ enum BranchState {
 #[derive(Debug)]
 struct Branch<'a> {
     /// Field names
-    fields: &'static [&'static str],
+    fields: Vec<Cow<'a, str>>,
     /// Flags for visited fields (same length as fields)
     visited: Vec<bool>,
     /// Branch state
     state: BranchState,
     /// Current key (should match one field)
-    key: Option<&'a str>,
+    key: Option<Cow<'a, str>>,
     /// List flag (applies to current key)
     list: bool,
+    /// Index of current list element
+    index: usize,
     /// Substitute key
-    substitute: Option<&'a str>,
+    substitute: Option<Cow<'a, str>>,
+    /// Flag indicating the substitute field name is unknown
+    unnamed: bool,
+    /// Schema node (for nested branches)
+    node: Option<usize>,
 }
 
 impl<'a> Branch<'a> {
     /// Create a new Branch with fields
-    fn with_fields(fields: &'static [&'static str]) -> Self {
+    fn with_fields(fields: Vec<Cow<'a, str>>) -> Self {
         let visited = vec![false; fields.len()];
         Branch {
             fields,
@@ -50,39 +64,49 @@ impl<'a> Branch<'a> {
             state: BranchState::Visit,
             key: None,
             list: false,
+            index: 0,
             substitute: None,
+            unnamed: false,
+            node: None,
         }
     }
 
     /// Create a new Branch
     fn new() -> Self {
-        Branch::with_fields(&[])
+        Branch::with_fields(Vec::new())
+    }
+
+    /// Set the schema node
+    fn with_node(mut self, node: Option<usize>) -> Self {
+        self.node = node;
+        self
     }
 
     /// Get first field
-    fn first_field(&self) -> Option<&'a str> {
-        self.fields.first().copied()
+    fn first_field(&self) -> Option<Cow<'a, str>> {
+        self.fields.first().cloned()
     }
 
     /// Visit one field
-    fn visit(&mut self, key: Option<&'a str>) {
-        self.key = key;
-        if let Some(f) = key {
+    fn visit(&mut self, key: Option<Cow<'a, str>>) {
+        if let Some(f) = &key {
             for i in 0..self.fields.len() {
-                if self.fields[i] == f {
+                if self.fields[i] == *f {
                     self.visited[i] = true
                 }
             }
         }
+        self.key = key;
     }
 
     /// Cleanup state for one field
-    fn cleanup_visit(&mut self) -> Option<&'static str> {
+    fn cleanup_visit(&mut self) -> Option<Cow<'a, str>> {
         if self.state == BranchState::Cleanup {
             for i in 0..self.fields.len() {
                 if !self.visited[i] {
                     self.visited[i] = true;
-                    return Some(self.fields[i]);
+                    self.key = Some(self.fields[i].clone());
+                    return self.key.clone();
                 }
             }
         }
@@ -106,6 +130,16 @@ struct MappingIter<'a> {
     defs: DefIter<'a>,
     /// Current define
     define: Option<Result<Define<'a>, ParseError>>,
+    /// Defines following the current one, with line positions
+    lookahead: VecDeque<(Result<Define<'a>, ParseError>, Position<'a>)>,
+    /// Line position of current define
+    position: Position<'a>,
+    /// Line position of define before the current one
+    previous_position: Position<'a>,
+    /// Byte offset of most recent value
+    value: Option<usize>,
+    /// Flag indicating most recent value is at the previous position
+    value_previous: bool,
     /// Stack of nested branches
     stack: Vec<Branch<'a>>,
 }
@@ -115,7 +149,11 @@ impl<'a> Iterator for MappingIter<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.define.is_none() {
-            self.define = self.defs.next();
+            self.define = self.next_define();
+        }
+        if let Some(Ok(define)) = &self.define {
+            self.value = Some(self.position.value_offset(&define.value));
+            self.value_previous = false;
         }
         if self.is_list() {
             self.next_list()
@@ -127,41 +165,124 @@ impl<'a> Iterator for MappingIter<'a> {
 
 impl<'a> MappingIter<'a> {
     /// Create a new key/value mapping iterator
-    fn new(input: &'a str) -> Self {
-        let defs = DefIter::new(input);
-        let define = None;
-        let stack = Vec::new();
+    fn new(defs: DefIter<'a>) -> Self {
         MappingIter {
             defs,
-            define,
-            stack,
+            define: None,
+            lookahead: VecDeque::new(),
+            position: Position::default(),
+            previous_position: Position::default(),
+            value: None,
+            value_previous: false,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Get the next define from lookahead or definition iterator
+    fn next_define(&mut self) -> Option<Result<Define<'a>, ParseError>> {
+        let (define, position) = match self.lookahead.pop_front() {
+            Some((define, position)) => (Some(define), position),
+            None => {
+                let define = self.defs.next();
+                (define, self.defs.position().clone())
+            }
+        };
+        if self.value.is_some() && !self.value_previous {
+            // Keep position of the most recent value for errors
+            self.previous_position =
+                std::mem::replace(&mut self.position, position);
+            self.value_previous = true;
+        } else {
+            self.position = position;
         }
+        define
     }
 
     /// Peek at next define
-    fn peek(&mut self) -> Option<Result<Define<'a>, ParseError>> {
+    fn peek(&mut self) -> Result<Option<&Define<'a>>> {
         if self.define.is_none() {
-            self.define = self.defs.next();
+            self.define = self.next_define();
+        }
+        match &self.define {
+            Some(Ok(define)) => Ok(Some(define)),
+            Some(Err(e)) => Err(Error::from(*e)),
+            None => Ok(None),
         }
-        self.define
+    }
+
+    /// Read ahead until a lookahead define is available at an index
+    fn read_ahead(&mut self, i: usize) -> Result<bool> {
+        if self.peek()?.is_none() {
+            return Ok(false);
+        }
+        while self.lookahead.len() <= i {
+            match self.defs.next() {
+                Some(define) => {
+                    let position = self.defs.position().clone();
+                    self.lookahead.push_back((define, position));
+                }
+                None => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Peek at the define following the next one
+    fn peek_second(&mut self) -> Result<Option<&Define<'a>>> {
+        if !self.read_ahead(0)? {
+            return Ok(None);
+        }
+        match &self.lookahead[0] {
+            (Ok(define), _) => Ok(Some(define)),
+            (Err(e), position) => {
+                // Errors are located at the lookahead line
+                self.position = position.clone();
+                Err(Error::from(*e))
+            }
+        }
+    }
+
+    /// Check if the key of the next define is repeated after its children
+    ///
+    /// Errors are not checked here, so they are returned in line order.
+    fn is_repeated(&mut self) -> Result<bool> {
+        let (indent, key) = match self.peek()? {
+            Some(define) => (define.indent, unquoted_key(&define.key)),
+            None => return Ok(false),
+        };
+        let key = key.into_owned();
+        for i in 0.. {
+            if !self.read_ahead(i)? {
+                break;
+            }
+            match &self.lookahead[i].0 {
+                Ok(define) if define.indent > indent => (),
+                Ok(define) if define.separator == Separator::TextAppend => (),
+                Ok(define) => {
+                    return Ok(define.indent == indent
+                        && unquoted_key(&define.key) == key);
+                }
+                Err(_) => break,
+            }
+        }
+        Ok(false)
     }
 
     /// Get the next define in a list
     fn next_list(&mut self) -> Option<Result<Define<'a>, ParseError>> {
-        if let Some(Ok(define)) = self.define {
-            match define.separator {
+        match self.define.take() {
+            Some(Ok(define)) => match define.separator {
                 Separator::Normal => {
                     let (d0, d1) = define.split_list();
-                    self.define = Ok(d1).transpose();
+                    self.define = d1.map(Ok);
                     Some(Ok(d0))
                 }
-                _ => {
-                    self.define = None;
-                    Some(Ok(define))
-                }
+                _ => Some(Ok(define)),
+            },
+            define => {
+                self.define = define;
+                self.define.clone()
             }
-        } else {
-            self.define
         }
     }
 
@@ -177,36 +298,122 @@ impl<'a> MappingIter<'a> {
 
     /// Check record substitute
     fn check_substitute(&mut self) -> Result {
+        match self.stack.last().and_then(Branch::first_field) {
+            Some(key) => self.substitute(key),
+            None => self.skip_empty_record(),
+        }
+        Ok(())
+    }
+
+    /// Skip the key of a nested record with no fields
+    fn skip_empty_record(&mut self) {
+        let indent = self.stack.len();
+        if let Some(Ok(define)) = &self.define {
+            if define.value.is_empty()
+                && indent > 1
+                && define.check_indent(indent - 1)
+            {
+                self.define = None;
+            }
+        }
+    }
+
+    /// Substitute value of current define for a key in the top branch
+    fn substitute(&mut self, key: Cow<'a, str>) {
         let indent = self.stack.len();
         if let Some(branch) = self.stack.last_mut() {
-            if let Some(key) = branch.first_field() {
-                if let Some(Ok(define)) = self.define.take() {
-                    if !define.value.is_empty() && indent > 0 {
-                        branch.substitute = Some(key);
-                        self.define = Some(Ok(Define::new(
-                            indent - 1,
-                            key,
-                            define.separator,
-                            define.value,
-                        )))
-                    }
+            if let Some(Ok(define)) = self.define.take() {
+                if !define.value.is_empty() && indent > 0 {
+                    branch.substitute = Some(key.clone());
+                    self.define = Some(Ok(Define::new(
+                        indent - 1,
+                        key,
+                        define.separator,
+                        define.value,
+                    )))
                 }
             }
         }
-        Ok(())
+    }
+
+    /// Substitute value of current define for an unnamed first field
+    fn substitute_unnamed(&mut self, key: Cow<'a, str>) {
+        self.substitute(key);
+        if let Some(branch) = self.stack.last_mut() {
+            branch.unnamed = branch.substitute.is_some();
+        }
+    }
+
+    /// Take the unnamed flag of the top branch
+    fn take_unnamed(&mut self) -> bool {
+        self.stack
+            .last_mut()
+            .is_some_and(|branch| std::mem::take(&mut branch.unnamed))
     }
 
     /// Set the current key on stack
-    fn set_key(&mut self, key: Option<&'a str>) {
+    fn set_key(&mut self, key: Option<Cow<'a, str>>) {
         if let Some(branch) = self.stack.last_mut() {
             branch.visit(key)
         }
     }
-
     /// Set the top of stack to a list
     fn set_list(&mut self, list: bool) {
         if let Some(branch) = self.stack.last_mut() {
             branch.list = list;
+            branch.index = 0;
+        }
+    }
+
+    /// Advance to the next list element
+    fn next_index(&mut self) {
+        if let Some(branch) = self.stack.last_mut() {
+            branch.index += 1;
+        }
+    }
+
+    /// Get the key path of the current define
+    fn key_path(&self) -> String {
+        branch_path(&self.stack)
+    }
+
+    /// Get the key path of the most recent line, if it was not read
+    fn line_key_path(&self) -> String {
+        match self.defs.line_define() {
+            Some(define) => {
+                let n = define.indent.min(self.stack.len());
+                let mut path = branch_path(&self.stack[..n]);
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(&unquoted_key(&define.key));
+                path
+            }
+            None => self.key_path(),
+        }
+    }
+
+    /// Get the location of an error
+    fn location(&self, e: &Error) -> Location {
+        let key = match e.parse_error() {
+            // The line with a missing line feed is never read as a define
+            Some(ParseError::MissingLinefeed) => self.line_key_path(),
+            _ => self.key_path(),
+        };
+        let position = if self.value_previous {
+            &self.previous_position
+        } else {
+            &self.position
+        };
+        match (e.parse_error(), self.value) {
+            // Malformed values are located at the value itself
+            (Some(pe), Some(offset)) if pe.is_value() => {
+                position.location(offset, key)
+            }
+            // Other errors from serde happen after the value is consumed
+            (None, Some(_)) => position.location(position.key_offset(), key),
+            (Some(pe), _) => self.position.error_location(pe, key),
+            _ => self.position.location(self.position.key_offset(), key),
         }
     }
 
@@ -218,10 +425,16 @@ impl<'a> MappingIter<'a> {
         }
     }
 
+    /// Check if the current field of the top branch is substitute
+    fn is_substitute(&self) -> bool {
+        self.stack.last().is_some_and(Branch::is_substitute)
+    }
+
     /// Check whether indent nesting matches
     fn check_indent(&mut self) -> Result<bool> {
-        match self.peek().transpose().map_err(Error::FailedParse)? {
-            Some(define) => Ok(define.check_indent(self.stack.len())),
+        let indent = self.stack.len();
+        match self.peek()? {
+            Some(define) => Ok(define.check_indent(indent)),
             None => Ok(false),
         }
     }
@@ -249,12 +462,13 @@ impl<'a> MappingIter<'a> {
 
     /// Check that key matches
     fn check_key(&mut self) -> Result<bool> {
-        if let Some(branch) = self.stack.last() {
-            if let Some(k) = branch.key {
-                if let Some(define) =
-                    self.peek().transpose().map_err(Error::FailedParse)?
+        if let Some(Branch { key: Some(_), .. }) = self.stack.last() {
+            if self.peek()?.is_some() {
+                if let (Some(branch), Some(Ok(define))) =
+                    (self.stack.last(), &self.define)
                 {
-                    return Ok(define.key == k);
+                    let key = unquoted_key(&define.key);
+                    return Ok(branch.key.as_deref() == Some(&*key));
                 }
             }
         }
@@ -268,7 +482,7 @@ impl<'a> MappingIter<'a> {
 
     /// Check if separator is a text append
     fn is_separator_text_append(&mut self) -> Result<bool> {
-        match self.peek().transpose().map_err(Error::FailedParse)? {
+        match self.peek()? {
             Some(define) => Ok(define.separator == Separator::TextAppend),
             _ => Ok(false),
         }
@@ -278,19 +492,324 @@ impl<'a> MappingIter<'a> {
     fn is_text_append(&mut self) -> Result<bool> {
         Ok(self.is_append()? && self.is_separator_text_append()?)
     }
+
+    /// Skip top-level defines (and nested defines) not matching a key
+    fn skip_other_keys(&mut self, key: &str) -> Result {
+        while let Some(define) = self.peek()? {
+            if define.indent == 0 && unquoted_key(&define.key) == key {
+                break;
+            }
+            self.define = None;
+        }
+        Ok(())
+    }
+
+    /// Check if the next define starts a record (has nested defines)
+    fn is_record(&mut self) -> Result<bool> {
+        let indent = match self.peek()? {
+            Some(define) => define.indent,
+            None => return Ok(false),
+        };
+        match self.peek_second()? {
+            Some(second) => Ok(second.indent == indent + 1),
+            None => Ok(false),
+        }
+    }
+}
+
+/// Get the key path of a stack of branches
+fn branch_path(stack: &[Branch]) -> String {
+    let mut path = String::new();
+    for branch in stack {
+        if let Some(key) = &branch.key {
+            if !path.is_empty() {
+                path.push('.');
+            }
+            path.push_str(key);
+            if branch.list {
+                let _ = write!(path, "[{}]", branch.index);
+            }
+        }
+    }
+    path
+}
+
+/// Options for deserializing MuON
+///
+/// # Example
+/// ```
+/// # use serde::Deserialize;
+/// use muon_rs::DeserializerOptions;
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Person {
+///     name: String,
+///     born: u32,
+/// }
+/// #[derive(Debug, Deserialize)]
+/// struct People {
+///     person: Vec<Person>,
+/// }
+/// let muon = "person: Arthur Schopenhauer\n\tborn: 1788\n";
+/// assert!(muon_rs::from_str::<People>(muon).is_err());
+/// let options = DeserializerOptions::new().tab_width(2);
+/// let people: People = options.from_str(muon).unwrap();
+/// assert_eq!(people.person[0].born, 1788);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DeserializerOptions {
+    /// Number of spaces for each tab in an indent
+    tab_width: Option<usize>,
+}
+
+impl DeserializerOptions {
+    /// Create default deserializer options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of spaces (1 to 8) for each tab in an indent
+    ///
+    /// This allows reading legacy files indented with tabs, which are
+    /// otherwise rejected with [`ParseError::InvalidIndentChar`].  Other
+    /// values are clamped to that range.
+    ///
+    /// If the first indented line is indented with one tab, each tab is one
+    /// indent level, whatever the width.  Otherwise, tabs are expanded to
+    /// the width, so they can be mixed with spaces.
+    pub fn tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = Some(tab_width.clamp(1, 8));
+        self
+    }
+
+    /// Deserialize `T` from a string slice containing MuON
+    ///
+    /// # Errors
+    ///
+    /// See [`from_str`].
+    pub fn from_str<'a, T>(self, s: &'a str) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        Deserializer::with_options(s, self).deserialize_document()
+    }
+
+    /// Deserialize `T` from a byte slice containing MuON
+    ///
+    /// # Errors
+    ///
+    /// See [`from_slice`].
+    pub fn from_slice<'a, T>(self, v: &'a [u8]) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        self.from_str(str::from_utf8(v)?)
+    }
+
+    /// Deserialize `T` from a reader IO stream containing MuON
+    ///
+    /// # Errors
+    ///
+    /// See [`from_reader`].
+    pub fn from_reader<R, T>(self, reader: R) -> Result<T>
+    where
+        R: Read,
+        T: DeserializeOwned,
+    {
+        Deserializer::from_reader_with_options(reader, self)
+            .deserialize_document()
+    }
 }
 
 /// Structure that can deserialize MuON into values.
 pub struct Deserializer<'de> {
     /// Iterator over key/value mappings
     mappings: MappingIter<'de>,
+    /// Flag indicating the visitor is for a `Value`
+    value_visitor: bool,
 }
 
 impl<'de> Deserializer<'de> {
     /// Create a Deserializer from a string slice
-    fn new(input: &'de str) -> Self {
-        let mappings = MappingIter::new(input);
-        Deserializer { mappings }
+    pub fn new(input: &'de str) -> Self {
+        Deserializer::with_options(input, DeserializerOptions::default())
+    }
+
+    /// Create a Deserializer from a string slice, with options
+    pub fn with_options(input: &'de str, options: DeserializerOptions) -> Self {
+        Deserializer::with_defs(DefIter::new(input), options)
+    }
+
+    /// Create a Deserializer from a reader IO stream
+    ///
+    /// The reader is wrapped with a [`std::io::BufReader`] internally.
+    ///
+    /// [`std::io::BufReader`]: https://doc.rust-lang.org/std/io/struct.BufReader.html
+    pub fn from_reader<R: Read + 'de>(reader: R) -> Self {
+        let options = DeserializerOptions::default();
+        Deserializer::from_reader_with_options(reader, options)
+    }
+
+    /// Create a Deserializer from a reader IO stream, with options
+    ///
+    /// The reader is wrapped with a [`std::io::BufReader`] internally.
+    ///
+    /// [`std::io::BufReader`]: https://doc.rust-lang.org/std/io/struct.BufReader.html
+    pub fn from_reader_with_options<R: Read + 'de>(
+        reader: R,
+        options: DeserializerOptions,
+    ) -> Self {
+        let reader = BufReader::new(reader);
+        Deserializer::with_defs(DefIter::from_reader(reader), options)
+    }
+
+    /// Create a Deserializer from a definition iterator
+    fn with_defs(mut defs: DefIter<'de>, options: DeserializerOptions) -> Self {
+        defs.set_tab_width(options.tab_width);
+        let mappings = MappingIter::new(defs);
+        Deserializer {
+            mappings,
+            value_visitor: false,
+        }
+    }
+}
+
+/// Iterator that deserializes values of one repeated top-level key
+///
+/// Each definition of the key is deserialized into a `T`, without collecting
+/// the whole list.  Iteration stops after the first error.
+///
+/// Use [`StreamDeserializer::with_key`] to skip any other top-level keys in
+/// the document.
+///
+/// # Example
+/// ```
+/// # use serde::Deserialize;
+/// # use std::fs::File;
+/// use muon_rs::{Deserializer, StreamDeserializer};
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Book {
+///     title: String,
+///     author: String,
+///     year: Option<i16>,
+///     character: Vec<Character>,
+/// }
+///
+/// #[derive(Debug, Deserialize)]
+/// struct Character {
+///     name: String,
+///     location: Option<String>,
+/// }
+///
+/// # fn main() -> Result<(), muon_rs::Error> {
+/// let muon = File::open("tests/books.muon")?;
+/// let books = StreamDeserializer::new(Deserializer::from_reader(muon));
+/// for book in books {
+///     let book: Book = book?;
+///     println!("{:?}", book);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct StreamDeserializer<'de, T> {
+    /// Wrapped deserializer
+    de: Deserializer<'de>,
+    /// Top-level key to deserialize (`None` for first key)
+    key: Option<String>,
+    /// Flag indicating iteration is done
+    done: bool,
+    /// Output type
+    output: PhantomData<T>,
+}
+
+impl<'de, T> StreamDeserializer<'de, T>
+where
+    T: Deserialize<'de>,
+{
+    /// Create a new stream deserializer
+    ///
+    /// # Example
+    /// ```
+    /// # use serde::Deserialize;
+    /// use muon_rs::{Deserializer, StreamDeserializer};
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// struct Person {
+    ///     name: String,
+    ///     born: u32,
+    /// }
+    /// let muon = "person: Arthur Schopenhauer\n  born: 1788\n\
+    ///             person: Immanuel Kant\n  born: 1724\n";
+    /// let people = StreamDeserializer::new(Deserializer::new(muon));
+    /// for person in people {
+    ///     let person: Person = person.unwrap();
+    ///     println!("{:?}", person);
+    /// }
+    /// ```
+    pub fn new(de: Deserializer<'de>) -> Self {
+        StreamDeserializer {
+            de,
+            key: None,
+            done: false,
+            output: PhantomData,
+        }
+    }
+
+    /// Create a new stream deserializer for one top-level key
+    ///
+    /// Definitions of any other top-level keys are skipped.
+    ///
+    /// # Example
+    /// ```
+    /// # use serde::Deserialize;
+    /// use muon_rs::{Deserializer, StreamDeserializer};
+    ///
+    /// #[derive(Debug, Deserialize)]
+    /// struct Person {
+    ///     name: String,
+    ///     born: u32,
+    /// }
+    /// let muon = "title: Philosophers\n\
+    ///             person: Arthur Schopenhauer\n  born: 1788\n\
+    ///             place: Danzig\n\
+    ///             person: Immanuel Kant\n  born: 1724\n";
+    /// let de = Deserializer::new(muon);
+    /// let people = StreamDeserializer::<Person>::with_key(de, "person");
+    /// assert_eq!(people.count(), 2);
+    /// ```
+    pub fn with_key(de: Deserializer<'de>, key: &str) -> Self {
+        StreamDeserializer {
+            de,
+            key: Some(key.to_string()),
+            done: false,
+            output: PhantomData,
+        }
+    }
+}
+
+impl<'de, T> Iterator for StreamDeserializer<'de, T>
+where
+    T: Deserialize<'de>,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.de.next_element(self.key.as_deref());
+        match self.de.locate(result) {
+            Ok(Some(value)) => Some(Ok(value)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
     }
 }
 
@@ -322,9 +841,7 @@ pub fn from_str<'a, T>(s: &'a str) -> Result<T>
 where
     T: Deserialize<'a>,
 {
-    let mut deserializer = Deserializer::new(s);
-    let t = T::deserialize(&mut deserializer)?;
-    Ok(t)
+    Deserializer::new(s).deserialize_document()
 }
 
 /// Deserialize `T` from a byte slice containing MuON
@@ -361,8 +878,9 @@ where
 
 /// Deserialize `T` from a reader IO stream containing MuON
 ///
-/// This may call many short reads, so wrapping the reader with
-/// [`std::io::BufReader`].
+/// Input is read one line at a time, so memory use depends on line length
+/// and nesting depth rather than the size of the whole document.  The reader
+/// is wrapped with a [`std::io::BufReader`] internally.
 ///
 /// [`std::io::BufReader`]: https://doc.rust-lang.org/std/io/struct.BufReader.html
 ///
@@ -406,122 +924,575 @@ where
 /// * The structure of the MuON data does not match the structure of `T`
 /// * A required field is missing
 /// * A value is too big to fit within a primitive defined by `T`
-pub fn from_reader<R, T>(mut reader: R) -> Result<T>
+pub fn from_reader<R, T>(reader: R) -> Result<T>
 where
     R: Read,
     T: DeserializeOwned,
 {
-    // FIXME: this should be optimized
-    let mut s = String::new();
-    reader.read_to_string(&mut s)?;
-    from_str(&s)
+    Deserializer::from_reader(reader).deserialize_document()
 }
 
-// FIXME: add a from_value function
-
 impl<'de> Deserializer<'de> {
-    /// Parse a define into a result
-    fn define_result(
-        &self,
-        define: Option<Result<Define<'de>, ParseError>>,
-    ) -> Result<Define<'de>> {
-        match define {
-            Some(Ok(define)) => Ok(define),
-            Some(Err(e)) => Err(Error::FailedParse(e)),
-            None => Err(Error::FailedParse(ParseError::ExpectedMore)),
+    /// Deserialize a document, adding location to any error
+    fn deserialize_document<T>(&mut self) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        let result = T::deserialize(&mut *self);
+        self.locate(result)
+    }
+
+    /// Deserialize the next element of a repeated top-level key
+    ///
+    /// If `key` is `None`, the first top-level key is used.
+    fn next_element<T>(&mut self, key: Option<&str>) -> Result<Option<T>>
+    where
+        T: Deserialize<'de>,
+    {
+        if self.mappings.stack.is_empty() {
+            // Peek to make sure schema has been parsed
+            self.mappings.peek()?;
+            self.mappings.push_stack(Branch::new());
+            self.mappings.set_list(true);
+        } else {
+            self.mappings.next_index();
+        }
+        if let Some(key) = key {
+            self.mappings.skip_other_keys(key)?;
         }
+        if !self.mappings.check_indent()? {
+            return Ok(None);
+        }
+        let key = self.peek_key()?;
+        if let Some(branch) = self.mappings.stack.last() {
+            if branch.key.as_ref().is_some_and(|k| *k != key) {
+                return Err(Error::from(ParseError::UnexpectedKey));
+            }
+        }
+        self.mappings.set_key(Some(key));
+        T::deserialize(&mut *self).map(Some)
     }
 
-    /// Peek the current key
-    fn peek_key(&mut self) -> Result<&'de str> {
-        let def = self.mappings.peek();
-        Ok(self.define_result(def)?.key)
+    /// Check for I/O errors and add location to a result
+    fn locate<T>(&mut self, result: Result<T>) -> Result<T> {
+        if let Some(e) = self.mappings.defs.take_error() {
+            return Err(e);
+        }
+        result.map_err(|e| {
+            let location = self.mappings.location(&e);
+            e.located(location)
+        })
+    }
+
+    /// Peek at the current define
+    fn peek_define(&mut self) -> Result<&Define<'de>> {
+        self.mappings
+            .peek()?
+            .ok_or(Error::from(ParseError::ExpectedMore))
+    }
+
+    /// Peek the current key (without quotes)
+    fn peek_key(&mut self) -> Result<Cow<'de, str>> {
+        Ok(unquote(self.peek_define()?.key.clone()))
     }
 
     /// Get the current value
-    fn get_value(&mut self) -> Result<&'de str> {
-        let def = self.mappings.next();
-        Ok(self.define_result(def)?.value)
+    fn get_value(&mut self) -> Result<Cow<'de, str>> {
+        if self.mappings.branch_state() == BranchState::Cleanup {
+            return Err(Error::from(ParseError::MissingField));
+        }
+        match self.mappings.next() {
+            Some(Ok(define)) => Ok(define.value),
+            Some(Err(e)) => Err(Error::from(e)),
+            None => Err(Error::from(ParseError::ExpectedMore)),
+        }
+    }
+
+    /// Parse a text value
+    fn parse_text(&mut self) -> Result<Cow<'de, str>> {
+        let val = self.get_value()?;
+        if !self.mappings.is_text_append()? {
+            return Ok(val);
+        }
+        // Allocate a buffer if multiple lines of text
+        let mut value = val.into_owned();
+        while self.mappings.is_text_append()? {
+            value.push('\n');
+            value.push_str(&self.get_value()?);
+        }
+        Ok(Cow::Owned(value))
+    }
+
+    /// Parse a bytes value
+    fn parse_bytes(&mut self) -> Result<Vec<u8>> {
+        parse::bytes(&self.parse_text()?)
+            .ok_or(Error::from(ParseError::ExpectedBytes))
+    }
+
+    /// Parse a char (`text <=1 >=1`) value
+    fn parse_char(&mut self) -> Result<char> {
+        let val = self.get_value()?;
+        // Check if char is newline
+        if self.mappings.is_text_append()? && val.is_empty() {
+            let val = self.get_value()?;
+            // Make sure no more newlines and line is empty
+            (!self.mappings.is_text_append()? && val.is_empty())
+                .then_some(())
+                .ok_or(Error::from(ParseError::ExpectedChar))?;
+            return Ok('\n');
+        }
+        // Don't allow more than one line if not newline
+        (!self.mappings.is_text_append()?)
+            .then_some(())
+            .ok_or(Error::from(ParseError::ExpectedChar))?;
+        parse::char(&val).ok_or(Error::from(ParseError::ExpectedChar))
+    }
+
+    /// Parse a bool value
+    fn parse_bool(&mut self) -> Result<bool> {
+        parse::bool(&self.get_value()?)
+            .ok_or(Error::from(ParseError::ExpectedBool))
+    }
+
+    /// Parse an int value
+    fn parse_int<T: Integer>(&mut self) -> Result<T> {
+        parse::int(&self.get_value()?)
+            .ok_or(Error::from(ParseError::ExpectedInt))
+    }
+
+    /// Parse a number value
+    fn parse_number<T: Number>(&mut self) -> Result<T> {
+        parse::number(&self.get_value()?)
+            .ok_or(Error::from(ParseError::ExpectedNumber))
+    }
+
+    /// Get the next key (or unvisited field during cleanup)
+    fn next_key(&mut self) -> Result<Cow<'de, str>> {
+        if let Some(branch) = self.mappings.stack.last_mut() {
+            if let Some(field) = branch.cleanup_visit() {
+                return Ok(field);
+            }
+        }
+        let key = self.peek_key()?;
+        self.mappings.set_key(Some(key.clone()));
+        Ok(key)
+    }
+
+    /// Start a map, consuming the define for its key (if not root)
+    fn start_map(&mut self, branch: Branch<'de>) {
+        if !self.mappings.stack.is_empty() {
+            self.mappings.next();
+        }
+        self.mappings.push_stack(branch);
+    }
+
+    /// Find the schema node for the current key
+    ///
+    /// Returns `None` if there is no schema, or if the key is not described
+    /// within a dictionary or `any` node.
+    fn schema_node(&self) -> Result<Option<usize>> {
+        let schema = match self.mappings.defs.schema() {
+            Some(schema) => schema,
+            None => return Ok(None),
+        };
+        let stack = &self.mappings.stack;
+        let (branch, key) = match stack.last() {
+            Some(branch) => match &branch.key {
+                Some(key) => (branch, key),
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+        // Only the root branch has no parent node
+        let parent = match (stack.len(), branch.node) {
+            (1, _) => None,
+            (_, Some(node)) => Some(node),
+            (_, None) => return Ok(None),
+        };
+        match schema.child(parent, key) {
+            Some(node) => Ok(Some(node)),
+            None => match parent.map(|p| schema.node(p).node_type()) {
+                Some(Type::Dictionary) | Some(Type::Any) => Ok(None),
+                _ => Err(Error::from(ParseError::UnexpectedKey)),
+            },
+        }
+    }
+
+    /// Get the schema default value for a missing field
+    fn default_value(&self) -> Result<Option<Value>> {
+        if self.mappings.branch_state() != BranchState::Cleanup {
+            return Ok(None);
+        }
+        let default = match (self.schema_node()?, self.mappings.defs.schema()) {
+            (Some(node), Some(schema)) => schema.node(node).default().cloned(),
+            _ => None,
+        };
+        Ok(default)
+    }
+
+    /// Deserialize the root record
+    fn deserialize_root<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // Peek to make sure schema has been parsed
+        let _ = self.mappings.peek();
+        let fields = match self.mappings.defs.schema() {
+            Some(schema) => schema.child_names(None),
+            None => Vec::new(),
+        };
+        self.mappings.push_stack(Branch::with_fields(fields));
+        visitor.visit_map(self)
+    }
+
+    /// Deserialize a value described by a schema node
+    fn deserialize_node<V>(
+        &mut self,
+        node: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let modifier = match self.mappings.defs.schema() {
+            Some(schema) => schema.node(node).modifier(),
+            None => None,
+        };
+        let cleanup = self.mappings.branch_state() == BranchState::Cleanup;
+        match modifier {
+            Some(Modifier::Optional) if cleanup => visitor.visit_none(),
+            Some(Modifier::Optional) => {
+                visitor.visit_some(NodeDeserializer { de: self, node })
+            }
+            Some(Modifier::List) if cleanup || !self.mappings.is_list() => {
+                de::Deserializer::deserialize_seq(self, visitor)
+            }
+            _ if cleanup => match self.default_value()? {
+                Some(value) => {
+                    de::Deserializer::deserialize_any(value, visitor)
+                }
+                None => Err(Error::from(ParseError::MissingField)),
+            },
+            _ => self.deserialize_type(node, visitor),
+        }
+    }
+
+    /// Deserialize a value by schema node type (ignoring modifier)
+    fn deserialize_type<V>(
+        &mut self,
+        node: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let (node_type, fields) = match self.mappings.defs.schema() {
+            Some(schema) => (
+                schema.node(node).node_type(),
+                schema.child_names(Some(node)),
+            ),
+            None => return self.deserialize_inferred(None, visitor),
+        };
+        match node_type {
+            Type::Text => de::Deserializer::deserialize_str(self, visitor),
+            Type::Bool => de::Deserializer::deserialize_bool(self, visitor),
+            Type::Int => {
+                let val = self.get_value()?;
+                self.visit_int(&val, visitor)
+            }
+            Type::Number => de::Deserializer::deserialize_f64(self, visitor),
+            Type::DateTime => {
+                let val = self.get_value()?;
+                val.parse::<DateTime>().map_err(Error::from)?;
+                self.visit_datetime(val, visitor)
+            }
+            Type::Date => {
+                let val = self.get_value()?;
+                val.parse::<Date>().map_err(Error::from)?;
+                self.visit_datetime(val, visitor)
+            }
+            Type::Time => {
+                let val = self.get_value()?;
+                val.parse::<Time>().map_err(Error::from)?;
+                self.visit_datetime(val, visitor)
+            }
+            Type::Record => {
+                let branch = Branch::with_fields(fields);
+                self.mappings.push_stack(branch.with_node(Some(node)));
+                self.mappings.check_substitute()?;
+                visitor.visit_map(self)
+            }
+            Type::Dictionary => {
+                self.start_map(Branch::new().with_node(Some(node)));
+                visitor.visit_map(self)
+            }
+            Type::Any => self.deserialize_inferred(Some(node), visitor),
+        }
+    }
+
+    /// Deserialize a value, inferring its type
+    fn deserialize_inferred<V>(
+        &mut self,
+        node: Option<usize>,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.mappings.branch_state() == BranchState::Cleanup {
+            // Missing values have no type to infer
+            return visitor.visit_unit();
+        }
+        if node.is_none()
+            && !self.mappings.is_list()
+            && !self.mappings.is_substitute()
+            && self.mappings.is_repeated()?
+        {
+            // Without a schema, repeated keys are a list
+            return de::Deserializer::deserialize_seq(self, visitor);
+        }
+        let define = self.peek_define()?;
+        let key = unquote(define.key.clone());
+        let separator = define.separator;
+        if self.mappings.is_record()? {
+            // No field names are known, so a Value substitutes using the
+            // record key, and other visitors get the first field index
+            self.mappings.push_stack(Branch::new().with_node(node));
+            if self.value_visitor {
+                self.mappings.substitute(key);
+            } else {
+                self.mappings.substitute_unnamed(key);
+            }
+            return visitor.visit_map(self);
+        }
+        if separator == Separator::TextValue {
+            return de::Deserializer::deserialize_str(self, visitor);
+        }
+        let val = self.parse_text()?;
+        if val.contains('\n') {
+            // Appended text is never inferred as another type
+            visitor.visit_str(&val)
+        } else if let Some(v) = parse::bool(&val) {
+            visitor.visit_bool(v)
+        } else if parse::int::<i128>(&val).is_some()
+            || parse::int::<u128>(&val).is_some()
+        {
+            self.visit_int(&val, visitor)
+        } else if let Some(v) = parse::number(&val) {
+            visitor.visit_f64(v)
+        } else if val.parse::<DateTime>().is_ok()
+            || val.parse::<Date>().is_ok()
+            || val.parse::<Time>().is_ok()
+        {
+            self.visit_datetime(val, visitor)
+        } else {
+            match val {
+                Cow::Borrowed(val) => visitor.visit_borrowed_str(val),
+                Cow::Owned(val) => visitor.visit_string(val),
+            }
+        }
+    }
+
+    /// Visit an int value with the smallest fitting type
+    fn visit_int<V>(&mut self, val: &str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if let Some(v) = parse::int(val) {
+            visitor.visit_i64(v)
+        } else if let Some(v) = parse::int(val) {
+            visitor.visit_u64(v)
+        } else if let Some(v) = parse::int(val) {
+            visitor.visit_i128(v)
+        } else if let Some(v) = parse::int(val) {
+            visitor.visit_u128(v)
+        } else {
+            Err(Error::from(ParseError::ExpectedInt))
+        }
+    }
+
+    /// Visit a date / time value
+    fn visit_datetime<V>(
+        &mut self,
+        val: Cow<'de, str>,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if std::mem::take(&mut self.value_visitor) {
+            let marker = iter::once((datetime::MARKER, val));
+            visitor.visit_map(MapDeserializer::new(marker))
+        } else {
+            match val {
+                Cow::Borrowed(val) => visitor.visit_borrowed_str(val),
+                Cow::Owned(val) => visitor.visit_string(val),
+            }
+        }
+    }
+}
+
+/// Deserializer for a schema node after its modifier has been handled
+struct NodeDeserializer<'a, 'de> {
+    /// Parent deserializer
+    de: &'a mut Deserializer<'de>,
+    /// Schema node
+    node: usize,
+}
+
+impl<'de> de::Deserializer<'de> for NodeDeserializer<'_, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.deserialize_type(self.node, visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+/// Deserializer for a map key
+///
+/// Keys are parsed in the same way as values of the requested type.
+struct KeyDeserializer<'de> {
+    /// Key text
+    key: Cow<'de, str>,
+    /// Flag indicating key is a substituted field with unknown name
+    unnamed: bool,
+}
+
+macro_rules! deserialize_key {
+    ($($method:ident $visit:ident $parse:ident $err:ident),* $(,)?) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                visitor.$visit(
+                    parse::$parse(&self.key)
+                        .ok_or(Error::from(ParseError::$err))?,
+                )
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.unnamed {
+            // Field identifiers also accept an index
+            return visitor.visit_u64(0);
+        }
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
     }
 
-    /// Parse a text value
-    fn parse_text(&mut self) -> Result<Cow<'de, str>> {
-        let mut val = self.get_value()?;
-        let mut value = String::new();
-        // Allocate a buffer if multiple lines of text
-        while self.mappings.is_text_append()? {
-            value.push_str(val);
-            value.push('\n');
-            val = self.get_value()?;
-        }
-        if value.is_empty() {
-            Ok(Cow::Borrowed(val))
-        } else {
-            value.push_str(val);
-            Ok(Cow::Owned(value))
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.key {
+            Cow::Borrowed(key) => visitor.visit_borrowed_str(key),
+            Cow::Owned(key) => visitor.visit_string(key),
         }
     }
 
-    /// Parse a char (`text <=1 >=1`) value
-    fn parse_char(&mut self) -> Result<char> {
-        let val = self.get_value()?;
-        // Check if char is newline
-        if self.mappings.is_text_append()? && val.is_empty() {
-            let val = self.get_value()?;
-            // Make sure no more newlines and line is empty
-            (!self.mappings.is_text_append()? && val.is_empty())
-                .then_some(())
-                .ok_or(Error::FailedParse(ParseError::ExpectedChar))?;
-            return Ok('\n');
-        }
-        // Don't allow more than one line if not newline
-        (!self.mappings.is_text_append()?)
-            .then_some(())
-            .ok_or(Error::FailedParse(ParseError::ExpectedChar))?;
-        parse::char(val).ok_or(Error::FailedParse(ParseError::ExpectedChar))
+    deserialize_key! {
+        deserialize_bool visit_bool bool ExpectedBool,
+        deserialize_i8 visit_i8 int ExpectedInt,
+        deserialize_i16 visit_i16 int ExpectedInt,
+        deserialize_i32 visit_i32 int ExpectedInt,
+        deserialize_i64 visit_i64 int ExpectedInt,
+        deserialize_i128 visit_i128 int ExpectedInt,
+        deserialize_u8 visit_u8 int ExpectedInt,
+        deserialize_u16 visit_u16 int ExpectedInt,
+        deserialize_u32 visit_u32 int ExpectedInt,
+        deserialize_u64 visit_u64 int ExpectedInt,
+        deserialize_u128 visit_u128 int ExpectedInt,
+        deserialize_f32 visit_f32 number ExpectedNumber,
+        deserialize_f64 visit_f64 number ExpectedNumber,
+        deserialize_char visit_char char ExpectedChar,
+        deserialize_bytes visit_byte_buf bytes ExpectedBytes,
+        deserialize_byte_buf visit_byte_buf bytes ExpectedBytes,
     }
 
-    /// Parse a bool value
-    fn parse_bool(&mut self) -> Result<bool> {
-        parse::bool(self.get_value()?)
-            .ok_or(Error::FailedParse(ParseError::ExpectedBool))
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
     }
 
-    /// Parse an int value
-    fn parse_int<T: Integer>(&mut self) -> Result<T> {
-        parse::int(self.get_value()?)
-            .ok_or(Error::FailedParse(ParseError::ExpectedInt))
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // Only unit variants can be keys
+        match self.key {
+            Cow::Borrowed(key) => {
+                visitor.visit_enum(BorrowedStrDeserializer::new(key))
+            }
+            Cow::Owned(key) => visitor.visit_enum(key.into_deserializer()),
+        }
     }
 
-    /// Parse a number value
-    fn parse_number<T: Number>(&mut self) -> Result<T> {
-        parse::number(self.get_value()?)
-            .ok_or(Error::FailedParse(ParseError::ExpectedNumber))
+    serde::forward_to_deserialize_any! {
+        option unit unit_struct seq tuple tuple_struct map struct identifier
+        ignored_any
     }
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_identifier(visitor)?;
-        if let Some(schema) = self.mappings.defs.schema() {
-            dbg!(&schema);
-            dbg!(&self.mappings.stack);
-            dbg!(&self.mappings.define);
-            todo!("create Value from schema");
+        if self.mappings.stack.is_empty() {
+            return self.deserialize_root(visitor);
+        }
+        match self.schema_node()? {
+            Some(node) => self.deserialize_node(node, visitor),
+            None => self.deserialize_inferred(None, visitor),
         }
-        Err(Error::FailedParse(ParseError::UnexpectedKey))
     }
 
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_any(visitor)
+        // Only keys described by a schema can be ignored
+        match self.schema_node()? {
+            Some(_) => self.deserialize_any(visitor),
+            None => Err(Error::from(ParseError::UnexpectedKey)),
+        }
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
@@ -625,21 +1596,24 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        self.deserialize_str(visitor)
+        de::Deserializer::deserialize_str(self, visitor)
     }
 
-    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(Error::UnsupportedType("bytes"))
+        de::Deserializer::deserialize_byte_buf(self, visitor)
     }
 
-    fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(Error::UnsupportedType("byte_buf"))
+        if self.mappings.branch_state() == BranchState::Cleanup {
+            return visitor.visit_byte_buf(vec![]);
+        }
+        visitor.visit_byte_buf(self.parse_bytes()?)
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
@@ -651,7 +1625,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
                 return visitor.visit_none();
             }
             if branch.is_substitute() {
-                return Err(Error::FailedParse(ParseError::InvalidSubstitute));
+                return Err(Error::from(ParseError::InvalidSubstitute));
             }
         }
         visitor.visit_some(self)
@@ -677,13 +1651,21 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
     fn deserialize_newtype_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        if name == datetime::VALUE_NAME {
+            // Only a Value visitor can handle dates in a marker map
+            self.value_visitor = true;
+            let value = de::Deserializer::deserialize_any(&mut *self, visitor);
+            self.value_visitor = false;
+            value
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
@@ -693,7 +1675,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         self.mappings.set_list(true);
         if let Some(branch) = self.mappings.stack.last() {
             if branch.is_substitute() {
-                return Err(Error::FailedParse(ParseError::InvalidSubstitute));
+                return Err(Error::from(ParseError::InvalidSubstitute));
             }
         }
         visitor.visit_seq(self)
@@ -703,7 +1685,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        de::Deserializer::deserialize_seq(self, visitor)
     }
 
     fn deserialize_tuple_struct<V>(
@@ -715,15 +1697,15 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        de::Deserializer::deserialize_seq(self, visitor)
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.mappings.next();
-        self.mappings.push_stack(Branch::new());
+        let node = self.schema_node()?;
+        self.start_map(Branch::new().with_node(node));
         visitor.visit_map(self)
     }
 
@@ -736,7 +1718,10 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        self.mappings.push_stack(Branch::with_fields(fields));
+        let node = self.schema_node()?;
+        let fields = fields.iter().map(|f| Cow::Borrowed(*f)).collect();
+        let branch = Branch::with_fields(fields);
+        self.mappings.push_stack(branch.with_node(node));
         self.mappings.check_substitute()?;
         visitor.visit_map(self)
     }
@@ -745,26 +1730,44 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         self,
         _name: &'static str,
         _variants: &'static [&'static str],
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(Error::UnsupportedType("enum"))
+        if !self.mappings.stack.is_empty() {
+            if self.mappings.branch_state() == BranchState::Cleanup {
+                return Err(Error::from(ParseError::MissingField));
+            }
+            // Unit variants are text values
+            if !self.peek_define()?.value.is_empty() {
+                return match self.parse_text()? {
+                    Cow::Owned(val) => {
+                        visitor.visit_enum(val.into_deserializer())
+                    }
+                    Cow::Borrowed(val) => {
+                        visitor.visit_enum(BorrowedStrDeserializer::new(val))
+                    }
+                };
+            }
+        }
+        // Other variants are records with one key (the variant name)
+        self.start_map(Branch::new());
+        let value = visitor.visit_enum(&mut *self)?;
+        if self.mappings.check_branch_done()? {
+            self.mappings.pop_stack();
+            Ok(value)
+        } else {
+            Err(Error::from(ParseError::UnexpectedKey))
+        }
     }
 
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        if let Some(branch) = self.mappings.stack.last_mut() {
-            if let Some(field) = branch.cleanup_visit() {
-                return visitor.visit_borrowed_str(field);
-            }
-        }
-        let key = self.peek_key()?;
-        self.mappings.set_key(Some(key));
-        visitor.visit_borrowed_str(key)
+        // Keys are deserialized separately; this is for identifier values
+        de::Deserializer::deserialize_str(self, visitor)
     }
 }
 
@@ -776,7 +1779,9 @@ impl<'de> SeqAccess<'de> for Deserializer<'de> {
         T: DeserializeSeed<'de>,
     {
         if self.mappings.is_append()? {
-            seed.deserialize(&mut *self).map(Some)
+            let value = seed.deserialize(&mut *self)?;
+            self.mappings.next_index();
+            Ok(Some(value))
         } else {
             self.mappings.set_list(false);
             self.mappings.set_key(None);
@@ -785,6 +1790,60 @@ impl<'de> SeqAccess<'de> for Deserializer<'de> {
     }
 }
 
+impl<'de> EnumAccess<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = match self.next_key()? {
+            Cow::Borrowed(key) => {
+                seed.deserialize(BorrowedStrDeserializer::<Error>::new(key))?
+            }
+            Cow::Owned(key) => {
+                seed.deserialize(StringDeserializer::<Error>::new(key))?
+            }
+        };
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        self.mappings.next();
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
+
 impl<'de> MapAccess<'de> for Deserializer<'de> {
     type Error = Error;
 
@@ -796,7 +1855,9 @@ impl<'de> MapAccess<'de> for Deserializer<'de> {
             self.mappings.pop_stack();
             Ok(None)
         } else {
-            seed.deserialize(&mut *self).map(Some)
+            let key = self.next_key()?;
+            let unnamed = self.mappings.take_unnamed();
+            seed.deserialize(KeyDeserializer { key, unnamed }).map(Some)
         }
     }
 
@@ -804,14 +1865,23 @@ impl<'de> MapAccess<'de> for Deserializer<'de> {
     where
         V: DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut *self)
+        match self.default_value()? {
+            Some(value) => seed.deserialize(value),
+            None => seed.deserialize(&mut *self),
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::super::datetime::*;
-    use super::{from_str, Error, ParseError};
+    use super::super::schema::{IntValue, NumValue, Value};
+    use super::{
+        from_reader, from_slice, from_str, Deserializer, DeserializerOptions,
+        Error, ParseError, StreamDeserializer,
+    };
+    use crate::error::Result;
+    use crate::error::{Category, ErrorKind};
     use serde::Deserialize;
     use std::collections::HashMap;
 
@@ -862,6 +1932,49 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn line_endings() -> Result<(), Box<Error>> {
+        let expected = Person {
+            name: "Arthur".to_string(),
+            score: 5,
+        };
+        let data = "\u{FEFF}name: Arthur\r\nscore: 5\r\n";
+        assert_eq!(expected, from_str(data)?);
+        assert_eq!(expected, from_reader(data.as_bytes())?);
+        let e = from_str::<Person>("name: Arthur\rscore: 5\n").unwrap_err();
+        assert_eq!(e.parse_error(), Some(ParseError::InvalidLineEnding));
+        assert_eq!(e.location().unwrap().column(), 13);
+        Ok(())
+    }
+
+    #[test]
+    fn tab_width() -> Result<(), Box<Error>> {
+        let data = "person:\n\tname: Arthur\n\tscore: 5\n";
+        let e = from_str::<People>(data).unwrap_err();
+        assert_eq!(e.parse_error(), Some(ParseError::InvalidIndentChar(1)));
+        assert_eq!(e.location().unwrap().column(), 1);
+        let expected = People {
+            person: vec![Person {
+                name: "Arthur".to_string(),
+                score: 5,
+            }],
+        };
+        let options = DeserializerOptions::new().tab_width(4);
+        assert_eq!(expected, options.from_str(data)?);
+        assert_eq!(expected, options.from_reader(data.as_bytes())?);
+        for width in [1, 5, 8] {
+            let options = DeserializerOptions::new().tab_width(width);
+            assert_eq!(expected, options.from_str(data)?);
+        }
+        // Tabs mixed with spaces
+        let data = "person:\n    name: Arthur\n\tscore: 5\n";
+        let options = DeserializerOptions::new().tab_width(4);
+        assert_eq!(expected, options.from_str(data)?);
+        let options = DeserializerOptions::new().tab_width(8);
+        assert!(options.from_str::<People>(data).is_err());
+        Ok(())
+    }
+
     #[test]
     fn lists() -> Result<(), Box<Error>> {
         #[derive(Deserialize, PartialEq, Debug)]
@@ -906,13 +2019,13 @@ mod test {
         let data = "float: 1e15\ndouble: inf\n";
         let expected = Data {
             float: 1e15,
-            double: std::f64::INFINITY,
+            double: f64::INFINITY,
         };
         assert_eq!(expected, from_str(data)?);
         let data = "float: 8_765.432\ndouble: -inf\n";
         let expected = Data {
             float: 8_765.432,
-            double: std::f64::NEG_INFINITY,
+            double: f64::NEG_INFINITY,
         };
         assert_eq!(expected, from_str(data)?);
         let data = "float: 123_.456\ndouble: 1.0\n";
@@ -951,8 +2064,11 @@ mod test {
         assert_eq!(expected, from_str(data)?);
         let data = "nested:\n  flag: false\n  wrapper:\n    int: 321\n";
         assert_eq!(expected, from_str(data)?);
-        match from_str::<Nested>("wrapper: 223344\n  int: 55\n").unwrap_err() {
-            Error::Deserialize(_) => Ok(()),
+        match from_str::<Nested>("wrapper: 223344\n  int: 55\n")
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::Deserialize(_) => Ok(()),
             r => panic!("bad error {r:?}"),
         }
     }
@@ -971,8 +2087,8 @@ mod test {
         let expected = Data { char: '\n' };
         assert_eq!(expected, from_str(data)?);
         let data = "char: ç\n    :>append some junk\n";
-        match from_str::<Data>(data).unwrap_err() {
-            Error::FailedParse(ParseError::ExpectedChar) => Ok(()),
+        match from_str::<Data>(data).unwrap_err().kind() {
+            ErrorKind::FailedParse(ParseError::ExpectedChar) => Ok(()),
             r => panic!("bad result: {r:?}"),
         }
     }
@@ -991,8 +2107,11 @@ mod test {
                 .to_string(),
         };
         assert_eq!(expected, from_str(data)?);
-        match from_str::<Data>("string: test\njunk: stuff\n").unwrap_err() {
-            Error::FailedParse(ParseError::UnexpectedKey) => Ok(()),
+        match from_str::<Data>("string: test\njunk: stuff\n")
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::FailedParse(ParseError::UnexpectedKey) => Ok(()),
             r => panic!("bad result: {r:?}"),
         }
     }
@@ -1075,12 +2194,77 @@ mod test {
     }
 
     #[test]
-    #[ignore] // FIXME: Test does not pass
+    fn defaults() -> Result<(), Box<Error>> {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Server {
+            host: String,
+            port: u16,
+            secure: bool,
+            timeout: Option<f32>,
+        }
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            name: String,
+            server: Vec<Server>,
+        }
+
+        let config = ":::\nname: text unnamed\nserver: list record\n  \
+                      host: text localhost\n  port: int 8080\n  \
+                      secure: bool false\n  timeout: number 2.5\n:::\n\
+                      server: example.com\n  port: 443\n  secure: true\n\
+                      server:\n  timeout: 10\n";
+        assert_eq!(
+            from_str::<Config>(config)?,
+            Config {
+                name: "unnamed".to_string(),
+                server: vec![
+                    Server {
+                        host: "example.com".to_string(),
+                        port: 443,
+                        secure: true,
+                        timeout: Some(2.5),
+                    },
+                    Server {
+                        host: "localhost".to_string(),
+                        port: 8080,
+                        secure: false,
+                        timeout: Some(10.0),
+                    },
+                ],
+            }
+        );
+        let value: Value = from_str(
+            ":::\nname: text\nport: int 8080\n:::\n\
+                                     name: default port\n",
+        )?;
+        assert_eq!(
+            value,
+            Value::Record(vec![
+                ("name".to_string(), text("default port")),
+                ("port".to_string(), int(8080)),
+            ])
+        );
+        let err = from_str::<Config>(":::\nname: text\n:::\nserver: a\n")
+            .unwrap_err();
+        assert_eq!(err.parse_error(), Some(ParseError::UnexpectedKey));
+        match from_str::<Config>(
+            ":::\nserver: list record\n  port: int\n\
+                                  :::\nserver:\n  host: a\n",
+        )
+        .unwrap_err()
+        .kind()
+        {
+            ErrorKind::FailedParse(ParseError::MissingField) => Ok(()),
+            r => panic!("bad error {r:?}"),
+        }
+    }
+
+    #[test]
     fn record_bad() -> Result<(), Box<Error>> {
         let people = "person:\n  name: Genghis Khan\n\
                       person:\n  name: Josef Stalin\n  score: 250\n";
-        match from_str::<People>(people).unwrap_err() {
-            Error::FailedParse(ParseError::MissingField) => Ok(()),
+        match from_str::<People>(people).unwrap_err().kind() {
+            ErrorKind::FailedParse(ParseError::MissingField) => Ok(()),
             r => panic!("bad error {r:?}"),
         }
     }
@@ -1095,11 +2279,11 @@ mod test {
             datetime: DateTime,
         }
 
-        let date = "2019-08-07".parse().map_err(Error::FailedParse)?;
-        let time = "12:34:56.789".parse().map_err(Error::FailedParse)?;
+        let date = "2019-08-07".parse().map_err(Error::from)?;
+        let time = "12:34:56.789".parse().map_err(Error::from)?;
         let datetime = "1999-12-31T23:59:59.999-00:00"
             .parse()
-            .map_err(Error::FailedParse)?;
+            .map_err(Error::from)?;
         let expected = Data {
             name: "one day".to_string(),
             date,
@@ -1166,8 +2350,8 @@ mod test {
             from_str("thing:\n  name: X\n  id: 1\nthing:\n  id: 2\n")?,
         );
         let data = "thing: X\n  id: 1\nthing:\n  id: 2\n";
-        match from_str::<Data>(data).unwrap_err() {
-            Error::FailedParse(ParseError::InvalidSubstitute) => Ok(()),
+        match from_str::<Data>(data).unwrap_err().kind() {
+            ErrorKind::FailedParse(ParseError::InvalidSubstitute) => Ok(()),
             r => panic!("bad error {r:?}"),
         }
     }
@@ -1189,6 +2373,29 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn empty_record() -> Result<(), Box<Error>> {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Empty {}
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data {
+            empty: Vec<Empty>,
+            other: u32,
+        }
+
+        let data = Data {
+            empty: vec![Empty {}, Empty {}],
+            other: 15,
+        };
+        assert_eq!(data, from_str("empty:\nempty:\nother: 15\n")?);
+        let value: Value =
+            from_str(":::\na: record\n  b: record\n:::\na:\n  b:\n")?;
+        let b = Value::Record(vec![]);
+        let a = Value::Record(vec![("b".to_string(), b)]);
+        assert_eq!(value, Value::Record(vec![("a".to_string(), a)]));
+        Ok(())
+    }
+
     #[test]
     fn substitute_optional_record() -> Result<(), Box<Error>> {
         #[derive(Debug, Deserialize, PartialEq)]
@@ -1225,8 +2432,8 @@ mod test {
             chan: Vec<Strings>,
         }
 
-        match from_str::<Data>("chan: first second\n").unwrap_err() {
-            Error::FailedParse(ParseError::InvalidSubstitute) => Ok(()),
+        match from_str::<Data>("chan: first second\n").unwrap_err().kind() {
+            ErrorKind::FailedParse(ParseError::InvalidSubstitute) => Ok(()),
             r => panic!("bad error {r:?}"),
         }
     }
@@ -1243,14 +2450,13 @@ mod test {
             num: Number,
         }
 
-        match from_str::<Data>("num: 42\n").unwrap_err() {
-            Error::FailedParse(ParseError::InvalidSubstitute) => Ok(()),
+        match from_str::<Data>("num: 42\n").unwrap_err().kind() {
+            ErrorKind::FailedParse(ParseError::InvalidSubstitute) => Ok(()),
             r => panic!("bad error {r:?}"),
         }
     }
 
     #[test]
-    #[ignore] // FIXME: Test does not pass
     fn hashmap_dict() -> Result<(), Box<Error>> {
         #[derive(Debug, Deserialize, PartialEq)]
         struct Data {
@@ -1294,4 +2500,402 @@ mod test {
         assert_eq!(data, from_str("name: test\nname:\nname: TEST\n")?);
         Ok(())
     }
+
+    #[test]
+    fn locations() -> Result<(), Box<Error>> {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Character {
+            name: String,
+            location: Option<u32>,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Book {
+            title: String,
+            character: Vec<Character>,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct BookList {
+            book: Vec<Book>,
+        }
+
+        let data = "book: Pale Fire\n  character: John Shade\n\
+                    book: Flight\n  character: Zits\n  character: Justice\n\
+                    \x20   location: Seattle\n";
+        let err = from_str::<BookList>(data).unwrap_err();
+        let loc = err.location().unwrap();
+        assert_eq!(loc.line(), 6);
+        assert_eq!(loc.column(), 15);
+        assert_eq!(loc.key(), "book[1].character[1].location");
+        assert_eq!(loc.snippet(), "    location: Seattle");
+        assert_eq!(
+            err.to_string(),
+            "expected int at line 6, column 15, \
+             key `book[1].character[1].location`\n\
+             6 |     location: Seattle\n  |               ^"
+        );
+        let err = from_str::<BookList>("book: a\n   character: b\n  x: 1\n")
+            .unwrap_err();
+        match err.kind() {
+            ErrorKind::FailedParse(ParseError::InvalidIndent) => (),
+            r => panic!("bad error {r:?}"),
+        }
+        let loc = err.location().unwrap();
+        assert_eq!((loc.line(), loc.column()), (3, 3));
+        let data = "book: Pale Fire\n  character: John Shade\n\
+                    \x20   location: 7";
+        for err in [
+            from_str::<BookList>(data).unwrap_err(),
+            from_reader::<_, BookList>(data.as_bytes()).unwrap_err(),
+        ] {
+            assert_eq!(err.parse_error(), Some(ParseError::MissingLinefeed));
+            let loc = err.location().unwrap();
+            assert_eq!(loc.line(), 3);
+            assert_eq!(loc.key(), "book[0].character[0].location");
+        }
+        let data = "person: Ann\n  score: 1\nperson: Bob\n";
+        let err = from_str::<People>(data).unwrap_err();
+        assert_eq!(err.parse_error(), Some(ParseError::MissingField));
+        let loc = err.location().unwrap();
+        assert_eq!((loc.line(), loc.key()), (3, "person[1].score"));
+        assert_eq!(loc.snippet(), "person: Bob");
+        Ok(())
+    }
+
+    #[test]
+    fn locations_consumed() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(untagged)]
+        enum Setting {
+            Flag(bool),
+            Count(i64),
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data {
+            s: Setting,
+            u: String,
+        }
+
+        let err = from_str::<Data>("s: x\nu: x\n").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Deserialize(_)));
+        let loc = err.location().unwrap();
+        assert_eq!((loc.line(), loc.column()), (1, 1));
+        assert_eq!(loc.key(), "s");
+        assert_eq!(loc.snippet(), "s: x");
+        let err = from_str::<Data>("u: y\ns: x\n").unwrap_err();
+        let loc = err.location().unwrap();
+        assert_eq!((loc.line(), loc.key()), (2, "s"));
+        assert_eq!(loc.snippet(), "s: x");
+    }
+
+    #[test]
+    fn categories() {
+        let err = from_str::<Wrapper>("int: five\n").unwrap_err();
+        assert_eq!(err.category(), Category::Data);
+        assert_eq!(err.parse_error(), Some(ParseError::ExpectedInt));
+        let err = from_str::<Wrapper>("int 5\n").unwrap_err();
+        assert!(err.is_syntax());
+        let err = from_str::<Wrapper>("int: 5").unwrap_err();
+        assert!(err.is_eof());
+        let err = from_str::<Wrapper>("value: 5\n").unwrap_err();
+        assert!(err.is_data());
+        assert_eq!(err.parse_error(), Some(ParseError::UnexpectedKey));
+        let err = Error::from(ErrorKind::UnsupportedType("bytes"));
+        assert_eq!(err.category(), Category::Unsupported);
+        assert_eq!(err.parse_error(), None);
+        let err = Error::from(std::fmt::Error);
+        assert_eq!(err.category(), Category::Data);
+        let err = from_slice::<Wrapper>(b"int: \xff\n").unwrap_err();
+        assert!(err.is_syntax());
+        let err = Error::from(String::from_utf8(vec![0xff]).unwrap_err());
+        assert!(err.is_syntax());
+    }
+
+    #[test]
+    fn stream() -> Result<(), Box<Error>> {
+        let data = "person: Genghis\n  score: 500\n\
+                    person: Josephine\n  score: 420\n";
+        let people: Vec<Person> =
+            StreamDeserializer::new(Deserializer::new(data))
+                .collect::<Result<_>>()?;
+        assert_eq!(people, from_str::<People>(data)?.person);
+        let de = Deserializer::from_reader(data.as_bytes());
+        let people: Vec<Person> =
+            StreamDeserializer::new(de).collect::<Result<_>>()?;
+        assert_eq!(people.len(), 2);
+        let mut it = StreamDeserializer::<Person>::new(Deserializer::new(""));
+        assert!(it.next().is_none());
+        let data = ":::\nperson: list record\n  name: text\n  \
+                    score: int\n:::\nperson: Ann\n  score: 1\n\
+                    person: Bob\n  score: x\nperson: Cy\n  score: 3\n";
+        let mut it = StreamDeserializer::<Person>::new(Deserializer::new(data));
+        assert_eq!(it.next().unwrap()?.name, "Ann");
+        let err = it.next().unwrap().unwrap_err();
+        let loc = err.location().unwrap();
+        assert_eq!((loc.line(), loc.key()), (9, "person[1].score"));
+        assert!(it.next().is_none());
+        let data = "person: Ann\n  score: 1\nplace: Here\n";
+        let mut it = StreamDeserializer::<Person>::new(Deserializer::new(data));
+        assert!(it.next().unwrap().is_ok());
+        let err = it.next().unwrap().unwrap_err();
+        assert_eq!(err.parse_error(), Some(ParseError::UnexpectedKey));
+        let data = "place: Here\n  person: Nobody\nperson: Ann\n  score: 1\n\
+                    notes: one\n     :>two\nperson: Bob\n  score: x\n";
+        let de = Deserializer::new(data);
+        let mut it = StreamDeserializer::<Person>::with_key(de, "person");
+        assert_eq!(it.next().unwrap()?.name, "Ann");
+        let err = it.next().unwrap().unwrap_err();
+        let loc = err.location().unwrap();
+        assert_eq!((loc.line(), loc.key()), (8, "person[1].score"));
+        assert!(it.next().is_none());
+        let de = Deserializer::new("place: Here\n");
+        let mut it = StreamDeserializer::<Person>::with_key(de, "person");
+        assert!(it.next().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn reader() -> Result<(), Box<Error>> {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Data {
+            name: String,
+            notes: String,
+            value: Value,
+        }
+        let data = "name: Arthur\nnotes: one\n     :>two\n\
+                    value: text\n  score: 5\n";
+        let d: Data = from_reader(data.as_bytes())?;
+        assert_eq!(d.name, "Arthur");
+        assert_eq!(d.notes, "one\ntwo");
+        let value = Value::Record(Vec::from([
+            ("value".to_string(), text("text")),
+            ("score".to_string(), int(5)),
+        ]));
+        assert_eq!(d.value, value);
+        let people: People =
+            from_reader(&b"person: Genghis\n  score: 500\n"[..])?;
+        assert_eq!(people.person[0].name, "Genghis");
+        let err =
+            from_reader::<_, People>(&b"person: Genghis\n  score: x\n"[..])
+                .unwrap_err();
+        let loc = err.location().unwrap();
+        assert_eq!((loc.line(), loc.column()), (2, 10));
+        assert_eq!(loc.key(), "person[0].score");
+        let err = from_reader::<_, Wrapper>(&b"int: 5"[..]).unwrap_err();
+        assert!(err.is_eof());
+        let err = from_reader::<_, Wrapper>(&b"int: \xff\n"[..]).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Utf8(_)));
+        let err = from_slice::<Wrapper>(b"int: \xff\n").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Utf8(_)));
+        Ok(())
+    }
+
+    fn text(v: &str) -> Value {
+        Value::Text(v.to_string())
+    }
+
+    fn int(v: i128) -> Value {
+        Value::Int(IntValue::Signed(v))
+    }
+
+    #[test]
+    fn value_schema() -> Result<(), Box<Error>> {
+        let data = ":::\nname: text\nscore: int\nflag: optional bool\n\
+                    tag: list text\nwhen: date\nitem: list record\n  id: int\n\
+                    \x20 note: optional text\n:::\nname: Some Name\nscore: -5\n\
+                    tag: a b\nwhen: 2019-08-07\nitem: 1\n  note: first\n\
+                    item:\n  id: 2\n";
+        let expected = Value::Record(Vec::from([
+            ("name".to_string(), text("Some Name")),
+            ("score".to_string(), int(-5)),
+            (
+                "tag".to_string(),
+                Value::List(Vec::from([text("a"), text("b")])),
+            ),
+            (
+                "when".to_string(),
+                Value::Date("2019-08-07".parse().map_err(Error::from)?),
+            ),
+            (
+                "item".to_string(),
+                Value::List(Vec::from([
+                    Value::Record(Vec::from([
+                        ("id".to_string(), int(1)),
+                        (
+                            "note".to_string(),
+                            Value::Optional(Some(Box::new(text("first")))),
+                        ),
+                    ])),
+                    Value::Record(Vec::from([
+                        ("id".to_string(), int(2)),
+                        ("note".to_string(), Value::Optional(None)),
+                    ])),
+                ])),
+            ),
+            ("flag".to_string(), Value::Optional(None)),
+        ]));
+        assert_eq!(expected, from_str(data)?);
+        match from_str::<Value>(":::\na: int\n:::\nb: 5\n")
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::FailedParse(ParseError::UnexpectedKey) => (),
+            r => panic!("bad error {r:?}"),
+        }
+        match from_str::<Value>(":::\na: int\nb: int\n:::\nb: 5\n")
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::FailedParse(ParseError::MissingField) => Ok(()),
+            r => panic!("bad error {r:?}"),
+        }
+    }
+
+    #[test]
+    fn value_inferred() -> Result<(), Box<Error>> {
+        let data = "flag: true\nint: x1F\nnum: 2.5\ntime: 12:34:56\n\
+                    text: some text\nbook: Pale Fire\n  year: 1962\n\
+                    empty:\n";
+        let expected = Value::Record(Vec::from([
+            ("flag".to_string(), Value::Bool(true)),
+            ("int".to_string(), int(31)),
+            ("num".to_string(), Value::Number(NumValue::Num64(2.5))),
+            (
+                "time".to_string(),
+                Value::Time("12:34:56".parse().map_err(Error::from)?),
+            ),
+            ("text".to_string(), text("some text")),
+            (
+                "book".to_string(),
+                Value::Record(Vec::from([
+                    ("book".to_string(), text("Pale Fire")),
+                    ("year".to_string(), int(1962)),
+                ])),
+            ),
+            ("empty".to_string(), text("")),
+        ]));
+        assert_eq!(expected, from_str(data)?);
+        let map: HashMap<String, Value> = from_str("a: 1\nb:=2\n")?;
+        assert_eq!(map.get("a"), Some(&int(1)));
+        assert_eq!(map.get("b"), Some(&text("2")));
+        let list = Value::List(Vec::from([int(1), int(2), int(3), int(4)]));
+        let expected = Value::Record(Vec::from([
+            ("a".to_string(), list.clone()),
+            ("b".to_string(), text("x y")),
+        ]));
+        assert_eq!(expected, from_str("a: 1 2 3\na: 4\nb: x y\n")?);
+        let map: HashMap<String, Value> = from_str("a: 1 2 3\na: 4\n")?;
+        assert_eq!(map.get("a"), Some(&list));
+        let expected = Value::Record(Vec::from([(
+            "r".to_string(),
+            Value::List(Vec::from([
+                Value::Record(Vec::from([("b".to_string(), int(1))])),
+                Value::Record(Vec::from([
+                    ("r".to_string(), text("x")),
+                    ("b".to_string(), int(2)),
+                ])),
+            ])),
+        )]));
+        assert_eq!(expected, from_str("r:\n  b: 1\nr: x\n  b: 2\n")?);
+        let err = from_str::<Value>("z: 0\n  b:\n      c: 2\n").unwrap_err();
+        assert_eq!(err.parse_error(), Some(ParseError::InvalidIndent));
+        let loc = err.location().unwrap();
+        assert_eq!((loc.line(), loc.column()), (3, 7));
+        Ok(())
+    }
+
+    #[test]
+    fn untagged() -> Result<(), Box<Error>> {
+        #[derive(Debug, Deserialize, PartialEq)]
+        #[serde(untagged)]
+        enum Setting {
+            Flag(bool),
+            Count(i64),
+            Name(String),
+            Size { width: f64, height: f64 },
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data {
+            a: Setting,
+            b: Setting,
+            c: Setting,
+            d: Setting,
+            e: Setting,
+        }
+
+        let data = Data {
+            a: Setting::Flag(true),
+            b: Setting::Count(42),
+            c: Setting::Name("a name".to_string()),
+            d: Setting::Size {
+                width: 2.0,
+                height: 1.5,
+            },
+            e: Setting::Name("2020-02-29".to_string()),
+        };
+        assert_eq!(
+            data,
+            from_str(
+                "a: true\nb: 42\nc: a name\nd:\n  width: 2\n  height: 1.5\n\
+                 e: 2020-02-29\n"
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn enums() -> Result<(), Box<Error>> {
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Shape {
+            Point,
+            Circle(f32),
+            Line(i32, i32),
+            Rect { width: u32, height: Option<u32> },
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Data {
+            a: Shape,
+            b: Shape,
+            c: Shape,
+            d: Shape,
+            list: Vec<Shape>,
+        }
+
+        let data = Data {
+            a: Shape::Point,
+            b: Shape::Circle(2.5),
+            c: Shape::Line(3, -4),
+            d: Shape::Rect {
+                width: 5,
+                height: None,
+            },
+            list: Vec::from([Shape::Point, Shape::Circle(1.0), Shape::Point]),
+        };
+        assert_eq!(
+            data,
+            from_str(
+                "a: Point\nb:\n  Circle: 2.5\nc:\n  Line: 3 -4\n\
+                 d:\n  Rect:\n    width: 5\nlist: Point\n\
+                 list:\n  Circle: 1.0\nlist: Point\n"
+            )?
+        );
+        assert_eq!(
+            Shape::Rect {
+                width: 1,
+                height: Some(2)
+            },
+            from_str("Rect: 1\n  height: 2\n")?
+        );
+        match from_str::<Shape>("Circle: 1.0\nLine: 1 2\n")
+            .unwrap_err()
+            .kind()
+        {
+            ErrorKind::FailedParse(ParseError::UnexpectedKey) => Ok(()),
+            r => panic!("bad error {r:?}"),
+        }
+    }
 }
@@ -0,0 +1,474 @@
+// document.rs
+//
+// Copyright (c) 2019-2020  Douglas Lau
+//
+use crate::common::Separator;
+use crate::error::Error;
+use crate::lines::{unquoted_key, DefIter, Item};
+use crate::ser::quoted_key;
+use std::fmt;
+use std::str::FromStr;
+
+/// Key / value definition on a document line
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    /// Indent count
+    indent: usize,
+    /// Key (without quotes)
+    key: String,
+    /// Key / value separator
+    separator: Separator,
+    /// Blank key flag (repeating the previous key)
+    blank: bool,
+    /// Byte offset of separator
+    key_end: usize,
+    /// Byte offset of value
+    value_start: usize,
+}
+
+/// One line of a document
+#[derive(Clone, Debug, PartialEq)]
+struct DocLine {
+    /// Original text (without line ending)
+    text: String,
+    /// Data definition (`None` for blank, comment and schema lines)
+    entry: Option<Entry>,
+    /// CR LF line ending flag
+    crlf: bool,
+}
+
+impl DocLine {
+    /// Make a definition line
+    ///
+    /// The prefix contains indent and key, which may be blank.
+    fn new(
+        prefix: String,
+        entry: (usize, &str),
+        separator: Separator,
+        value: &str,
+    ) -> Self {
+        let (indent, key) = entry;
+        let blank = prefix.trim_start_matches(' ').is_empty();
+        let mut text = prefix;
+        let key_end = text.len();
+        if value.is_empty() && separator == Separator::Normal {
+            text.push(':');
+        } else {
+            text.push_str(separator.as_str());
+        }
+        let value_start = text.len();
+        text.push_str(value);
+        let entry = Entry {
+            indent,
+            key: key.to_string(),
+            separator,
+            blank,
+            key_end,
+            value_start,
+        };
+        DocLine {
+            text,
+            entry: Some(entry),
+            crlf: false,
+        }
+    }
+
+    /// Get the value text
+    fn value(&self) -> &str {
+        match &self.entry {
+            Some(entry) => &self.text[entry.value_start..],
+            None => "",
+        }
+    }
+
+    /// Check if this line appends text to the previous definition
+    fn is_append(&self) -> bool {
+        matches!(&self.entry, Some(e) if e.separator == Separator::TextAppend)
+    }
+}
+
+/// MuON document which preserves formatting
+///
+/// Comments, blank lines, key quoting, separators, indentation, line endings
+/// and a byte order mark are all kept, so writing a document with
+/// [`Display`](fmt::Display) produces the original text, except where it was
+/// edited.
+///
+/// Values are found by a path of keys separated by `.`, such as
+/// `book.character.location`.  A key can be followed by an index in square
+/// brackets to select one of several definitions with the same key, like
+/// `book[1].author`.  Schema definitions are not part of any path.
+///
+/// # Example
+/// ```
+/// use muon_rs::Document;
+///
+/// let muon = "# Books\nbook: Pale Fire\n  author: Vladimir Nabokov\n";
+/// let mut doc: Document = muon.parse().unwrap();
+/// assert_eq!(doc.get("book.author").as_deref(), Some("Vladimir Nabokov"));
+/// doc.insert("book.year", "1962");
+/// assert_eq!(
+///     doc.to_string(),
+///     "# Books\nbook: Pale Fire\n  author: Vladimir Nabokov\n  year: 1962\n"
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    /// All lines
+    lines: Vec<DocLine>,
+    /// Number of spaces per indent (if known)
+    indent_spaces: Option<usize>,
+    /// Byte order mark flag
+    bom: bool,
+    /// CR LF line ending flag for new lines
+    crlf: bool,
+}
+
+impl FromStr for Document {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut lines: Vec<DocLine> = Vec::new();
+        let mut raw_lines = text.split_inclusive('\n');
+        let mut defs = DefIter::new(text);
+        while let Some(item) = defs.next_item() {
+            let crlf = raw_lines.next().is_some_and(|ln| ln.ends_with("\r\n"));
+            let position = defs.position();
+            let item = item.map_err(|e| {
+                let location = position.error_location(e, "".into());
+                Error::from(e).located(location)
+            })?;
+            let text = position.text().to_string();
+            let entry = match item {
+                Item::Define(define) => {
+                    let blank = position.is_key_blank();
+                    let key_offset = position.key_offset();
+                    let key_end = if blank {
+                        key_offset
+                    } else {
+                        key_offset + define.key.len()
+                    };
+                    Some(Entry {
+                        indent: define.indent,
+                        key: unquoted_key(&define.key).into_owned(),
+                        separator: define.separator,
+                        blank,
+                        key_end,
+                        value_start: position.value_offset(&define.value),
+                    })
+                }
+                _ => None,
+            };
+            lines.push(DocLine { text, entry, crlf });
+        }
+        let crlf = lines.first().is_some_and(|ln| ln.crlf);
+        Ok(Document {
+            lines,
+            indent_spaces: defs.indent_spaces(),
+            bom: text.starts_with('\u{FEFF}'),
+            crlf,
+        })
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.bom {
+            write!(f, "\u{FEFF}")?;
+        }
+        for line in &self.lines {
+            let ending = if line.crlf { "\r\n" } else { "\n" };
+            write!(f, "{}{ending}", line.text)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse one path segment into key and index
+fn parse_segment(segment: &str) -> Option<(&str, usize)> {
+    match segment.strip_suffix(']') {
+        Some(seg) => {
+            let (key, index) = seg.split_once('[')?;
+            Some((key, index.parse().ok()?))
+        }
+        None => Some((segment, 0)),
+    }
+}
+
+impl Document {
+    /// Create an empty document
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the entry at a line index
+    fn entry(&self, i: usize) -> Option<&Entry> {
+        self.lines.get(i).and_then(|ln| ln.entry.as_ref())
+    }
+
+    /// Get the end of lines appended to a definition (exclusive)
+    fn append_end(&self, i: usize) -> usize {
+        let mut end = i + 1;
+        for (j, line) in self.lines.iter().enumerate().skip(i + 1) {
+            match &line.entry {
+                Some(_) if line.is_append() => end = j + 1,
+                Some(_) => break,
+                None => (),
+            }
+        }
+        end
+    }
+
+    /// Get the end of a definition, including children (exclusive)
+    fn define_end(&self, i: usize) -> usize {
+        let indent = self.entry(i).map_or(0, |e| e.indent);
+        let mut end = i + 1;
+        for (j, line) in self.lines.iter().enumerate().skip(i + 1) {
+            match &line.entry {
+                Some(e) if line.is_append() || e.indent > indent => end = j + 1,
+                Some(_) => break,
+                None => (),
+            }
+        }
+        end
+    }
+
+    /// Find the line index of a definition by path
+    fn find(&self, path: &str) -> Option<usize> {
+        let mut start = 0;
+        let mut end = self.lines.len();
+        let mut found = None;
+        for (depth, segment) in path.split('.').enumerate() {
+            let (key, index) = parse_segment(segment)?;
+            let i = (start..end)
+                .filter(|i| {
+                    let line = &self.lines[*i];
+                    matches!(&line.entry, Some(e) if e.indent == depth
+                        && e.key == key
+                        && !line.is_append())
+                })
+                .nth(index)?;
+            start = i + 1;
+            end = self.define_end(i);
+            found = Some(i);
+        }
+        found
+    }
+
+    /// Get the value of a definition
+    ///
+    /// Text appended with `:>` is joined with line feeds.  Returns `None` if
+    /// the path was not found.
+    pub fn get(&self, path: &str) -> Option<String> {
+        let i = self.find(path)?;
+        let mut value = self.lines[i].value().to_string();
+        for line in &self.lines[i + 1..self.append_end(i)] {
+            if line.is_append() {
+                value.push('\n');
+                value.push_str(line.value());
+            }
+        }
+        Some(value)
+    }
+
+    /// Get the number of spaces per indent, choosing 2 if unknown
+    fn indent_spaces(&mut self) -> usize {
+        *self.indent_spaces.get_or_insert(2)
+    }
+
+    /// Make lines for a value, with `:>` for each line after the first
+    fn value_lines(
+        prefix: String,
+        entry: (usize, &str),
+        separator: Separator,
+        value: &str,
+    ) -> Vec<DocLine> {
+        let width = prefix.chars().count();
+        let mut values = value.split('\n');
+        let first = values.next().unwrap_or_default();
+        let mut lines = vec![DocLine::new(prefix, entry, separator, first)];
+        for value in values {
+            let prefix = " ".repeat(width);
+            let sep = Separator::TextAppend;
+            lines.push(DocLine::new(prefix, entry, sep, value));
+        }
+        lines
+    }
+
+    /// Set the line ending of new lines
+    fn with_ending(&self, mut lines: Vec<DocLine>) -> Vec<DocLine> {
+        for line in &mut lines {
+            line.crlf = self.crlf;
+        }
+        lines
+    }
+
+    /// Replace the value of a definition
+    ///
+    /// The key and separator are kept, but any appended text is replaced.
+    /// Returns the previous value, or `None` if the path was not found.
+    pub fn replace(&mut self, path: &str, value: &str) -> Option<String> {
+        let old = self.get(path)?;
+        let i = self.find(path)?;
+        let line = &self.lines[i];
+        let entry = line.entry.as_ref()?;
+        let prefix = line.text[..entry.key_end].to_string();
+        let key = entry.key.clone();
+        let lines = Self::value_lines(
+            prefix,
+            (entry.indent, &key),
+            entry.separator,
+            value,
+        );
+        let end = self.append_end(i);
+        self.lines.splice(i..end, self.with_ending(lines));
+        self.fix_blank_keys();
+        Some(old)
+    }
+
+    /// Insert a new definition
+    ///
+    /// The definition is added after any others in the parent record, which
+    /// is named by every key in the path except the last.  An empty value
+    /// starts a record.  Returns `false` if the parent was not found.
+    pub fn insert(&mut self, path: &str, value: &str) -> bool {
+        let (parent, key) = match path.rsplit_once('.') {
+            Some((parent, key)) => (Some(parent), key),
+            None => (None, path),
+        };
+        let (at, depth) = match parent {
+            Some(parent) => match self.find(parent) {
+                Some(i) => {
+                    let indent = self.entry(i).map_or(0, |e| e.indent);
+                    (self.define_end(i), indent + 1)
+                }
+                None => return false,
+            },
+            None => (self.lines.len(), 0),
+        };
+        let mut prefix = String::new();
+        if depth > 0 {
+            prefix.push_str(&" ".repeat(depth * self.indent_spaces()));
+        }
+        prefix.push_str(&quoted_key(key));
+        let separator = if value.starts_with(' ') {
+            Separator::TextValue
+        } else {
+            Separator::Normal
+        };
+        let lines = Self::value_lines(prefix, (depth, key), separator, value);
+        self.lines.splice(at..at, self.with_ending(lines));
+        self.fix_blank_keys();
+        true
+    }
+
+    /// Remove a definition, including any child definitions
+    ///
+    /// Returns the removed value, or `None` if the path was not found.
+    pub fn remove(&mut self, path: &str) -> Option<String> {
+        let old = self.get(path)?;
+        let i = self.find(path)?;
+        let end = self.define_end(i);
+        self.lines.drain(i..end);
+        self.fix_blank_keys();
+        Some(old)
+    }
+
+    /// Write out keys which no longer repeat the previous definition
+    fn fix_blank_keys(&mut self) {
+        let indent_spaces = self.indent_spaces.unwrap_or(0);
+        let mut prev: Option<(usize, String)> = None;
+        for line in &mut self.lines {
+            let Some(entry) = &mut line.entry else {
+                continue;
+            };
+            let repeat = prev
+                .as_ref()
+                .is_some_and(|(i, k)| *i == entry.indent && *k == entry.key);
+            if entry.blank && !repeat {
+                let mut text = " ".repeat(entry.indent * indent_spaces);
+                text.push_str(&quoted_key(&entry.key));
+                let key_end = text.len();
+                text.push_str(&line.text[entry.key_end..]);
+                entry.value_start = entry.value_start + key_end - entry.key_end;
+                entry.key_end = key_end;
+                entry.blank = false;
+                line.text = text;
+            }
+            prev = Some((entry.indent, entry.key.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::ParseError;
+
+    #[test]
+    fn lossless() -> Result<(), Error> {
+        let books = include_str!("../tests/books.muon");
+        assert_eq!(books.parse::<Document>()?.to_string(), books);
+        let text = "# Comment\n:::\na: list int\n:::\n\n\"a\":   1\n   \
+                    : 2\n  # indented comment\nb:\n    c:=x\n    c:>y\n";
+        assert_eq!(text.parse::<Document>()?.to_string(), text);
+        let text = "\u{FEFF}a: 1\r\nb:\r\n  c: 2\n";
+        let mut doc: Document = text.parse()?;
+        assert_eq!(doc.to_string(), text);
+        doc.insert("b.d", "3");
+        assert_eq!(doc.to_string(), "\u{FEFF}a: 1\r\nb:\r\n  c: 2\n  d: 3\r\n");
+        let e = "a: 1\nb 2\n".parse::<Document>().unwrap_err();
+        assert_eq!(e.parse_error(), Some(ParseError::MissingSeparator));
+        assert_eq!(e.location().unwrap().line(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn get() -> Result<(), Error> {
+        let doc: Document = include_str!("../tests/books.muon").parse()?;
+        assert_eq!(doc.get("book").as_deref(), Some("Pale Fire"));
+        assert_eq!(doc.get("book[1].year").as_deref(), Some("2003"));
+        assert_eq!(
+            doc.get("book[0].character[1].location").as_deref(),
+            Some("Zembla")
+        );
+        assert_eq!(doc.get("book[1].character[1].location"), None);
+        assert_eq!(doc.get("book[2]"), None);
+        assert_eq!(doc.get("author"), None);
+        let doc: Document = "\"a\": 1\n   : 2\nb:=x\n # c\n :>y\n".parse()?;
+        assert_eq!(doc.get("a[1]").as_deref(), Some("2"));
+        assert_eq!(doc.get("b").as_deref(), Some("x\ny"));
+        Ok(())
+    }
+
+    #[test]
+    fn edit() -> Result<(), Error> {
+        let mut doc: Document =
+            "# Top\na:   1\n : 2\nb:\n    \"c\":=x\n    c:>y\n# End\n"
+                .parse()?;
+        assert_eq!(doc.replace("b.c", "z").as_deref(), Some("x\ny"));
+        assert_eq!(doc.replace("a[1]", "3\n4").as_deref(), Some("2"));
+        assert_eq!(doc.replace("d", "5"), None);
+        assert_eq!(
+            doc.to_string(),
+            "# Top\na:   1\n : 3\n :>4\nb:\n    \"c\":=z\n# End\n"
+        );
+        assert!(doc.insert("b.d", "text"));
+        assert!(doc.insert("e", ""));
+        assert!(doc.insert("e.f", " space"));
+        assert!(!doc.insert("g.h", "1"));
+        assert_eq!(
+            doc.to_string(),
+            "# Top\na:   1\n : 3\n :>4\nb:\n    \"c\":=z\n    d: text\n\
+             # End\ne:\n    f:= space\n"
+        );
+        assert_eq!(doc.remove("a").as_deref(), Some("  1"));
+        assert_eq!(doc.remove("b").as_deref(), Some(""));
+        assert_eq!(doc.remove("b"), None);
+        assert_eq!(
+            doc.to_string(),
+            "# Top\na: 3\n :>4\n# End\ne:\n    f:= space\n"
+        );
+        Ok(())
+    }
+}
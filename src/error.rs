@@ -10,34 +10,79 @@ use std::str::{ParseBoolError, Utf8Error};
 #[non_exhaustive]
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ParseError {
+    /// Key which is not a list is repeated in a record
+    DuplicateKey,
+    /// Value is not a valid `bool`
     ExpectedBool,
+    /// Input ended unexpectedly
     ExpectedMore,
+    /// Value is not valid bytes (`0x` followed by hexadecimal digit pairs)
+    ExpectedBytes,
+    /// Value is not a single character
     ExpectedChar,
+    /// Value is not a valid `date`
     ExpectedDate,
+    /// Value is not a valid `datetime`
     ExpectedDateTime,
+    /// Value is not a valid `int`
     ExpectedInt,
+    /// Value is not a valid `number`
     ExpectedNumber,
+    /// Value is not a valid `time`
     ExpectedTime,
+    /// Value is not a valid time offset
     ExpectedTimeOffset,
+    /// Schema default does not match its type
     InvalidDefault,
+    /// Indent is not a multiple of 2, 3 or 4 spaces
     InvalidIndent,
+    /// Indent contains a tab or other whitespace at a column (starting at 1)
+    InvalidIndentChar(usize),
+    /// Carriage return is not followed by a line feed
+    InvalidLineEnding,
+    /// Separator is not `: `, `:>` or `:=`
     InvalidSeparator,
+    /// Value cannot be substituted for the first field of a record
     InvalidSubstitute,
+    /// Schema type is not valid
     InvalidType,
+    /// Required field is missing
     MissingField,
+    /// Definition has no key
     MissingKey,
+    /// Last line does not end with a line feed
     MissingLinefeed,
+    /// Line has no key / value separator
     MissingSeparator,
+    /// Key is not described by the schema
     UnexpectedKey,
+    /// Schema separator (`:::`) in the wrong place
     UnexpectedSchemaSeparator,
 }
 
+/// Category of an [`Error`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    /// Failure to read or write bytes on an I/O stream
+    Io,
+    /// Input is not syntactically valid MuON
+    Syntax,
+    /// Input is valid MuON, but does not match the expected data
+    Data,
+    /// Input ended unexpectedly
+    Eof,
+    /// Type is not supported by MuON
+    Unsupported,
+}
+
 impl ParseError {
     fn description(self) -> &'static str {
         use ParseError::*;
         match self {
+            DuplicateKey => "duplicate key",
             ExpectedBool => "expected bool",
             ExpectedMore => "expected more input data",
+            ExpectedBytes => "expected bytes",
             ExpectedChar => "expected char",
             ExpectedDate => "expected date",
             ExpectedDateTime => "expected datetime",
@@ -47,6 +92,8 @@ impl ParseError {
             ExpectedTimeOffset => "expected time offset",
             InvalidDefault => "invalid default",
             InvalidIndent => "invalid indent",
+            InvalidIndentChar(_) => "tab or other whitespace in indent",
+            InvalidLineEnding => "carriage return without line feed",
             InvalidSeparator => "invalid separator",
             InvalidSubstitute => "invalid substitute value",
             InvalidType => "invalid type",
@@ -58,6 +105,44 @@ impl ParseError {
             UnexpectedSchemaSeparator => "unexpected schema separator",
         }
     }
+
+    /// Get the error category
+    ///
+    /// [`ParseError::MissingLinefeed`] is [`Category::Eof`], since the
+    /// input ended in the middle of a line.
+    pub fn category(self) -> Category {
+        use ParseError::*;
+        match self {
+            ExpectedMore | MissingLinefeed => Category::Eof,
+            InvalidDefault
+            | InvalidIndent
+            | InvalidIndentChar(_)
+            | InvalidLineEnding
+            | InvalidSeparator
+            | InvalidType
+            | MissingKey
+            | MissingSeparator
+            | UnexpectedSchemaSeparator => Category::Syntax,
+            _ => Category::Data,
+        }
+    }
+
+    /// Check if error is caused by a malformed value
+    pub(crate) fn is_value(self) -> bool {
+        use ParseError::*;
+        matches!(
+            self,
+            ExpectedBool
+                | ExpectedBytes
+                | ExpectedChar
+                | ExpectedDate
+                | ExpectedDateTime
+                | ExpectedInt
+                | ExpectedNumber
+                | ExpectedTime
+                | ExpectedTimeOffset
+        )
+    }
 }
 
 impl From<ParseBoolError> for ParseError {
@@ -72,9 +157,76 @@ impl Display for ParseError {
     }
 }
 
-/// Errors which can occur when serializing and deserializing MuON data.
+/// Location of an error in MuON data
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    /// Line number (starting at 1)
+    line: usize,
+    /// Column number (starting at 1)
+    column: usize,
+    /// Key path
+    key: String,
+    /// Source line
+    snippet: String,
+}
+
+impl Location {
+    /// Create a location from a byte offset into a line
+    pub(crate) fn new(
+        line: usize,
+        text: &str,
+        offset: usize,
+        key: String,
+    ) -> Self {
+        let offset = offset.min(text.len());
+        let column = text[..offset].chars().count() + 1;
+        let snippet = text.to_string();
+        Location {
+            line,
+            column,
+            key,
+            snippet,
+        }
+    }
+
+    /// Get the line number (starting at 1)
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Get the column number (starting at 1)
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// Get the key path, such as `book[1].character[0].location`
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the source line
+    pub fn snippet(&self) -> &str {
+        &self.snippet
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "line {}, column {}", self.line, self.column)?;
+        if !self.key.is_empty() {
+            write!(formatter, ", key `{}`", self.key)?;
+        }
+        let num = self.line.to_string();
+        let pad = " ".repeat(num.len());
+        let caret = " ".repeat(self.column - 1);
+        write!(formatter, "\n{num} | {}\n{pad} | {caret}^", self.snippet)
+    }
+}
+
+/// Kind of [`Error`] which can occur when serializing and deserializing
+/// MuON data.
 #[derive(Debug)]
-pub enum Error {
+pub enum ErrorKind {
     /// I/O errors
     IO(io::Error),
     /// Formatting error while serializing
@@ -95,59 +247,177 @@ pub enum Error {
     FailedParse(ParseError),
 }
 
+/// Errors which can occur when serializing and deserializing MuON data.
+///
+/// Use [`Error::kind()`] to match on the kind of error, and
+/// [`Error::location()`] to find where it happened.
+#[derive(Debug)]
+pub struct Error {
+    /// Kind of error
+    kind: ErrorKind,
+    /// Location in MuON data, if known
+    location: Option<Box<Location>>,
+}
+
+impl ErrorKind {
+    /// Get the error category
+    pub fn category(&self) -> Category {
+        match self {
+            ErrorKind::IO(_) => Category::Io,
+            ErrorKind::Utf8(_) | ErrorKind::FromUtf8(_) => Category::Syntax,
+            ErrorKind::Format(_)
+            | ErrorKind::Serialize(_)
+            | ErrorKind::Deserialize(_) => Category::Data,
+            ErrorKind::UnsupportedType(_) | ErrorKind::InvalidKey => {
+                Category::Unsupported
+            }
+            ErrorKind::FailedParse(e) => e.category(),
+        }
+    }
+}
+
+impl Error {
+    /// Add a location to an error, unless it already has one
+    pub(crate) fn located(mut self, location: Location) -> Self {
+        if self.location.is_none() {
+            self.location = Some(Box::new(location));
+        }
+        self
+    }
+
+    /// Get the kind of error
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Convert the error into its kind, dropping the location
+    pub fn into_kind(self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Get the location of the error, if known
+    pub fn location(&self) -> Option<&Location> {
+        self.location.as_deref()
+    }
+
+    /// Get the error category
+    pub fn category(&self) -> Category {
+        self.kind.category()
+    }
+
+    /// Get the parse error, if any
+    pub fn parse_error(&self) -> Option<ParseError> {
+        match self.kind {
+            ErrorKind::FailedParse(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Check if error was caused by an I/O failure
+    pub fn is_io(&self) -> bool {
+        self.category() == Category::Io
+    }
+
+    /// Check if error was caused by invalid MuON syntax
+    pub fn is_syntax(&self) -> bool {
+        self.category() == Category::Syntax
+    }
+
+    /// Check if error was caused by data not matching the expected type
+    pub fn is_data(&self) -> bool {
+        self.category() == Category::Data
+    }
+
+    /// Check if error was caused by input ending unexpectedly
+    pub fn is_eof(&self) -> bool {
+        self.category() == Category::Eof
+    }
+}
+
 /// MuON result type
 pub type Result<T = (), E = Error> = std::result::Result<T, E>;
 
 impl serde::ser::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
-        Error::Serialize(msg.to_string())
+        ErrorKind::Serialize(msg.to_string()).into()
     }
 }
 
 impl serde::de::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
-        Error::Deserialize(msg.to_string())
+        ErrorKind::Deserialize(msg.to_string()).into()
     }
 }
 
-impl Display for Error {
+impl Display for ErrorKind {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Error::IO(e) => e.fmt(formatter),
-            Error::Format(e) => e.fmt(formatter),
-            Error::Utf8(e) => e.fmt(formatter),
-            Error::FromUtf8(e) => e.fmt(formatter),
-            Error::Serialize(msg) => formatter.write_str(msg),
-            Error::Deserialize(msg) => formatter.write_str(msg),
-            Error::UnsupportedType(msg) => formatter.write_str(msg),
-            Error::InvalidKey => formatter.write_str("string keys only"),
-            Error::FailedParse(e) => e.fmt(formatter),
+            ErrorKind::IO(e) => e.fmt(formatter),
+            ErrorKind::Format(e) => e.fmt(formatter),
+            ErrorKind::Utf8(e) => e.fmt(formatter),
+            ErrorKind::FromUtf8(e) => e.fmt(formatter),
+            ErrorKind::Serialize(msg) => formatter.write_str(msg),
+            ErrorKind::Deserialize(msg) => formatter.write_str(msg),
+            ErrorKind::UnsupportedType(msg) => formatter.write_str(msg),
+            ErrorKind::InvalidKey => formatter.write_str("invalid key"),
+            ErrorKind::FailedParse(e) => e.fmt(formatter),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(formatter, "{} at {location}", self.kind),
+            None => self.kind.fmt(formatter),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::IO(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error {
+            kind,
+            location: None,
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        ErrorKind::FailedParse(e).into()
+    }
+}
 
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self {
-        Error::IO(e)
+        ErrorKind::IO(e).into()
     }
 }
 
 impl From<fmt::Error> for Error {
     fn from(e: fmt::Error) -> Self {
-        Error::Format(e)
+        ErrorKind::Format(e).into()
     }
 }
 
 impl From<Utf8Error> for Error {
     fn from(e: Utf8Error) -> Self {
-        Error::Utf8(e)
+        ErrorKind::Utf8(e).into()
     }
 }
 
 impl From<std::string::FromUtf8Error> for Error {
     fn from(e: std::string::FromUtf8Error) -> Self {
-        Error::FromUtf8(e)
+        ErrorKind::FromUtf8(e).into()
     }
 }
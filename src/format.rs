@@ -0,0 +1,389 @@
+// format.rs
+//
+// Copyright (c) 2019-2020  Douglas Lau
+//
+use crate::common::{Define, Separator};
+use crate::error::{Error, Result};
+use crate::lines::{unquoted_key, DefIter, Item};
+use crate::schema::Modifier;
+use crate::ser::{quoted_key, ListStyle};
+use std::borrow::Cow;
+
+/// Options for formatting MuON text
+///
+/// # Example
+/// ```
+/// use muon_rs::{FormatOptions, ListStyle};
+///
+/// let muon = ":::\na: record\n    b: list int\n    c: text\n:::\n\
+///     a:\n    \"b\": 1\n       : 2\n    c:=x\n";
+/// let options = FormatOptions::new().indent(2);
+/// assert_eq!(
+///     muon_rs::format(muon, options).unwrap(),
+///     ":::\na: record\n  b: list int\n  c: text\n:::\na:\n  b: 1 2\n  c:=x\n"
+/// );
+/// let options = options.list_style(ListStyle::Repeat);
+/// assert_eq!(
+///     muon_rs::format(muon, options).unwrap(),
+///     ":::\na: record\n  b: list int\n  c: text\n:::\na:\n  b: 1\n  b: 2\n  c:=x\n"
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Number of spaces per indent
+    indent: usize,
+    /// Style of list output
+    list_style: ListStyle,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent: 2,
+            list_style: ListStyle::default(),
+        }
+    }
+}
+
+impl FormatOptions {
+    /// Create default format options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of spaces per indent (2, 3 or 4)
+    ///
+    /// **Note:** MuON only allows indents of 2, 3 or 4 spaces, so other
+    /// values are clamped to that range: `1` becomes `2`, and anything
+    /// above `4` becomes `4`.
+    pub fn indent(mut self, n_indent: usize) -> Self {
+        self.indent = n_indent.clamp(2, 4);
+        self
+    }
+
+    /// Set the list style
+    ///
+    /// With [`ListStyle::Merge`], list items are merged onto one line, and
+    /// items which cannot be merged use a blank key.  With
+    /// [`ListStyle::Repeat`], each item is written on its own line with the
+    /// key repeated.
+    pub fn list_style(mut self, list_style: ListStyle) -> Self {
+        self.list_style = list_style;
+        self
+    }
+}
+
+/// Part of a document to format
+enum Part<'a> {
+    /// Line written unchanged
+    Line(Cow<'a, str>),
+    /// Schema definition
+    Schema(Define<'a>),
+    /// Data definition, with list flag from the schema
+    Define(Define<'a>, bool),
+}
+
+impl Part<'_> {
+    /// Get a data definition
+    fn define(&self) -> Option<&Define<'_>> {
+        match self {
+            Part::Define(define, _) => Some(define),
+            _ => None,
+        }
+    }
+}
+
+/// Formatter for MuON text
+struct Formatter {
+    /// Format options
+    options: FormatOptions,
+    /// Indent and key of previous data definition
+    previous: Option<(usize, String)>,
+    /// Formatted output
+    out: String,
+}
+
+impl Formatter {
+    /// Write one line
+    fn write_line(&mut self, line: &str) {
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    /// Write a key
+    fn write_key(&mut self, define: &Define, blank: bool) {
+        let indent = define.indent * self.options.indent;
+        let key = quoted_key(&unquoted_key(&define.key));
+        if blank {
+            let width = indent + key.chars().count();
+            self.out.extend(std::iter::repeat_n(' ', width));
+        } else {
+            self.out.extend(std::iter::repeat_n(' ', indent));
+            self.out.push_str(&key);
+        }
+    }
+
+    /// Write a definition
+    fn write_define(&mut self, define: &Define, blank: bool) {
+        self.write_key(define, blank);
+        if define.value.is_empty() && define.separator == Separator::Normal {
+            self.write_line(":");
+        } else {
+            self.out.push_str(define.separator.as_str());
+            self.write_line(&define.value);
+        }
+    }
+
+    /// Write a data definition, using a blank key to repeat the previous key
+    fn write_data(&mut self, define: &Define) {
+        let key = unquoted_key(&define.key).into_owned();
+        let previous = Some((define.indent, key));
+        // Text append lines always have a blank key
+        let blank = self.previous == previous
+            && (self.options.list_style == ListStyle::Merge
+                || define.separator == Separator::TextAppend);
+        self.write_define(define, blank);
+        self.previous = previous;
+    }
+
+    /// Write a run of definitions with the same key
+    fn write_run(&mut self, run: &[Define], list: bool) {
+        match self.options.list_style {
+            ListStyle::Merge if list => {
+                let values: Vec<&str> =
+                    run.iter().map(|d| d.value.as_ref()).collect();
+                let define = &run[0];
+                let value = values.join(" ");
+                let sep = define.separator;
+                self.write_data(&Define::new(
+                    define.indent,
+                    define.key.as_ref(),
+                    sep,
+                    value,
+                ));
+            }
+            ListStyle::Repeat if list => {
+                for define in run {
+                    for value in
+                        define.value.split(' ').filter(|v| !v.is_empty())
+                    {
+                        let key = define.key.as_ref();
+                        let sep = define.separator;
+                        let indent = define.indent;
+                        self.write_data(&Define::new(indent, key, sep, value));
+                    }
+                }
+            }
+            _ => {
+                for define in run {
+                    self.write_data(define);
+                }
+            }
+        }
+    }
+}
+
+/// Get the next data definition after a part
+fn next_define<'p>(parts: &'p [Part], i: usize) -> Option<&'p Define<'p>> {
+    parts[i + 1..].iter().filter_map(Part::define).next()
+}
+
+/// Check if a data definition has child definitions
+fn has_children(parts: &[Part], i: usize) -> bool {
+    match (parts[i].define(), next_define(parts, i)) {
+        (Some(define), Some(next)) => next.indent > define.indent,
+        _ => false,
+    }
+}
+
+/// Check if a part is a list item which can be split or merged
+///
+/// It must have a non-empty `: ` value, with no appended text or children.
+fn is_list_item(parts: &[Part], i: usize) -> bool {
+    match parts[i].define() {
+        Some(define) => {
+            define.separator == Separator::Normal
+                && !define.value.is_empty()
+                && !has_children(parts, i)
+                && !next_define(parts, i)
+                    .is_some_and(|d| d.separator == Separator::TextAppend)
+        }
+        None => false,
+    }
+}
+
+/// Get the end of a run of list items with the same key (exclusive)
+fn run_end(parts: &[Part], start: usize) -> usize {
+    let mut end = start;
+    if let Some(first) = parts[start].define() {
+        while end < parts.len() && is_list_item(parts, end) {
+            match parts[end].define() {
+                Some(d) if d.indent == first.indent && d.key == first.key => {
+                    end += 1;
+                }
+                _ => break,
+            }
+        }
+    }
+    end
+}
+
+/// Format MuON text
+///
+/// Indentation is normalized, keys are quoted only where needed, and
+/// key / value separators are tidied.  Repeated keys are written using
+/// blank keys, and list items are merged or split depending on
+/// [`FormatOptions::list_style`].  Items are only merged or split where the
+/// schema says a key is a `list` of `bool`, `int`, `number` or a date / time
+/// type, since that could otherwise change the data.  Comments and blank
+/// lines are kept.
+///
+/// # Errors
+///
+/// An error will be returned if the text is not valid MuON.
+pub fn format(text: &str, options: FormatOptions) -> Result<String> {
+    let mut parts = Vec::new();
+    let mut path: Vec<String> = Vec::new();
+    let mut defs = DefIter::new(text);
+    while let Some(item) = defs.next_item() {
+        let position = defs.position();
+        let item = item.map_err(|e| {
+            let location = position.error_location(e, "".into());
+            Error::from(e).located(location)
+        })?;
+        let part = match item {
+            Item::SchemaSeparator => Part::Line(":::".into()),
+            Item::Blank => Part::Line("".into()),
+            Item::Comment(comment) => Part::Line(comment),
+            Item::SchemaNode(define) => Part::Schema(define),
+            Item::Define(define) => {
+                if define.separator != Separator::TextAppend {
+                    path.truncate(define.indent);
+                    path.push(unquoted_key(&define.key).into_owned());
+                }
+                let path: Vec<&str> = path.iter().map(|k| k.as_str()).collect();
+                let list = defs
+                    .schema()
+                    .and_then(|s| s.find(&path))
+                    .is_some_and(|n| {
+                        n.modifier() == Some(Modifier::List)
+                            && n.node_type().is_splittable()
+                    });
+                Part::Define(define, list)
+            }
+        };
+        parts.push(part);
+    }
+    let mut formatter = Formatter {
+        options,
+        previous: None,
+        out: String::with_capacity(text.len()),
+    };
+    let mut i = 0;
+    while i < parts.len() {
+        let end = run_end(&parts, i);
+        if end > i {
+            let list = matches!(parts[i], Part::Define(_, list) if list);
+            let run: Vec<Define> = parts[i..end]
+                .iter()
+                .filter_map(Part::define)
+                .cloned()
+                .collect();
+            formatter.write_run(&run, list);
+            i = end;
+            continue;
+        }
+        match &parts[i] {
+            Part::Line(line) => {
+                formatter.previous = None;
+                formatter.write_line(line);
+            }
+            Part::Schema(define) => formatter.write_define(define, false),
+            Part::Define(define, _) => {
+                // Nested branches cannot follow a blank key
+                if has_children(&parts, i) {
+                    formatter.previous = None;
+                }
+                formatter.write_data(define);
+            }
+        }
+        i += 1;
+    }
+    Ok(formatter.out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{from_str, Value};
+
+    #[test]
+    fn format_text() -> Result<()> {
+        let text = "# Comment\n:::\n\"a\": list int\nb: record\n    c: text\n\
+                    :::\n\na: 1\n : 2\nb: \n    \"c\": x:y\n    \
+                    c:>more\n    \"#d\":=\n";
+        assert_eq!(
+            format(text, FormatOptions::new())?,
+            "# Comment\n:::\na: list int\nb: record\n  c: text\n:::\n\n\
+             a: 1 2\nb:\n  c: x:y\n   :>more\n  \"#d\":=\n"
+        );
+        let options = FormatOptions::new().indent(4);
+        assert_eq!(
+            format("a:\n  b: 1\n   :=x y\n", options)?,
+            "a:\n    b: 1\n     :=x y\n"
+        );
+        let options = FormatOptions::new().indent(1);
+        assert_eq!(format("a:\n    b: 1\n", options)?, "a:\n  b: 1\n");
+        let options = FormatOptions::new().indent(8);
+        assert_eq!(format("a:\n  b: 1\n", options)?, "a:\n    b: 1\n");
+        let e = format("a: 1\nb 2\n", FormatOptions::new()).unwrap_err();
+        assert_eq!(e.location().unwrap().line(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn lists() -> Result<()> {
+        let text = ":::\na: list int\nb: text\nc: list record\n  \
+                    d: int\n:::\na: 1 2\nb: 3 4\nc: 5\nc:\n  d: 6\ne: 7\n\
+                    e: 8 9\n# x\ne: 10\nf: 11\n :>12\n : 13\n";
+        assert_eq!(
+            format(text, FormatOptions::new())?,
+            ":::\na: list int\nb: text\nc: list record\n  d: int\n:::\n\
+             a: 1 2\nb: 3 4\nc: 5\nc:\n  d: 6\ne: 7\n : 8 9\n# x\ne: 10\n\
+             f: 11\n :>12\n : 13\n"
+        );
+        let options = FormatOptions::new().list_style(ListStyle::Repeat);
+        assert_eq!(
+            format(text, options)?,
+            ":::\na: list int\nb: text\nc: list record\n  d: int\n:::\n\
+             a: 1\na: 2\nb: 3 4\nc: 5\nc:\n  d: 6\ne: 7\ne: 8 9\n\
+             # x\ne: 10\nf: 11\n :>12\nf: 13\n"
+        );
+        let text = ":::\na: list int\n:::\na: 1  2\n";
+        assert_eq!(
+            format(text, options)?,
+            ":::\na: list int\n:::\na: 1\na: 2\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn same_value() -> Result<()> {
+        let texts = [
+            "name: Ann Lee\nname: Bob\n",
+            "name: Ann  Lee\n",
+            "num: 1\nnum: 2\nnum: 3 4\n",
+            ":::\nnum: list int\nname: list text\n:::\n\
+             num: 1\nnum: 2 3\nname: Ann Lee\nname: Bob\n",
+        ];
+        for text in texts {
+            let value: Value = from_str(text)?;
+            for list_style in [ListStyle::Merge, ListStyle::Repeat] {
+                let options = FormatOptions::new().list_style(list_style);
+                let formatted = format(text, options)?;
+                assert_eq!(from_str::<Value>(&formatted)?, value, "{text:?}");
+            }
+        }
+        Ok(())
+    }
+}
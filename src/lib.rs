@@ -64,6 +64,9 @@
 //! # }
 //! ```
 //!
+//! Large documents with one repeated top-level key can be read one element at
+//! a time using a [`StreamDeserializer`].
+//!
 //! ## Serializing
 //!
 //! Deriving [`serde::Serialize`] on a struct is just as easy.  The
@@ -116,6 +119,14 @@
 //! # }
 //! ```
 //!
+//! Indent width, record substitution, list and text style and line endings
+//! can be changed with [`SerializerOptions`].  Existing MuON text can be
+//! tidied using [`format()`], or edited without losing comments using a
+//! [`Document`].  Use [`check`] to find every error in a document, and
+//! [`lint`] to find text which is valid but suspicious.  A [`Parser`]
+//! produces events with byte spans, for tools which don't need serde, and a
+//! [`Writer`] emits MuON one value at a time.
+//!
 //! ## Types
 //!
 //! MuON types can be mapped to different Rust types.
@@ -136,18 +147,82 @@
 //!
 //! [`HashMap`]: std::collections::HashMap
 //! [`BTreeMap`]: std::collections::BTreeMap
+//!
+//! Dictionary keys can be any type which is written as a single value, such
+//! as [`u32`], [`Date`] or a unit enum variant.  They are parsed in the same
+//! way as values.
+//!
+//! Byte arrays are written as `text`: `0x` followed by two hexadecimal digits
+//! per byte, continued with `:>` after every 32 bytes.  Unlike an `x` prefix,
+//! this is not read as an `int` without a schema.
+//!
+//! With the `derive` feature, a [`Schema`] describing a struct can be created
+//! by deriving [`MuonSchema`].
+//!
+//! ## Enums
+//!
+//! Unit variants are written as `text`, such as `shape: Point`.  Other
+//! variants are written as a record with a single key naming the variant:
+//!
+//! ```muon
+//! shape:
+//!   Circle: 2.5
+//! shape:
+//!   Rect: 3
+//!     height: 4
+//! ```
+//!
+//! Internally tagged, adjacently tagged and untagged enums are also
+//! supported.  The tag of an internally tagged enum is never substituted
+//! onto the record key line; tag names other than `type` must be set with
+//! [`SerializerOptions::enum_tags`].
+//!
+//! **Note:** the serializer cannot tell an untagged struct variant from a
+//! plain struct, so its first field may be substituted onto the record key
+//! line.  If an earlier variant also matches that value, such as a newtype
+//! variant holding an `i64` ahead of `Record { x: i64 }`, the earlier variant
+//! is read back instead.  Turn substitution off with
+//! [`SerializerOptions::substitute`] to avoid this.
 
+mod check;
 mod common;
 mod datetime;
 mod de;
+mod document;
 mod error;
+mod format;
 mod lines;
+mod lint;
 mod parse;
+mod parser;
 mod schema;
 mod ser;
+mod shape;
+mod value;
+mod writer;
 
+pub use check::{check, Diagnostic};
+pub use common::Separator;
 pub use datetime::{Date, DateTime, Time, TimeOffset};
-pub use de::{from_reader, from_slice, from_str, Deserializer};
-pub use error::{Error, Result};
-pub use schema::{IntValue, NumValue, Value};
-pub use ser::{to_string, to_vec, to_writer, Serializer};
+pub use de::{
+    from_reader, from_slice, from_str, Deserializer, DeserializerOptions,
+    StreamDeserializer,
+};
+pub use document::Document;
+pub use error::{Category, Error, ErrorKind, Location, ParseError, Result};
+pub use format::{format, FormatOptions};
+pub use lint::{lint, LintCode, LintWarning};
+pub use parser::{Event, Parser};
+pub use schema::{
+    IntValue, Modifier, MuonSchema, Node, NumValue, Schema, Type,
+    ValidationError, Value,
+};
+pub use ser::{
+    to_string, to_vec, to_writer, LineEnding, ListStyle, Serializer,
+    SerializerOptions, TextStyle,
+};
+pub use value::{from_value, to_value};
+pub use writer::Writer;
+
+#[cfg(feature = "derive")]
+pub use muon_rs_derive::MuonSchema;
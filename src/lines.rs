@@ -3,8 +3,10 @@
 // Copyright (c) 2019-2020  Douglas Lau
 //
 use crate::common::{Define, Separator};
-use crate::error::ParseError;
+use crate::error::{Error, Location, ParseError};
 use crate::schema::Schema;
+use std::borrow::Cow;
+use std::io::BufRead;
 
 /// Line parsing states
 enum State {
@@ -34,9 +36,9 @@ pub(crate) enum Line<'a> {
     /// Blank line
     Blank,
     /// Comment (starting with #)
-    Comment(&'a str),
+    Comment(Cow<'a, str>),
     /// Definition (key, separator, value)
-    Definition(&'a str, Separator, &'a str),
+    Definition(Cow<'a, str>, Separator, Cow<'a, str>),
 }
 
 impl State {
@@ -85,25 +87,43 @@ impl State {
     }
 
     /// Convert state to a Line
-    fn to_line<'a>(&self, line: &'a str) -> Result<Line<'a>, ParseError> {
+    fn to_line<'a>(&self, line: Cow<'a, str>) -> Result<Line<'a>, ParseError> {
         use State::*;
-        match self {
+        match *self {
             Comment => Ok(Line::Comment(line)),
             DefDone(off, separator) => {
-                let (key, value) = line.split_at(*off);
-                let v = value.len().min(separator.as_str().len());
-                let value = &value[v..];
-                Ok(Line::Definition(key, *separator, value))
+                let v = (line.len() - off).min(separator.as_str().len());
+                let (key, value) = split_line(line, off, off + v);
+                Ok(Line::Definition(key, separator, value))
             }
-            Error(e) => Err(*e),
+            Error(e) => Err(e),
             _ => Err(ParseError::MissingSeparator),
         }
     }
 }
 
+/// Split a line into key and value at byte offsets
+fn split_line(
+    line: Cow<str>,
+    key_end: usize,
+    value_start: usize,
+) -> (Cow<str>, Cow<str>) {
+    match line {
+        Cow::Borrowed(line) => (
+            Cow::Borrowed(&line[..key_end]),
+            Cow::Borrowed(&line[value_start..]),
+        ),
+        Cow::Owned(mut line) => {
+            let value = line.split_off(value_start);
+            line.truncate(key_end);
+            (Cow::Owned(line), Cow::Owned(value))
+        }
+    }
+}
+
 impl<'a> Line<'a> {
     /// Create line from input
-    fn new(line: &'a str) -> Result<Self, ParseError> {
+    fn new(line: Cow<'a, str>) -> Result<Self, ParseError> {
         if line.is_empty() {
             Ok(Line::Blank)
         } else if line == ":::" {
@@ -122,16 +142,196 @@ impl<'a> Line<'a> {
     }
 }
 
+/// Position of a line in MuON data
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Position<'a> {
+    /// Line number (starting at 1)
+    number: usize,
+    /// Line text
+    text: Cow<'a, str>,
+}
+
+impl Default for Position<'_> {
+    /// Position at the start of the first line, before it is read
+    fn default() -> Self {
+        Position {
+            number: 1,
+            text: Cow::Borrowed(""),
+        }
+    }
+}
+
+impl Position<'_> {
+    /// Get a character of the indent at a column (starting at 1)
+    pub(crate) fn indent_char(&self, column: usize) -> Option<char> {
+        self.text.chars().nth(column.checked_sub(1)?)
+    }
+
+    /// Get byte offset of the key (after indent)
+    pub(crate) fn key_offset(&self) -> usize {
+        self.text.len() - self.text.trim_start_matches(' ').len()
+    }
+
+    /// Get the line text
+    pub(crate) fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Check if the key is blank (repeating the previous key)
+    pub(crate) fn is_key_blank(&self) -> bool {
+        self.text[self.key_offset()..].starts_with(':')
+    }
+
+    /// Get byte offset of a value at the end of the line
+    pub(crate) fn value_offset(&self, value: &str) -> usize {
+        self.text.len().saturating_sub(value.len())
+    }
+
+    /// Get the location at a byte offset
+    pub(crate) fn location(&self, offset: usize, key: String) -> Location {
+        Location::new(self.number, &self.text, offset, key)
+    }
+
+    /// Get the location of a parse error on the line
+    pub(crate) fn error_location(
+        &self,
+        e: ParseError,
+        key: String,
+    ) -> Location {
+        let offset = match e {
+            // A carriage return is located where it appears in the line
+            ParseError::InvalidLineEnding => {
+                self.text.find('\r').unwrap_or_default()
+            }
+            _ => self.key_offset(),
+        };
+        self.location(offset, key)
+    }
+}
+
+/// Source of MuON lines
+enum Source<'a> {
+    /// String slice
+    Text(&'a str),
+    /// Buffered reader
+    Reader(Box<dyn BufRead + 'a>),
+}
+
 /// Iterator over lines
+///
+/// If an I/O error or invalid UTF-8 happens, the [`LineIter::next()`] method
+/// will return `None`.  Use [`LineIter::take_error()`] to check for this.
 pub(crate) struct LineIter<'a> {
-    /// Input string
-    input: &'a str,
+    /// Line source
+    source: Source<'a>,
+    /// Number of lines read
+    count: usize,
+    /// Position of most recent line
+    position: Position<'a>,
+    /// I/O or UTF-8 error from reader
+    error: Option<Error>,
 }
 
 impl<'a> LineIter<'a> {
     /// Create a new line iterator
     pub(crate) fn new(input: &'a str) -> Self {
-        LineIter { input }
+        LineIter::with_source(Source::Text(input))
+    }
+
+    /// Create a new line iterator from a buffered reader
+    pub(crate) fn from_reader<R: BufRead + 'a>(reader: R) -> Self {
+        LineIter::with_source(Source::Reader(Box::new(reader)))
+    }
+
+    /// Create a new line iterator from a source
+    fn with_source(source: Source<'a>) -> Self {
+        LineIter {
+            source,
+            count: 0,
+            position: Position::default(),
+            error: None,
+        }
+    }
+
+    /// Get position of the most recent line
+    pub(crate) fn position(&self) -> &Position<'a> {
+        &self.position
+    }
+
+    /// Take the I/O or UTF-8 error, if any
+    pub(crate) fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+
+    /// Read the next line of text
+    ///
+    /// A line missing its linefeed is returned as `Err`.
+    fn read_line(&mut self) -> Option<Result<Cow<'a, str>, Cow<'a, str>>> {
+        match &mut self.source {
+            Source::Text(input) => {
+                let text: &'a str = input;
+                // Should keys be allowed to contain linefeeds?
+                match text.split_once('\n') {
+                    Some((line, remaining)) => {
+                        *input = remaining;
+                        Some(Ok(Cow::Borrowed(line)))
+                    }
+                    None if !text.is_empty() => {
+                        *input = "";
+                        Some(Err(Cow::Borrowed(text)))
+                    }
+                    None => None,
+                }
+            }
+            Source::Reader(reader) => {
+                let mut buf = Vec::new();
+                match reader.read_until(b'\n', &mut buf) {
+                    Ok(0) => None,
+                    Ok(_) => match String::from_utf8(buf) {
+                        Ok(mut line) if line.ends_with('\n') => {
+                            line.pop();
+                            Some(Ok(Cow::Owned(line)))
+                        }
+                        Ok(line) => Some(Err(Cow::Owned(line))),
+                        Err(e) => {
+                            self.error = Some(e.utf8_error().into());
+                            None
+                        }
+                    },
+                    Err(e) => {
+                        self.error = Some(e.into());
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Trim a line ending in CR LF, and a byte order mark on the first line
+    fn trim_line(&self, line: Cow<'a, str>) -> Cow<'a, str> {
+        let bom = if self.count == 0 && line.starts_with('\u{FEFF}') {
+            '\u{FEFF}'.len_utf8()
+        } else {
+            0
+        };
+        let cr = usize::from(line.ends_with('\r'));
+        match line {
+            Cow::Borrowed(line) => Cow::Borrowed(&line[bom..line.len() - cr]),
+            Cow::Owned(mut line) => {
+                line.truncate(line.len() - cr);
+                line.drain(..bom);
+                Cow::Owned(line)
+            }
+        }
+    }
+
+    /// Set the position of the most recent line
+    fn set_position(&mut self, text: Cow<'a, str>) {
+        self.count += 1;
+        self.position = Position {
+            number: self.count,
+            text,
+        };
     }
 }
 
@@ -139,15 +339,23 @@ impl<'a> Iterator for LineIter<'a> {
     type Item = Result<Line<'a>, ParseError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        // Should keys be allowed to contain linefeeds?
-        if let Some(lf) = self.input.find('\n') {
-            let (line, remaining) = self.input.split_at(lf);
-            self.input = &remaining[1..]; // trim linefeed
-            Some(Line::new(line))
-        } else if !self.input.is_empty() {
-            Some(Err(ParseError::MissingLinefeed))
-        } else {
-            None
+        match self.read_line() {
+            Some(Ok(line)) => {
+                let line = self.trim_line(line);
+                self.set_position(line.clone());
+                if line.contains('\r') {
+                    Some(Err(ParseError::InvalidLineEnding))
+                } else {
+                    Some(Line::new(line))
+                }
+            }
+            Some(Err(line)) => {
+                let line = self.trim_line(line);
+                self.set_position(line);
+                Some(Err(ParseError::MissingLinefeed))
+            }
+            // Position stays at the last line read
+            None => None,
         }
     }
 }
@@ -162,10 +370,41 @@ fn key_indent(key: &str) -> Option<usize> {
     }
 }
 
+/// Item of a MuON document
+#[derive(Debug, PartialEq)]
+pub(crate) enum Item<'a> {
+    /// Schema separator (:::)
+    SchemaSeparator,
+    /// Blank line
+    Blank,
+    /// Comment (starting with #)
+    Comment(Cow<'a, str>),
+    /// Schema node definition
+    SchemaNode(Define<'a>),
+    /// Definition
+    Define(Define<'a>),
+}
+
+/// Remove quotes from a key, if any
+pub(crate) fn unquoted_key(key: &str) -> Cow<'_, str> {
+    match key.strip_prefix('"').and_then(|k| k.strip_suffix('"')) {
+        Some(k) => k.replace("\"\"", "\"").into(),
+        None => key.into(),
+    }
+}
+
+/// Remove quotes from a key, keeping borrowed keys borrowed
+pub(crate) fn unquote(key: Cow<str>) -> Cow<str> {
+    match key {
+        Cow::Borrowed(k) => unquoted_key(k),
+        Cow::Owned(k) => Cow::Owned(unquoted_key(&k).into_owned()),
+    }
+}
+
 /// Iterator for definitions
 ///
-/// If a parsing error happens, the [`DefIter::next()`] method will return
-/// `None`.  Use [`DefIter::error()`] to check for this.
+/// If an I/O error or invalid UTF-8 happens, the [`DefIter::next()`] method
+/// will return `None`.  Use [`DefIter::take_error()`] to check for this.
 pub(crate) struct DefIter<'a> {
     /// Line iterator
     lines: LineIter<'a>,
@@ -175,12 +414,23 @@ pub(crate) struct DefIter<'a> {
     schema: Option<Schema<'a>>,
     /// Current definition (for append handling)
     define: Option<Define<'a>>,
+    /// Number of spaces for each tab in an indent
+    tab_width: Option<usize>,
 }
 
 impl<'a> DefIter<'a> {
     /// Create a new definition iterator
     pub(crate) fn new(input: &'a str) -> Self {
-        let lines = LineIter::new(input);
+        DefIter::with_lines(LineIter::new(input))
+    }
+
+    /// Create a new definition iterator from a buffered reader
+    pub(crate) fn from_reader<R: BufRead + 'a>(reader: R) -> Self {
+        DefIter::with_lines(LineIter::from_reader(reader))
+    }
+
+    /// Create a new definition iterator from a line iterator
+    fn with_lines(lines: LineIter<'a>) -> Self {
         let indent_spaces = None;
         let schema = None;
         let define = None;
@@ -189,21 +439,78 @@ impl<'a> DefIter<'a> {
             indent_spaces,
             schema,
             define,
+            tab_width: None,
         }
     }
 
+    /// Create a new definition iterator for a standalone schema
+    pub(crate) fn new_schema(input: &'a str) -> Self {
+        let mut defs = DefIter::new(input);
+        defs.schema = Some(Schema::new());
+        defs
+    }
+
+    /// Set the number of spaces for each tab in an indent
+    ///
+    /// Without a tab width, tabs are not allowed in indents.
+    pub(crate) fn set_tab_width(&mut self, tab_width: Option<usize>) {
+        self.tab_width = tab_width;
+    }
+
+    /// Get the number of spaces in one indent, if known
+    pub(crate) fn indent_spaces(&self) -> Option<usize> {
+        self.indent_spaces
+    }
+
     /// Get schema
-    #[allow(dead_code)]
-    pub(crate) fn schema(&self) -> Option<&Schema> {
+    pub(crate) fn schema(&self) -> Option<&Schema<'a>> {
         self.schema.as_ref()
     }
 
+    /// Get position of the most recent line
+    pub(crate) fn position(&self) -> &Position<'a> {
+        self.lines.position()
+    }
+
+    /// Parse the most recent line as a definition, without processing it
+    ///
+    /// This is useful for lines which were not returned because of an error,
+    /// such as a missing line feed.
+    pub(crate) fn line_define(&self) -> Option<Define<'a>> {
+        match Line::new(self.position().text.clone()) {
+            Ok(Line::Definition(key, separator, value)) => {
+                let key = self.check_indent_chars(key).ok()?;
+                self.make_define(key, separator, value).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Take the I/O or UTF-8 error, if any
+    pub(crate) fn take_error(&mut self) -> Option<Error> {
+        self.lines.take_error()
+    }
+
+    /// Take the parsed schema
+    pub(crate) fn into_schema(self) -> Option<Schema<'a>> {
+        self.schema
+    }
+
     /// Set the indent spaces if needed
-    fn set_indent_spaces(&mut self, key: &'a str) -> Result<(), ParseError> {
+    ///
+    /// If the first indent is made of tabs, one tab is one indent level.
+    fn set_indent_spaces(
+        &mut self,
+        key: &str,
+        tabs: bool,
+    ) -> Result<(), ParseError> {
         if self.indent_spaces.is_none() {
             match key_indent(key) {
                 // Only 2, 3 or 4 space indents are valid
-                Some(sp) if (2..=4).contains(&sp) => {
+                Some(sp)
+                    if (2..=4).contains(&sp)
+                        || (tabs && Some(sp) == self.tab_width) =>
+                {
                     self.indent_spaces = Some(sp);
                     Ok(())
                 }
@@ -217,7 +524,7 @@ impl<'a> DefIter<'a> {
 
     /// Get the current key length (number of characters)
     fn key_len(&self) -> usize {
-        if let Some(define) = self.define {
+        if let Some(define) = &self.define {
             let i = define.indent * self.indent_spaces.unwrap_or(0);
             let k = define.key.chars().count();
             i + k
@@ -248,62 +555,103 @@ impl<'a> DefIter<'a> {
     /// Make a definition from key and value
     fn make_define(
         &self,
-        key: &'a str,
+        key: Cow<'a, str>,
         separator: Separator,
-        value: &'a str,
+        value: Cow<'a, str>,
     ) -> Result<Define<'a>, ParseError> {
         // is key blank? (all spaces)
         if key.chars().all(|c| c == ' ') {
             if key.len() == self.key_len() {
-                if let Some(define) = self.define {
+                if let Some(define) = &self.define {
                     return Ok(Define::new(
                         define.indent,
-                        define.key,
+                        define.key.clone(),
                         separator,
                         value,
                     ));
                 }
             }
-        } else if let Some(indent) = self.indent_count(key) {
-            let mut k = key;
+        } else if let Some(indent) = self.indent_count(&key) {
             // trim leading spaces only (not all whitespace)
-            while let Some(' ') = k.chars().next() {
-                k = &k[1..];
-            }
+            let k = match key {
+                Cow::Borrowed(k) => Cow::Borrowed(k.trim_start_matches(' ')),
+                Cow::Owned(mut k) => {
+                    k.drain(..k.len() - k.trim_start_matches(' ').len());
+                    Cow::Owned(k)
+                }
+            };
             return Ok(Define::new(indent, k, separator, value));
         }
         Err(ParseError::InvalidIndent)
     }
 
+    /// Check the indent of a key, expanding tabs if allowed
+    fn check_indent_chars(
+        &self,
+        key: Cow<'a, str>,
+    ) -> Result<Cow<'a, str>, ParseError> {
+        let mut tabs = false;
+        for (column, c) in key.chars().enumerate() {
+            match c {
+                ' ' => (),
+                '\t' if self.tab_width.is_some() => tabs = true,
+                _ if c.is_whitespace() => {
+                    return Err(ParseError::InvalidIndentChar(column + 1));
+                }
+                _ => break,
+            }
+        }
+        match self.tab_width {
+            Some(width) if tabs => {
+                let k = key.trim_start_matches([' ', '\t']);
+                let indent = &key[..key.len() - k.len()];
+                let mut expanded = indent.replace('\t', &" ".repeat(width));
+                expanded.push_str(k);
+                Ok(Cow::Owned(expanded))
+            }
+            _ => Ok(key),
+        }
+    }
+
     /// Process a define
     fn process_define(
         &mut self,
-        key: &'a str,
+        key: Cow<'a, str>,
         separator: Separator,
-        value: &'a str,
-    ) -> Result<Option<Define<'a>>, ParseError> {
-        self.set_indent_spaces(key)?;
+        value: Cow<'a, str>,
+    ) -> Result<Item<'a>, ParseError> {
+        let tabs = key.trim_start_matches(' ').starts_with('\t');
+        let key = self.check_indent_chars(key)?;
+        self.set_indent_spaces(&key, tabs)?;
         let def = self.make_define(key, separator, value)?;
         if let (None, Some(schema)) = (&self.define, &mut self.schema) {
-            if schema.add_define(def)? {
-                return Ok(None);
+            if schema.add_define(def.clone())?.is_none() {
+                return Ok(Item::SchemaNode(def));
             }
         }
-        Ok(Some(def))
+        // Nesting may only deepen one level past the previous define
+        let depth = self.define.as_ref().map_or(0, |d| d.indent + 1);
+        if def.indent > depth {
+            return Err(ParseError::InvalidIndent);
+        }
+        // Only indent and key are needed for append handling
+        let (indent, key) = (def.indent, def.key.clone());
+        self.define = Some(Define::new(indent, key, def.separator, ""));
+        Ok(Item::Define(def))
     }
 
     /// Process a schema separator
-    fn process_schema(&mut self) -> Result<Option<Define<'a>>, ParseError> {
+    fn process_schema(&mut self) -> Result<Item<'a>, ParseError> {
         match (&self.define, &mut self.schema) {
             (None, None) => {
                 self.schema = Some(Schema::new());
-                Ok(None)
+                Ok(Item::SchemaSeparator)
             }
             (None, Some(schema)) => {
                 if schema.finish() {
                     Err(ParseError::UnexpectedSchemaSeparator)
                 } else {
-                    Ok(None)
+                    Ok(Item::SchemaSeparator)
                 }
             }
             (_, _) => Err(ParseError::UnexpectedSchemaSeparator),
@@ -311,31 +659,32 @@ impl<'a> DefIter<'a> {
     }
 
     /// Process a line
-    fn process_line(
-        &mut self,
-        ln: Line<'a>,
-    ) -> Result<Option<Define<'a>>, ParseError> {
+    fn process_line(&mut self, ln: Line<'a>) -> Result<Item<'a>, ParseError> {
         match ln {
             Line::SchemaSeparator => self.process_schema(),
-            Line::Blank | Line::Comment(_) => Ok(None),
+            Line::Blank => Ok(Item::Blank),
+            Line::Comment(comment) => Ok(Item::Comment(comment)),
             Line::Definition(key, separator, value) => {
                 self.process_define(key, separator, value)
             }
         }
     }
+
+    /// Get the next item, including comments and schema nodes
+    pub(crate) fn next_item(&mut self) -> Option<Result<Item<'a>, ParseError>> {
+        let ln = self.lines.next()?;
+        Some(ln.and_then(|ln| self.process_line(ln)))
+    }
 }
 
 impl<'a> Iterator for DefIter<'a> {
     type Item = Result<Define<'a>, ParseError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some(ln) = self.lines.next() {
-            match ln.and_then(|ln| self.process_line(ln)) {
-                Ok(None) => (),
-                Ok(Some(define)) => {
-                    self.define = Some(define);
-                    return Some(Ok(define));
-                }
+        while let Some(item) = self.next_item() {
+            match item {
+                Ok(Item::Define(define)) => return Some(Ok(define)),
+                Ok(_) => (),
                 Err(e) => return Some(Err(e)),
             }
         }
@@ -352,20 +701,53 @@ mod test {
         let a = ":::\n# Comment\n:::\n\na: value a\nb:=value b\nc:>value c\n";
         let mut li = LineIter::new(a);
         assert_eq!(li.next().unwrap(), Ok(Line::SchemaSeparator));
-        assert_eq!(li.next().unwrap(), Ok(Line::Comment("# Comment")));
+        assert_eq!(li.next().unwrap(), Ok(Line::Comment("# Comment".into())));
         assert_eq!(li.next().unwrap(), Ok(Line::SchemaSeparator));
         assert_eq!(li.next().unwrap(), Ok(Line::Blank));
         assert_eq!(
             li.next().unwrap(),
-            Ok(Line::Definition("a", Separator::Normal, "value a")),
+            Ok(Line::Definition(
+                "a".into(),
+                Separator::Normal,
+                "value a".into()
+            )),
         );
         assert_eq!(
             li.next().unwrap(),
-            Ok(Line::Definition("b", Separator::TextValue, "value b")),
+            Ok(Line::Definition(
+                "b".into(),
+                Separator::TextValue,
+                "value b".into()
+            )),
         );
         assert_eq!(
             li.next().unwrap(),
-            Ok(Line::Definition("c", Separator::TextAppend, "value c")),
+            Ok(Line::Definition(
+                "c".into(),
+                Separator::TextAppend,
+                "value c".into()
+            )),
+        );
+    }
+
+    #[test]
+    fn crlf_li() {
+        let a = "\u{FEFF}a: 1\r\n\r\n# c\r\nb:=x\ry\r\n";
+        let mut li = LineIter::new(a);
+        assert_eq!(
+            li.next().unwrap(),
+            Ok(Line::Definition("a".into(), Separator::Normal, "1".into())),
+        );
+        assert_eq!(li.position().text, "a: 1");
+        assert_eq!(li.next().unwrap(), Ok(Line::Blank));
+        assert_eq!(li.next().unwrap(), Ok(Line::Comment("# c".into())));
+        assert_eq!(li.next(), Some(Err(ParseError::InvalidLineEnding)));
+        assert_eq!(li.position().number, 4);
+        assert_eq!(li.next(), None);
+        let mut li = LineIter::from_reader("\u{FEFF}a: 1\r\n".as_bytes());
+        assert_eq!(
+            li.next().unwrap(),
+            Ok(Line::Definition("a".into(), Separator::Normal, "1".into())),
         );
     }
 
@@ -377,8 +759,13 @@ mod test {
         assert_eq!(li.next(), Some(Err(ParseError::MissingSeparator)));
         assert_eq!(li.next(), Some(Err(ParseError::InvalidSeparator)));
         assert_eq!(li.next(), Some(Err(ParseError::InvalidSeparator)));
+        assert_eq!(li.position().number, 4);
         assert_eq!(li.next(), Some(Err(ParseError::MissingLinefeed)));
-        assert_eq!(li.next(), Some(Err(ParseError::MissingLinefeed)));
+        assert_eq!(li.position().number, 5);
+        assert_eq!(li.position().text, "b: value b");
+        assert_eq!(li.next(), None);
+        assert_eq!(li.position().number, 5);
+        assert_eq!(li.position().text, "b: value b");
     }
 
     #[test]
@@ -407,6 +794,58 @@ mod test {
         assert_eq!(di.next(), Some(Err(ParseError::InvalidIndent)));
     }
 
+    #[test]
+    fn tab_indent() {
+        let a = "a:\n\tb: 1\n  \u{3000}c: 2\n";
+        let mut di = DefIter::new(a);
+        assert_eq!(
+            di.next().unwrap(),
+            Ok(Define::new(0, "a", Separator::Normal, ""))
+        );
+        assert_eq!(di.next(), Some(Err(ParseError::InvalidIndentChar(1))));
+        assert_eq!(di.next(), Some(Err(ParseError::InvalidIndentChar(3))));
+        let mut di = DefIter::new("a:\n\tb: 1\n\t\t:=2\n");
+        di.set_tab_width(Some(2));
+        assert_eq!(
+            di.next().unwrap(),
+            Ok(Define::new(0, "a", Separator::Normal, ""))
+        );
+        assert_eq!(
+            di.next().unwrap(),
+            Ok(Define::new(1, "b", Separator::Normal, "1"))
+        );
+        assert_eq!(di.next(), Some(Err(ParseError::InvalidIndent)));
+        let mut di = DefIter::new("a:\n\tb: 1\n\t :=2\n");
+        di.set_tab_width(Some(2));
+        di.next();
+        di.next();
+        assert_eq!(
+            di.next().unwrap(),
+            Ok(Define::new(1, "b", Separator::TextValue, "2"))
+        );
+        let mut di = DefIter::new("a:\n\tb:\n\t\tc: 1\n");
+        di.set_tab_width(Some(8));
+        di.next();
+        di.next();
+        assert_eq!(
+            di.next().unwrap(),
+            Ok(Define::new(2, "c", Separator::Normal, "1"))
+        );
+        // Legacy style with 4 space indents and 8 space tabs
+        let mut di = DefIter::new("a:\n    b:\n\tc:\n\t    d: 1\n");
+        di.set_tab_width(Some(8));
+        di.next();
+        di.next();
+        assert_eq!(
+            di.next().unwrap(),
+            Ok(Define::new(2, "c", Separator::Normal, ""))
+        );
+        assert_eq!(
+            di.next().unwrap(),
+            Ok(Define::new(3, "d", Separator::Normal, "1"))
+        );
+    }
+
     #[test]
     fn def_iter2() {
         let a =
@@ -0,0 +1,582 @@
+// lint.rs
+//
+// Copyright (c) 2019-2020  Douglas Lau
+//
+use crate::common::{Define, Separator};
+use crate::error::{Location, ParseError};
+use crate::lines::{unquoted_key, DefIter, Item, Position};
+use crate::parse;
+use crate::schema::{Modifier, Schema, Type};
+use crate::ser::is_colon_homoglyph;
+use std::fmt;
+
+/// Code identifying a kind of lint warning
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LintCode {
+    /// Unquoted key contains a character which looks like a colon
+    ColonHomoglyph,
+    /// Value ends with whitespace
+    TrailingWhitespace,
+    /// List is written with both merged and repeated items
+    MixedListStyle,
+    /// Key is not in the schema, but is similar to one which is
+    KeyTypo,
+    /// Definition after a schema separator looks like a schema node
+    SchemaLike,
+    /// Number cannot be represented exactly as `f64`
+    PrecisionLoss,
+    /// Indent contains a tab
+    TabIndent,
+}
+
+impl LintCode {
+    /// Get the lint code as a string slice
+    ///
+    /// These codes are stable, and can be used to filter warnings.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LintCode::ColonHomoglyph => "colon-homoglyph",
+            LintCode::TrailingWhitespace => "trailing-whitespace",
+            LintCode::MixedListStyle => "mixed-list-style",
+            LintCode::KeyTypo => "key-typo",
+            LintCode::SchemaLike => "schema-like",
+            LintCode::PrecisionLoss => "precision-loss",
+            LintCode::TabIndent => "tab-indent",
+        }
+    }
+}
+
+impl fmt::Display for LintCode {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
+/// Suspicious, but valid, MuON found by [`lint`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintWarning {
+    /// Lint code
+    code: LintCode,
+    /// Warning message
+    message: String,
+    /// Location in document
+    location: Location,
+}
+
+impl LintWarning {
+    /// Create a new lint warning
+    fn new(
+        code: LintCode,
+        message: impl Into<String>,
+        position: &Position,
+        offset: usize,
+        key: String,
+    ) -> Self {
+        LintWarning {
+            code,
+            message: message.into(),
+            location: position.location(offset, key),
+        }
+    }
+
+    /// Get the lint code
+    pub fn code(&self) -> LintCode {
+        self.code
+    }
+
+    /// Get the warning message
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Get the location in the document
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} [{}] at {}",
+            self.message, self.code, self.location
+        )
+    }
+}
+
+/// Data definition to lint
+struct Entry<'a> {
+    /// Definition
+    define: Define<'a>,
+    /// Position in document
+    position: Position<'a>,
+    /// Key path (including the key)
+    path: Vec<String>,
+}
+
+impl Entry<'_> {
+    /// Get the key path as a string
+    fn key(&self) -> String {
+        self.path.join(".")
+    }
+
+    /// Get byte offset of the value
+    fn value_offset(&self) -> usize {
+        self.position.value_offset(&self.define.value)
+    }
+}
+
+/// Lint state
+struct Linter<'a> {
+    /// Data definitions
+    entries: Vec<Entry<'a>>,
+    /// Warnings found
+    warnings: Vec<LintWarning>,
+}
+
+impl<'a> Linter<'a> {
+    /// Check a definition (data or schema)
+    fn check_define(&mut self, define: &Define, position: &Position) {
+        let key = define.key.to_string();
+        if !define.key.starts_with('"') {
+            let mut chars = define.key.char_indices();
+            if let Some((i, _)) = chars.find(|(_, c)| is_colon_homoglyph(*c)) {
+                let offset = position.key_offset() + i;
+                self.warnings.push(LintWarning::new(
+                    LintCode::ColonHomoglyph,
+                    "colon homoglyph in unquoted key",
+                    position,
+                    offset,
+                    key.clone(),
+                ));
+            }
+        }
+        let value = define.value.trim_end();
+        if value.len() < define.value.len() {
+            let offset = position.value_offset(&define.value) + value.len();
+            self.warnings.push(LintWarning::new(
+                LintCode::TrailingWhitespace,
+                "trailing whitespace in value",
+                position,
+                offset,
+                key,
+            ));
+        }
+    }
+
+    /// Check for schema-like definitions after the schema separator
+    fn check_schema_like(&mut self, after: usize) {
+        for entry in &self.entries[after..] {
+            if !is_schema_like(&entry.define) {
+                break;
+            }
+            self.warnings.push(LintWarning::new(
+                LintCode::SchemaLike,
+                "definition after `:::` looks like a schema node",
+                &entry.position,
+                entry.value_offset(),
+                entry.key(),
+            ));
+        }
+    }
+
+    /// Check if an entry is a list item which could be merged or repeated
+    fn is_list_item(&self, i: usize) -> bool {
+        let define = &self.entries[i].define;
+        define.separator == Separator::Normal
+            && !define.value.is_empty()
+            && !self.entries.get(i + 1).is_some_and(|e| {
+                e.define.separator == Separator::TextAppend
+                    || e.define.indent > define.indent
+            })
+    }
+
+    /// Check if two entries have the same indent and key
+    fn is_same_key(&self, i: usize, j: usize) -> bool {
+        let (a, b) = (&self.entries[i].define, &self.entries[j].define);
+        a.indent == b.indent && a.key == b.key
+    }
+
+    /// Check for lists with mixed styles
+    fn check_lists(&mut self, schema: Option<&Schema>) {
+        let mut i = 0;
+        while i < self.entries.len() {
+            let mut end = i;
+            while end < self.entries.len()
+                && self.is_list_item(end)
+                && self.is_same_key(i, end)
+            {
+                end += 1;
+            }
+            if end - i > 1 {
+                let list = &self.entries[i..end];
+                let merged = schema.is_some_and(|s| is_merged_list(s, list));
+                if let Some(warning) = check_list(list, merged) {
+                    self.warnings.push(warning);
+                }
+            }
+            i = end.max(i + 1);
+        }
+    }
+
+    /// Check entries against a schema
+    fn check_schema(&mut self, schema: &Schema) {
+        for entry in &self.entries {
+            if entry.define.separator == Separator::TextAppend {
+                continue;
+            }
+            let path: Vec<&str> =
+                entry.path.iter().map(|k| k.as_str()).collect();
+            if let Some((key, parent)) = path.split_last() {
+                let names = schema.record_names(parent).unwrap_or_default();
+                if let Some(name) = similar_key(key, &names) {
+                    self.warnings.push(LintWarning::new(
+                        LintCode::KeyTypo,
+                        format!("unknown key; did you mean `{name}`?"),
+                        &entry.position,
+                        entry.position.key_offset(),
+                        entry.key(),
+                    ));
+                }
+            }
+            let node_type = schema.find(&path).map(|n| n.node_type());
+            check_numbers(&mut self.warnings, entry, node_type);
+        }
+    }
+}
+
+/// Check if the schema allows list items to be merged onto one line
+///
+/// Only lists of non-text types can be merged; other values containing a
+/// space are a single item.
+fn is_merged_list(schema: &Schema, list: &[Entry]) -> bool {
+    let path: Vec<&str> = list[0].path.iter().map(|k| k.as_str()).collect();
+    schema.find(&path).is_some_and(|n| {
+        n.modifier() == Some(Modifier::List) && n.node_type().is_splittable()
+    })
+}
+
+/// Check one list for mixed styles
+///
+/// Items can be merged onto one line, or repeated with either blank or
+/// explicit keys, but not a mix of those.  Values containing a space only
+/// count as merged items when `merged` is set.
+fn check_list(list: &[Entry], merged: bool) -> Option<LintWarning> {
+    let blank = list[1].position.is_key_blank();
+    let (_, entry) = list.iter().enumerate().find(|(i, entry)| {
+        (merged && entry.define.value.contains(' '))
+            || (*i > 0 && entry.position.is_key_blank() != blank)
+    })?;
+    Some(LintWarning::new(
+        LintCode::MixedListStyle,
+        "list mixes merged and repeated items",
+        &entry.position,
+        entry.position.key_offset(),
+        entry.key(),
+    ))
+}
+
+/// Check numbers in a value for precision loss
+///
+/// Integers are only checked if the schema type is `number`.
+fn check_numbers(
+    warnings: &mut Vec<LintWarning>,
+    entry: &Entry,
+    node_type: Option<Type>,
+) {
+    if entry.define.separator != Separator::Normal {
+        return;
+    }
+    let ints = match node_type {
+        None | Some(Type::Any) => false,
+        Some(Type::Number) => true,
+        Some(_) => return,
+    };
+    let mut offset = entry.value_offset();
+    for item in entry.define.value.split(' ') {
+        let int = parse::int::<i128>(item).is_some();
+        if (ints || !int) && is_precision_lost(item) {
+            warnings.push(LintWarning::new(
+                LintCode::PrecisionLoss,
+                "number loses precision as f64",
+                &entry.position,
+                offset,
+                entry.key(),
+            ));
+        }
+        offset += item.len() + 1;
+    }
+}
+
+/// Check if a definition value looks like a schema node
+fn is_schema_like(define: &Define) -> bool {
+    let (_modifier, value): (Option<Modifier>, &str) =
+        Modifier::from_str_start(&define.value);
+    define.separator == Separator::Normal
+        && value
+            .split(' ')
+            .next()
+            .is_some_and(|t| t.parse::<Type>().is_ok())
+}
+
+/// Find a name similar to a key, if the key is not in the names
+fn similar_key<'n>(key: &str, names: &'n [impl AsRef<str>]) -> Option<&'n str> {
+    if names.iter().any(|n| n.as_ref() == key) {
+        return None;
+    }
+    // Allow more edits for longer keys, and none for very short keys
+    let max = (key.chars().count() / 3).min(2);
+    names
+        .iter()
+        .map(|n| (n.as_ref(), edit_distance(key, n.as_ref())))
+        .filter(|(_, d)| *d <= max)
+        .min_by_key(|(_, d)| *d)
+        .map(|(n, _)| n)
+}
+
+/// Get the edit distance between two strings
+///
+/// Insertions, deletions, substitutions and adjacent transpositions are each
+/// one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+/// Get the significant digits and exponent of a decimal number
+fn decimal_digits(v: &str) -> Option<(String, isize)> {
+    let v = v.trim_start_matches(['-', '+']).replace('_', "");
+    let (mantissa, exp) = match v.split_once(['e', 'E']) {
+        Some((m, e)) => (m, e.parse::<isize>().ok()?),
+        None => (v.as_str(), 0),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{int}{frac}");
+    let lead = digits.len() - digits.trim_start_matches('0').len();
+    let digits = digits.trim_matches('0');
+    if digits.is_empty() {
+        return Some((String::new(), 0));
+    }
+    let exp = exp + int.len() as isize - lead as isize;
+    Some((digits.to_string(), exp))
+}
+
+/// Check if a number loses precision when parsed as `f64`
+fn is_precision_lost(v: &str) -> bool {
+    match parse::number::<f64>(v) {
+        Some(n) if n.is_finite() => {
+            decimal_digits(v) != decimal_digits(&format!("{n:e}"))
+        }
+        // Infinity and NaN are only lossless when written that way
+        Some(_) => !v.ends_with("inf") && !v.ends_with("NaN"),
+        None => false,
+    }
+}
+
+/// Lint a MuON document, reporting suspicious but valid text
+///
+/// Warnings are reported for:
+/// - Colon homoglyphs in unquoted keys
+/// - Trailing whitespace in values
+/// - Lists with both merged and repeated items
+/// - Keys which look like typos of keys in the schema
+/// - Definitions after a schema separator which look like schema nodes
+/// - Numbers which lose precision when parsed as `f64`
+/// - Indents containing tabs, which are only read with a
+///   [`tab_width`](crate::DeserializerOptions::tab_width)
+///
+/// Other malformed lines are skipped; use [`check`](crate::check) to find those.
+///
+/// # Example
+/// ```
+/// use muon_rs::LintCode;
+///
+/// let muon = ":::\nname: text\nscore: number\n:::\nnmae: Arthur\n\
+///             score: 0.1000000000000000000001\n";
+/// let warnings = muon_rs::lint(muon);
+/// assert_eq!(warnings[0].code(), LintCode::KeyTypo);
+/// assert_eq!(warnings[1].code(), LintCode::PrecisionLoss);
+/// for warning in warnings {
+///     println!("{warning}");
+/// }
+/// ```
+pub fn lint(text: &str) -> Vec<LintWarning> {
+    let mut linter = Linter {
+        entries: Vec::new(),
+        warnings: Vec::new(),
+    };
+    let mut path: Vec<String> = Vec::new();
+    let mut separators = 0;
+    let mut schema_end = None;
+    let mut defs = DefIter::new(text);
+    while let Some(item) = defs.next_item() {
+        let position = defs.position().clone();
+        match item {
+            Ok(Item::SchemaSeparator) => {
+                separators += 1;
+                if separators == 2 {
+                    schema_end = Some(linter.entries.len());
+                }
+            }
+            Ok(Item::SchemaNode(define)) => {
+                linter.check_define(&define, &position);
+            }
+            Ok(Item::Define(define)) => {
+                linter.check_define(&define, &position);
+                if define.separator != Separator::TextAppend {
+                    path.truncate(define.indent);
+                    path.push(unquoted_key(&define.key).into_owned());
+                }
+                let path = path.clone();
+                linter.entries.push(Entry {
+                    define,
+                    position,
+                    path,
+                });
+            }
+            Err(ParseError::InvalidIndentChar(column))
+                if position.indent_char(column) == Some('\t') =>
+            {
+                linter.warnings.push(LintWarning::new(
+                    LintCode::TabIndent,
+                    "indent contains a tab",
+                    &position,
+                    column - 1,
+                    String::new(),
+                ));
+            }
+            _ => (),
+        }
+    }
+    if let Some(after) = schema_end {
+        linter.check_schema_like(after);
+    }
+    linter.check_lists(defs.schema());
+    match defs.schema() {
+        Some(schema) => linter.check_schema(schema),
+        None => {
+            for entry in &linter.entries {
+                check_numbers(&mut linter.warnings, entry, None);
+            }
+        }
+    }
+    let mut warnings = linter.warnings;
+    warnings.sort_by_key(|w| (w.location.line(), w.location.column()));
+    warnings
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn codes(text: &str) -> Vec<(usize, LintCode)> {
+        lint(text)
+            .iter()
+            .map(|w| (w.location().line(), w.code()))
+            .collect()
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(codes("a: 1\nb: 2\n"), []);
+        assert_eq!(
+            codes("a\u{A789}b: 1\n\"c\u{A789}d\": 2\n"),
+            [(1, LintCode::ColonHomoglyph)]
+        );
+        let text = ":::\nname: text\nscore: int\nid: int\nitem: record\n  \
+                    color: text\n:::\nnmae: x\nscroe: 1\nie: 2\nitem:\n  \
+                    colour: red\n";
+        assert_eq!(
+            codes(text),
+            [
+                (8, LintCode::KeyTypo),
+                (9, LintCode::KeyTypo),
+                (12, LintCode::KeyTypo),
+            ]
+        );
+        assert_eq!(lint(text)[2].location().key(), "item.colour");
+        let text = ":::\nname: text\n:::\n\"name\": Arthur\n";
+        assert_eq!(codes(text), []);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("ab", "ba"), 1);
+    }
+
+    #[test]
+    fn values() {
+        let warnings = lint("a: text \nb:=x\t\nc: 1\n");
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].code(), LintCode::TrailingWhitespace);
+        assert_eq!(warnings[0].location().column(), 8);
+        assert_eq!(warnings[1].location().line(), 2);
+        let text = ":::\na: list int\nb: list int\nc: list int\n:::\n\
+                    a: 1\na: 2 3\nb: 1\n : 2\nb: 3\nc: 4\n : 5\n";
+        assert_eq!(
+            codes(text),
+            [
+                (7, LintCode::MixedListStyle),
+                (10, LintCode::MixedListStyle)
+            ]
+        );
+        assert_eq!(
+            codes("b: 1\n : 2\nb: 3\n"),
+            [(3, LintCode::MixedListStyle)]
+        );
+        assert_eq!(codes("a: x y\na:\n  b: 1\n"), []);
+        assert_eq!(codes("name: Ann Lee\nname: Bob\n"), []);
+        let text = ":::\nname: list text\n:::\nname: Ann Lee\nname: Bob\n";
+        assert_eq!(codes(text), []);
+    }
+
+    #[test]
+    fn schema_like() {
+        let text = ":::\na: text\n:::\nb: optional int\nc: list text\n\
+                    a: some text\n";
+        assert_eq!(
+            codes(text),
+            [(4, LintCode::SchemaLike), (5, LintCode::SchemaLike)]
+        );
+    }
+
+    #[test]
+    fn precision() {
+        assert_eq!(
+            codes("a: 0.1 2.5e-3 1_000.0 inf 12345678901234567890\n"),
+            []
+        );
+        let warnings = lint("a: 1.5 0.12345678901234567891 1e999\n");
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].code(), LintCode::PrecisionLoss);
+        assert_eq!(warnings[0].location().column(), 8);
+        assert_eq!(warnings[1].location().column(), 31);
+        let text = ":::\na: number\nb: int\n:::\na: 9007199254740993\n\
+                    b: 9007199254740993\n";
+        assert_eq!(codes(text), [(5, LintCode::PrecisionLoss)]);
+    }
+
+    #[test]
+    fn tabs() {
+        let warnings = lint("a:\n\tb: 1\n  \tc: 2\n  d: 3\n");
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].code(), LintCode::TabIndent);
+        assert_eq!(warnings[0].location().line(), 2);
+        assert_eq!(warnings[0].location().column(), 1);
+        assert_eq!(warnings[1].location().column(), 3);
+        assert_eq!(codes("a:\n  b: x\ty\n"), []);
+    }
+}
@@ -121,6 +121,22 @@ fn sanitize_num(value: &str, radix: u32) -> Option<Cow<'_, str>> {
     Some(value.replace('_', "").into())
 }
 
+/// Parse bytes from a string slice
+///
+/// Bytes are written as `0x` followed by pairs of hexadecimal digits, which
+/// can be split across lines.  Unlike an `x` prefix, which is a hexadecimal
+/// `int`, this is never inferred as a number.
+pub(crate) fn bytes(v: &str) -> Option<Vec<u8>> {
+    let hex = v.strip_prefix("0x")?.replace('\n', "");
+    let hex = sanitize_num(&hex, 16)?;
+    (hex.len() % 2 == 0 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+        .then_some(())?;
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
 /// Parse a bool from a string slice
 pub(crate) fn bool(value: &str) -> Option<bool> {
     value.parse().ok()
@@ -189,8 +205,8 @@ mod test {
         assert_eq!(number::<f32>("123._456"), None);
         assert_eq!(number::<f32>("12.34.56"), None);
         assert_eq!(number::<f64>("-123.456789e0").unwrap(), -123.456789);
-        assert_eq!(number::<f64>("inf").unwrap(), std::f64::INFINITY);
-        assert_eq!(number::<f64>("-inf").unwrap(), std::f64::NEG_INFINITY);
+        assert_eq!(number::<f64>("inf").unwrap(), f64::INFINITY);
+        assert_eq!(number::<f64>("-inf").unwrap(), f64::NEG_INFINITY);
         assert_eq!(number::<f64>("1__0.0"), None);
         assert_eq!(number::<f64>("infinity"), None);
         assert_eq!(number::<f64>("INF"), None);
@@ -225,4 +241,20 @@ mod test {
         assert_eq!(char("a"), Some('a'));
         assert_eq!(char("\0"), Some('\0'));
     }
+
+    #[test]
+    fn bytes() {
+        assert_eq!(super::bytes("0x"), Some(vec![]));
+        assert_eq!(super::bytes("0x00ff7F"), Some(vec![0, 255, 127]));
+        assert_eq!(super::bytes("0x0102\n03"), Some(vec![1, 2, 3]));
+        assert_eq!(super::bytes("0xde_ad"), Some(vec![0xDE, 0xAD]));
+        assert_eq!(super::bytes("0102"), None);
+        assert_eq!(super::bytes("x0102"), None);
+        assert_eq!(super::bytes("0x012"), None);
+        assert_eq!(super::bytes("0x+1"), None);
+        assert_eq!(super::bytes("0xgg"), None);
+        assert_eq!(super::bytes("0x_00"), None);
+        assert_eq!(int::<i64>("0x0102"), None);
+        assert_eq!(number::<f64>("0x0102"), None);
+    }
 }
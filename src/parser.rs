@@ -0,0 +1,455 @@
+// parser.rs
+//
+// Copyright (c) 2019-2020  Douglas Lau
+//
+use crate::common::{Define, Separator};
+use crate::error::{Error, ParseError};
+use crate::lines::{unquote, DefIter, Item, Position};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::str::SplitInclusive;
+
+/// Event produced by a [`Parser`]
+///
+/// Spans are byte ranges into the input text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event<'a> {
+    /// Start of a record, which contains the following events until its
+    /// matching [`Event::EndRecord`]
+    StartRecord {
+        /// Key (without quotes)
+        key: Cow<'a, str>,
+        /// Span of key (empty for a blank key)
+        key_span: Range<usize>,
+    },
+    /// Key / value definition
+    ///
+    /// A value on the same line as [`Event::StartRecord`] is reported with
+    /// the record key.  It is substituted for the first field of the record.
+    Value {
+        /// Key (without quotes)
+        key: Cow<'a, str>,
+        /// Span of key (empty for a blank key)
+        key_span: Range<usize>,
+        /// Key / value separator
+        separator: Separator,
+        /// Value text
+        text: Cow<'a, str>,
+        /// Span of value text
+        text_span: Range<usize>,
+    },
+    /// Text appended to the previous value with `:>`
+    AppendText {
+        /// Appended text
+        text: Cow<'a, str>,
+        /// Span of appended text
+        text_span: Range<usize>,
+    },
+    /// End of a record
+    EndRecord,
+    /// Comment line
+    Comment {
+        /// Comment text, including the `#`
+        text: Cow<'a, str>,
+        /// Span of comment
+        span: Range<usize>,
+    },
+    /// Start of a schema (`:::`)
+    SchemaStart {
+        /// Span of separator
+        span: Range<usize>,
+    },
+    /// End of a schema (`:::`)
+    SchemaEnd {
+        /// Span of separator
+        span: Range<usize>,
+    },
+}
+
+/// Line read ahead of the current event
+struct RawLine<'a> {
+    /// Parsed item
+    item: Result<Item<'a>, ParseError>,
+    /// Position of line
+    position: Position<'a>,
+    /// Byte offset of line text in input
+    start: usize,
+}
+
+/// Pull parser for MuON text, without serde
+///
+/// Nesting of records is resolved from indentation.  Definitions within a
+/// schema produce events in the same way as data, between
+/// [`Event::SchemaStart`] and [`Event::SchemaEnd`].  Blank lines produce no
+/// events.  A comment indented less than an open record ends it, unless the
+/// next definition is still within the record.
+///
+/// After an error, parsing recovers at the next line.
+///
+/// # Example
+/// ```
+/// use muon_rs::{Event, Parser};
+///
+/// let muon = "# Books\nbook: Pale Fire\n  year: 1962\n";
+/// for event in Parser::new(muon) {
+///     match event.unwrap() {
+///         Event::StartRecord { key, .. } => println!("start {key}"),
+///         Event::Value { key, text, .. } => println!("{key} = {text}"),
+///         Event::EndRecord => println!("end"),
+///         _ => (),
+///     }
+/// }
+/// ```
+pub struct Parser<'a> {
+    /// Definition iterator
+    defs: DefIter<'a>,
+    /// Raw input lines
+    raw: SplitInclusive<'a, char>,
+    /// Byte offset of next raw line
+    offset: usize,
+    /// Lines read ahead
+    ahead: VecDeque<RawLine<'a>>,
+    /// Pending events
+    events: VecDeque<Event<'a>>,
+    /// Number of open records
+    depth: usize,
+    /// Flag indicating a schema is open
+    schema: bool,
+}
+
+/// Make a located error
+fn located(e: ParseError, position: &Position) -> Error {
+    let location = position.error_location(e, "".into());
+    Error::from(e).located(location)
+}
+
+impl<'a> Parser<'a> {
+    /// Create a new parser
+    pub fn new(input: &'a str) -> Self {
+        Parser {
+            defs: DefIter::new(input),
+            raw: input.split_inclusive('\n'),
+            offset: 0,
+            ahead: VecDeque::new(),
+            events: VecDeque::new(),
+            depth: 0,
+            schema: false,
+        }
+    }
+
+    /// Read one line from the definition iterator
+    fn read_line(&mut self) -> Option<RawLine<'a>> {
+        let item = self.defs.next_item()?;
+        let position = self.defs.position().clone();
+        let raw = self.raw.next().unwrap_or_default();
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        // Byte order mark is trimmed from the start of the first line
+        let start = self.offset + line.len() - position.text().len();
+        self.offset += raw.len();
+        Some(RawLine {
+            item,
+            position,
+            start,
+        })
+    }
+
+    /// Get the next line
+    fn next_line(&mut self) -> Option<RawLine<'a>> {
+        self.ahead.pop_front().or_else(|| self.read_line())
+    }
+
+    /// Get the indent of the next definition, reading ahead as needed
+    ///
+    /// Returns `None` at a schema separator, an error or the end of input.
+    fn next_indent(&mut self) -> Option<usize> {
+        for i in 0.. {
+            if i == self.ahead.len() {
+                let line = self.read_line()?;
+                self.ahead.push_back(line);
+            }
+            match &self.ahead[i].item {
+                Ok(Item::Define(d) | Item::SchemaNode(d))
+                    if d.separator != Separator::TextAppend =>
+                {
+                    return Some(d.indent);
+                }
+                Ok(Item::Define(_) | Item::SchemaNode(_))
+                | Ok(Item::Comment(_) | Item::Blank) => (),
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// Check if the next definition is a child of one at an indent
+    fn has_children(&mut self, indent: usize) -> bool {
+        self.next_indent().is_some_and(|i| i > indent)
+    }
+
+    /// Get the indent count of a comment
+    fn comment_indent(&self, text: &str) -> usize {
+        let spaces = text.len() - text.trim_start_matches(' ').len();
+        self.defs.indent_spaces().map_or(0, |n| spaces / n)
+    }
+
+    /// End records until a number remain open
+    fn end_records(&mut self, depth: usize) {
+        while self.depth > depth {
+            self.depth -= 1;
+            self.events.push_back(Event::EndRecord);
+        }
+    }
+
+    /// Process one line
+    fn process_line(&mut self, line: RawLine<'a>) -> Result<(), Error> {
+        let RawLine {
+            item,
+            position,
+            start,
+        } = line;
+        match item.map_err(|e| located(e, &position))? {
+            Item::Blank => (),
+            Item::Comment(text) => {
+                // Records are ended by a comment with a smaller indent,
+                // unless the next definition is still within them
+                let indent = self.comment_indent(&text);
+                let indent =
+                    self.next_indent().map_or(indent, |i| i.max(indent));
+                self.end_records(indent);
+                let span = start..start + text.len();
+                self.events.push_back(Event::Comment { text, span });
+            }
+            Item::SchemaSeparator => {
+                let span = start..start + position.text().len();
+                if self.schema {
+                    self.end_records(0);
+                    self.events.push_back(Event::SchemaEnd { span });
+                } else {
+                    self.events.push_back(Event::SchemaStart { span });
+                }
+                self.schema = !self.schema;
+            }
+            Item::Define(define) | Item::SchemaNode(define) => {
+                self.process_define(define, &position, start)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Process a definition
+    fn process_define(
+        &mut self,
+        define: Define<'a>,
+        position: &Position<'a>,
+        start: usize,
+    ) -> Result<(), Error> {
+        let value_start = start + position.value_offset(&define.value);
+        let text_span = value_start..value_start + define.value.len();
+        if define.separator == Separator::TextAppend {
+            let text = define.value;
+            self.events.push_back(Event::AppendText { text, text_span });
+            return Ok(());
+        }
+        if define.indent > self.depth {
+            return Err(located(ParseError::InvalidIndent, position));
+        }
+        self.end_records(define.indent);
+        let key_start = start + position.key_offset();
+        let key_len = if position.is_key_blank() {
+            0
+        } else {
+            define.key.len()
+        };
+        let key_span = key_start..key_start + key_len;
+        let key = unquote(define.key);
+        if self.has_children(define.indent) {
+            self.depth += 1;
+            self.events.push_back(Event::StartRecord {
+                key: key.clone(),
+                key_span: key_span.clone(),
+            });
+            if define.value.is_empty() && define.separator == Separator::Normal
+            {
+                return Ok(());
+            }
+        }
+        self.events.push_back(Event::Value {
+            key,
+            key_span,
+            separator: define.separator,
+            text: define.value,
+            text_span,
+        });
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for Parser<'a> {
+    type Item = Result<Event<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(Ok(event));
+            }
+            match self.next_line() {
+                Some(line) => {
+                    if let Err(e) = self.process_line(line) {
+                        return Some(Err(e));
+                    }
+                }
+                None if self.depth > 0 => self.end_records(0),
+                None => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn value<'a>(
+        key: &'a str,
+        key_span: Range<usize>,
+        text: &'a str,
+        text_span: Range<usize>,
+    ) -> Event<'a> {
+        Event::Value {
+            key: key.into(),
+            key_span,
+            separator: Separator::Normal,
+            text: text.into(),
+            text_span,
+        }
+    }
+
+    #[test]
+    fn records() -> Result<(), Error> {
+        let text = "# top\na: 1\nb: x\n  c:\n    d: 2\n  # e\n  \
+                    \"f\":=y\n     :>z\nb:\ng: 3\n";
+        let events = Parser::new(text).collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            events,
+            [
+                Event::Comment {
+                    text: "# top".into(),
+                    span: 0..5,
+                },
+                value("a", 6..7, "1", 9..10),
+                Event::StartRecord {
+                    key: "b".into(),
+                    key_span: 11..12,
+                },
+                value("b", 11..12, "x", 14..15),
+                Event::StartRecord {
+                    key: "c".into(),
+                    key_span: 18..19,
+                },
+                value("d", 25..26, "2", 28..29),
+                Event::EndRecord,
+                Event::Comment {
+                    text: "  # e".into(),
+                    span: 30..35,
+                },
+                Event::Value {
+                    key: "f".into(),
+                    key_span: 38..41,
+                    separator: Separator::TextValue,
+                    text: "y".into(),
+                    text_span: 43..44,
+                },
+                Event::AppendText {
+                    text: "z".into(),
+                    text_span: 52..53,
+                },
+                Event::EndRecord,
+                value("b", 54..55, "", 56..56),
+                value("g", 57..58, "3", 60..61),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn comments() -> Result<(), Error> {
+        let text = "a:\n  b: 1\n# c\nd:\n# e\n  f: 2\n";
+        let events = Parser::new(text).collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            events,
+            [
+                Event::StartRecord {
+                    key: "a".into(),
+                    key_span: 0..1,
+                },
+                value("b", 5..6, "1", 8..9),
+                Event::EndRecord,
+                Event::Comment {
+                    text: "# c".into(),
+                    span: 10..13,
+                },
+                Event::StartRecord {
+                    key: "d".into(),
+                    key_span: 14..15,
+                },
+                Event::Comment {
+                    text: "# e".into(),
+                    span: 17..20,
+                },
+                value("f", 23..24, "2", 26..27),
+                Event::EndRecord,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn schema() -> Result<(), Error> {
+        let text = "\u{FEFF}:::\r\na: record\r\n  b: int\r\n:::\r\na:\r\n  \
+                    b: 1\r\n   : 2\r\n";
+        let events = Parser::new(text).collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            events,
+            [
+                Event::SchemaStart { span: 3..6 },
+                Event::StartRecord {
+                    key: "a".into(),
+                    key_span: 8..9,
+                },
+                value("a", 8..9, "record", 11..17),
+                value("b", 21..22, "int", 24..27),
+                Event::EndRecord,
+                Event::SchemaEnd { span: 29..32 },
+                Event::StartRecord {
+                    key: "a".into(),
+                    key_span: 34..35,
+                },
+                value("b", 40..41, "1", 43..44),
+                value("b", 49..49, "2", 51..52),
+                Event::EndRecord,
+            ]
+        );
+        assert_eq!(&text[51..52], "2");
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        let text = "a: 1\n  b:\n      c: 2\nd 3\ne: 4\n";
+        let results: Vec<_> = Parser::new(text).collect();
+        assert_eq!(results.len(), 7);
+        let b = value("b", 7..8, "", 9..9);
+        assert_eq!(results[2].as_ref().ok(), Some(&b));
+        let e = results[3].as_ref().unwrap_err();
+        assert_eq!(e.parse_error(), Some(ParseError::InvalidIndent));
+        assert_eq!(e.location().unwrap().line(), 3);
+        let e = results[4].as_ref().unwrap_err();
+        assert_eq!(e.parse_error(), Some(ParseError::MissingSeparator));
+        assert_eq!(e.location().unwrap().line(), 4);
+        assert_eq!(results[5].as_ref().ok(), Some(&Event::EndRecord));
+        let e = value("e", 25..26, "4", 28..29);
+        assert_eq!(results[6].as_ref().ok(), Some(&e));
+    }
+}
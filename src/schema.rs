@@ -3,15 +3,23 @@
 // Copyright (c) 2019  Douglas Lau
 //
 use crate::common::Define;
-use crate::datetime::{Date, DateTime, Time};
-use crate::error::ParseError;
+use crate::common::Separator;
+use crate::datetime::{self, Date, DateTime, Time};
+use crate::error::{Error, Location, ParseError};
+use crate::lines::{unquote, unquoted_key, DefIter, Line, LineIter, Position};
 use crate::parse;
-use serde::{de::Visitor, Deserialize, Deserializer};
-use std::fmt;
+use crate::ser::quoted_key;
+use crate::shape;
+use serde::de::{MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write};
 use std::str::FromStr;
 
 /// Integer value enum
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum IntValue {
     /// Unsigned integer value
     Unsigned(u128),
@@ -34,7 +42,7 @@ impl FromStr for IntValue {
 }
 
 /// Number value enum
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum NumValue {
     /// 32-bit number
     Num32(f32),
@@ -57,7 +65,7 @@ impl FromStr for NumValue {
 }
 
 /// A MuON value
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Value {
     /// Text value
     Text(String),
@@ -85,6 +93,21 @@ pub enum Value {
     List(Vec<Value>),
 }
 
+impl Value {
+    /// Create a date / time value from a string slice
+    pub(crate) fn from_datetime(v: &str) -> Option<Self> {
+        if let Ok(datetime) = v.parse() {
+            Some(Value::DateTime(datetime))
+        } else if let Ok(date) = v.parse() {
+            Some(Value::Date(date))
+        } else if let Ok(time) = v.parse() {
+            Some(Value::Time(time))
+        } else {
+            None
+        }
+    }
+}
+
 /// Visitor for deserializing a MuON Value
 struct ValueVisitor;
 
@@ -176,9 +199,60 @@ impl<'de> Visitor<'de> for ValueVisitor {
     where
         D: Deserializer<'de>,
     {
-        let v = deserializer.deserialize_any(self)?;
+        let v = Value::deserialize(deserializer)?;
         Ok(Value::Optional(Some(Box::new(v))))
     }
+
+    fn visit_newtype_struct<D>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut list = Vec::new();
+        while let Some(v) = seq.next_element()? {
+            list.push(v);
+        }
+        Ok(Value::List(list))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        // Dates and times are wrapped in a single-entry marker map
+        if let [(Value::Text(k), Value::Text(v))] = &entries[..] {
+            if k == datetime::MARKER {
+                return Value::from_datetime(v).ok_or_else(|| {
+                    serde::de::Error::custom(ParseError::ExpectedDateTime)
+                });
+            }
+        }
+        if entries.iter().all(|(k, _)| matches!(k, Value::Text(_))) {
+            let record = entries
+                .into_iter()
+                .filter_map(|(k, v)| match k {
+                    Value::Text(k) => Some((k, v)),
+                    _ => None,
+                })
+                .collect();
+            Ok(Value::Record(record))
+        } else {
+            Ok(Value::Dictionary(entries))
+        }
+    }
 }
 
 impl<'de> Deserialize<'de> for Value {
@@ -186,12 +260,94 @@ impl<'de> Deserialize<'de> for Value {
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_any(ValueVisitor {})
+        deserializer
+            .deserialize_newtype_struct(datetime::VALUE_NAME, ValueVisitor)
+    }
+}
+
+impl Serialize for IntValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            IntValue::Unsigned(v) => serializer.serialize_u128(*v),
+            IntValue::Signed(v) => serializer.serialize_i128(*v),
+        }
+    }
+}
+
+impl Serialize for NumValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            NumValue::Num32(v) => serializer.serialize_f32(*v),
+            NumValue::Num64(v) => serializer.serialize_f64(*v),
+        }
+    }
+}
+
+/// Name of the newtype struct wrapping fields of a [`Value::Record`]
+///
+/// This lets the MuON serializer tell records apart from dictionaries.
+pub(crate) const RECORD_NAME: &str = "$muon_rs::private::Record";
+
+/// Fields of a record value, serialized as a map
+struct RecordFields<'a>(&'a [(String, Value)]);
+
+impl Serialize for RecordFields<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Value::Text(v) => serializer.serialize_str(v),
+            Value::Bool(v) => serializer.serialize_bool(*v),
+            Value::Int(v) => v.serialize(serializer),
+            Value::Number(v) => v.serialize(serializer),
+            Value::DateTime(v) => v.serialize(serializer),
+            Value::Date(v) => v.serialize(serializer),
+            Value::Time(v) => v.serialize(serializer),
+            Value::Record(record) => serializer
+                .serialize_newtype_struct(RECORD_NAME, &RecordFields(record)),
+            Value::Dictionary(dict) => {
+                let mut map = serializer.serialize_map(Some(dict.len()))?;
+                for (key, value) in dict {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+            Value::Any(v) => v.serialize(serializer),
+            Value::Optional(Some(v)) => serializer.serialize_some(v),
+            Value::Optional(None) => serializer.serialize_none(),
+            Value::List(list) => {
+                let mut seq = serializer.serialize_seq(Some(list.len()))?;
+                for value in list {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+        }
     }
 }
 
 /// Type modifier for a schema
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Modifier {
     /// Optional values may not be present
     Optional,
@@ -199,26 +355,25 @@ pub enum Modifier {
     List,
 }
 
-/// Schema Node
+/// Schema node, describing one key
 #[derive(Debug)]
 pub struct Node<'a> {
     /// Indent level
     indent: usize,
     /// Type name
-    #[allow(dead_code)]
-    name: &'a str,
+    name: Cow<'a, str>,
     /// Type modifier
-    #[allow(dead_code)]
     modifier: Option<Modifier>,
     /// Node type
     node_type: Type,
     /// Default value
-    #[allow(dead_code)]
     default: Option<Value>,
+    /// Doc comment
+    doc: Option<Cow<'a, str>>,
 }
 
 /// Schema Type
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Type {
     /// Text is a [`String`]
     Text,
@@ -243,7 +398,22 @@ pub enum Type {
     Any,
 }
 
-/// Full schema
+/// MuON schema
+///
+/// A schema describes the keys and types of a MuON document.  It can be
+/// parsed from the `:::` block at the top of a document, or from a standalone
+/// schema file.
+///
+/// ```rust
+/// # use muon_rs::{Schema, Type};
+/// # fn main() -> Result<(), muon_rs::Error> {
+/// let schema = Schema::parse("book: list record\n  title: text\n")?;
+/// let title = schema.find(&["book", "title"]).unwrap();
+/// assert_eq!(title.node_type(), Type::Text);
+/// assert!(schema.validate("book: Hamlet\n").is_ok());
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Debug)]
 pub struct Schema<'a> {
     /// List of all nodes
@@ -254,7 +424,7 @@ pub struct Schema<'a> {
 
 impl Modifier {
     /// Create a type modifier from start of a string slice
-    fn from_str_start(val: &str) -> (Option<Self>, &str) {
+    pub(crate) fn from_str_start(val: &str) -> (Option<Self>, &str) {
         let v: Vec<&str> = val.splitn(2, ' ').collect();
         if v.len() > 1 {
             match v[0] {
@@ -266,6 +436,20 @@ impl Modifier {
             (None, val)
         }
     }
+
+    /// Get the modifier as a string slice
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Modifier::Optional => "optional",
+            Modifier::List => "list",
+        }
+    }
+}
+
+impl fmt::Display for Modifier {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
 }
 
 impl FromStr for Type {
@@ -288,39 +472,130 @@ impl FromStr for Type {
     }
 }
 
+impl fmt::Display for Type {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
 impl Type {
+    /// Get the type as a string slice
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Type::Text => "text",
+            Type::Bool => "bool",
+            Type::Int => "int",
+            Type::Number => "number",
+            Type::DateTime => "datetime",
+            Type::Date => "date",
+            Type::Time => "time",
+            Type::Record => "record",
+            Type::Dictionary => "dictionary",
+            Type::Any => "any",
+        }
+    }
+
+    /// Check if list items of the type can be split or merged on spaces
+    ///
+    /// Text items may contain spaces, so they cannot be split.
+    pub(crate) fn is_splittable(self) -> bool {
+        matches!(
+            self,
+            Type::Bool
+                | Type::Int
+                | Type::Number
+                | Type::DateTime
+                | Type::Date
+                | Type::Time
+        )
+    }
+
     /// Parse a value by type
-    fn parse_value(
+    fn parse_value(&self, v: &str) -> Result<Value, ParseError> {
+        Ok(match self {
+            Type::Text => Value::Text(String::from(v)),
+            Type::Bool => Value::Bool(v.parse()?),
+            Type::Int => Value::Int(v.parse()?),
+            Type::Number => Value::Number(v.parse()?),
+            Type::DateTime => Value::DateTime(v.parse()?),
+            Type::Date => Value::Date(v.parse()?),
+            Type::Time => Value::Time(v.parse()?),
+            _ => return Err(ParseError::InvalidType),
+        })
+    }
+
+    /// Parse a default value by type
+    ///
+    /// Only scalar types with no modifier can have a default.
+    fn parse_default(
         &self,
         modifier: &Option<Modifier>,
         v: &str,
     ) -> Result<Value, ParseError> {
-        Ok(match (modifier, self) {
-            (None, Type::Text) => Value::Text(String::from(v)),
-            (None, Type::Bool) => Value::Bool(v.parse()?),
-            (None, Type::Int) => Value::Int(v.parse()?),
-            (None, Type::Number) => Value::Number(v.parse()?),
-            (None, Type::DateTime) => Value::DateTime(v.parse()?),
-            (None, Type::Date) => Value::Date(v.parse()?),
-            (None, Type::Time) => Value::Time(v.parse()?),
-            _ => return Err(ParseError::InvalidDefault),
-        })
+        match modifier {
+            None => self.parse_value(v).map_err(|_| ParseError::InvalidDefault),
+            Some(_) => Err(ParseError::InvalidDefault),
+        }
+    }
+
+    /// Check that a value can be parsed by type
+    fn check_value(&self, v: &str) -> Result<(), ParseError> {
+        self.parse_value(v).map(|_| ())
     }
 }
 
 impl<'a> Node<'a> {
+    /// Create a new schema node
+    ///
+    /// * `depth`: Nesting depth (0 for root nodes)
+    /// * `name`: Key name
+    /// * `modifier`: Type modifier
+    /// * `node_type`: Node type
+    pub fn new(
+        depth: usize,
+        name: impl Into<Cow<'a, str>>,
+        modifier: Option<Modifier>,
+        node_type: Type,
+    ) -> Self {
+        Node {
+            indent: depth,
+            name: name.into(),
+            modifier,
+            node_type,
+            default: None,
+            doc: None,
+        }
+    }
+
+    /// Set the default value, parsed by node type
+    ///
+    /// # Errors
+    ///
+    /// [`ParseError::InvalidDefault`] is returned for `list`, `record`,
+    /// `dictionary` and `any` nodes, or if the value does not match the type.
+    pub fn with_default(mut self, default: &str) -> Result<Self, Error> {
+        let default = self.node_type.parse_default(&self.modifier, default)?;
+        self.default = Some(default);
+        Ok(self)
+    }
+
+    /// Set the doc comment, written as `#` lines ahead of the node
+    pub fn with_doc(mut self, doc: impl Into<Cow<'a, str>>) -> Self {
+        self.doc = Some(doc.into());
+        self
+    }
+
     /// Create a schema node from a definition
     fn from_define(define: Define<'a>) -> Result<Self, ParseError> {
         let indent = define.indent;
-        let name = define.key;
-        let value = define.value;
-        let (modifier, value) = Modifier::from_str_start(value);
+        let name = unquote(define.key);
+        let (modifier, value) = Modifier::from_str_start(&define.value);
         let mut v = value.splitn(2, ' ');
         if let Some(tp) = v.next() {
             let node_type: Type = tp.parse()?;
             let default = v
                 .next()
-                .map(|dflt| node_type.parse_value(&modifier, dflt))
+                .map(|dflt| node_type.parse_default(&modifier, dflt))
                 .transpose()?;
             Ok(Node {
                 indent,
@@ -328,12 +603,86 @@ impl<'a> Node<'a> {
                 modifier,
                 node_type,
                 default,
+                doc: None,
             })
         } else {
             Err(ParseError::InvalidType)
         }
     }
 
+    /// Get the key name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the nesting depth (0 for root nodes)
+    pub fn depth(&self) -> usize {
+        self.indent
+    }
+
+    /// Get the type modifier
+    pub fn modifier(&self) -> Option<Modifier> {
+        self.modifier
+    }
+
+    /// Get the node type
+    pub fn node_type(&self) -> Type {
+        self.node_type
+    }
+
+    /// Get the default value
+    pub fn default(&self) -> Option<&Value> {
+        self.default.as_ref()
+    }
+
+    /// Get the doc comment
+    pub fn doc(&self) -> Option<&str> {
+        self.doc.as_deref()
+    }
+
+    /// Write the node as a schema line
+    fn write_line(&self, text: &mut String, n_indent: usize) {
+        let indent = self.indent * n_indent;
+        for line in self.doc.iter().flat_map(|doc| doc.lines()) {
+            let _ = writeln!(text, "{:indent$}# {line}", "");
+        }
+        let name = quoted_key(&self.name);
+        let _ = write!(text, "{:indent$}{name}: ", "");
+        if let Some(modifier) = self.modifier {
+            let _ = write!(text, "{modifier} ");
+        }
+        let _ = write!(text, "{}", self.node_type);
+        if let Some(dflt) = self.default.clone().and_then(Value::into_text) {
+            let _ = write!(text, " {dflt}");
+        }
+        text.push('\n');
+    }
+
+    /// Check if a value is required for the node
+    fn is_required(&self) -> bool {
+        self.modifier.is_none() && self.default.is_none()
+    }
+
+    /// Check a definition value
+    fn check_value(
+        &self,
+        separator: Separator,
+        v: &str,
+    ) -> Result<(), ParseError> {
+        match (self.node_type, separator) {
+            (Type::Text, _) | (Type::Any, _) => Ok(()),
+            (Type::Record, _) | (Type::Dictionary, _) => Ok(()),
+            (node_type, Separator::Normal) => match self.modifier {
+                Some(Modifier::List) => v
+                    .split(' ')
+                    .filter(|item| !item.is_empty())
+                    .try_for_each(|item| node_type.check_value(item)),
+                _ => node_type.check_value(v),
+            },
+            (node_type, _) => node_type.check_value(""),
+        }
+    }
+
     /// Check if node indent is valid with previous node
     fn is_indent_valid(&self, prev: Option<&Self>) -> bool {
         match prev {
@@ -352,13 +701,121 @@ impl<'a> Node<'a> {
 }
 
 impl<'a> Schema<'a> {
-    /// Create a new schema
-    pub(crate) fn new() -> Self {
+    /// Parse a schema
+    ///
+    /// If `text` starts with a `:::` line, the schema is read from that
+    /// block, and the rest of the document is ignored.  Otherwise, `text` is
+    /// a standalone schema file.
+    ///
+    /// # Errors
+    ///
+    /// An error will be returned if the schema is malformed.
+    pub fn parse(text: &'a str) -> Result<Self, Error> {
+        let block = LineIter::new(text)
+            .find(|line| !matches!(line, Ok(Line::Blank | Line::Comment(_))));
+        let mut defs = match block {
+            Some(Ok(Line::SchemaSeparator)) => DefIter::new(text),
+            _ => DefIter::new_schema(text),
+        };
+        // Stop at the first definition after the schema
+        if let Some(Err(e)) = defs.next() {
+            let position = defs.position();
+            let location = position.error_location(e, "".into());
+            return Err(Error::from(e).located(location));
+        }
+        defs.into_schema()
+            .ok_or(Error::from(ParseError::ExpectedMore))
+    }
+
+    /// Infer a schema from a document without one
+    ///
+    /// The type of each key is guessed from its values.  Repeated keys are
+    /// described as `list`, keys missing from some records as `optional`, and
+    /// keys with nested definitions as `record`.  A value substituted for the
+    /// first field of a record is described by a field named after the
+    /// record key, since the real name is unknown.
+    ///
+    /// ```rust
+    /// # use muon_rs::Schema;
+    /// # fn main() -> Result<(), muon_rs::Error> {
+    /// let muon = "book: Hamlet\n  year: 1603\nbook: Ulysses\n  \
+    ///             year: 1922\n  rating: 4.5 3\n";
+    /// let schema = Schema::infer(muon)?;
+    /// assert_eq!(
+    ///     schema.to_string(),
+    ///     "book: list record\n  book: text\n  year: int\n  \
+    ///      rating: list number\n",
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// An error will be returned if the document is malformed.
+    pub fn infer(text: &str) -> Result<Schema<'static>, Error> {
+        shape::infer(text)
+    }
+
+    /// Get all nodes, in the order they were defined
+    pub fn nodes(&self) -> impl Iterator<Item = &Node<'a>> {
+        self.nodes.iter()
+    }
+
+    /// Find a node by key path
+    pub fn find(&self, path: &[&str]) -> Option<&Node<'a>> {
+        let mut node = None;
+        for name in path {
+            node = Some(self.child(node, name)?);
+        }
+        node.map(|n| self.node(n))
+    }
+
+    /// Validate a MuON document against the schema
+    ///
+    /// # Errors
+    ///
+    /// All keys which violate the schema are returned.
+    pub fn validate(&self, text: &str) -> Result<(), Vec<ValidationError>> {
+        let mut validator = Validator::new(self);
+        let mut defs = DefIter::new(text);
+        while let Some(define) = defs.next() {
+            match define {
+                Ok(define) => validator.check_define(&define, defs.position()),
+                Err(e) => {
+                    let position = defs.position();
+                    let offset = position.key_offset();
+                    validator.push_error(Vec::new(), None, e, position, offset);
+                    break;
+                }
+            }
+        }
+        let errors = validator.finish();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Create a new empty schema
+    pub fn new() -> Self {
         let nodes = Vec::new();
         let finished = false;
         Schema { nodes, finished }
     }
 
+    /// Add a node after all existing nodes
+    ///
+    /// # Errors
+    ///
+    /// [`ParseError::InvalidIndent`] is returned if the node is nested more
+    /// than one level deeper than the last node, or if the last node cannot
+    /// have children.
+    pub fn push(&mut self, node: Node<'a>) -> Result<(), Error> {
+        self.add_node(node).map_err(Error::from)
+    }
+
     /// Add node
     fn add_node(&mut self, node: Node<'a>) -> Result<(), ParseError> {
         node.is_indent_valid(self.nodes.last())
@@ -369,15 +826,18 @@ impl<'a> Schema<'a> {
     }
 
     /// Add a define
+    ///
+    /// If the schema is finished, the define is returned.
     pub(crate) fn add_define(
         &mut self,
         def: Define<'a>,
-    ) -> Result<bool, ParseError> {
-        let not_finished = !self.finished;
-        if not_finished {
+    ) -> Result<Option<Define<'a>>, ParseError> {
+        if self.finished {
+            Ok(Some(def))
+        } else {
             self.add_node(Node::from_define(def)?)?;
+            Ok(None)
         }
-        Ok(not_finished)
     }
 
     /// Finish the schema
@@ -386,4 +846,570 @@ impl<'a> Schema<'a> {
         self.finished = true;
         finished
     }
+
+    /// Get a node by index
+    pub(crate) fn node(&self, n: usize) -> &Node<'a> {
+        &self.nodes[n]
+    }
+
+    /// Push a node (indent must be valid)
+    pub(crate) fn push_node(&mut self, node: Node<'a>) {
+        self.nodes.push(node);
+    }
+
+    /// Check if the schema has no nodes
+    pub(crate) fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Get the schema as MuON text, with a number of spaces per indent
+    pub(crate) fn to_text(&self, n_indent: usize) -> String {
+        let mut text = String::new();
+        for node in &self.nodes {
+            node.write_line(&mut text, n_indent);
+        }
+        text
+    }
+
+    /// Get indices of child nodes (`None` parent for root nodes)
+    fn children(
+        &self,
+        parent: Option<usize>,
+    ) -> impl Iterator<Item = usize> + '_ {
+        let (start, indent) = match parent {
+            Some(p) => (p + 1, self.nodes[p].indent + 1),
+            None => (0, 0),
+        };
+        self.nodes[start..]
+            .iter()
+            .take_while(move |node| node.indent >= indent)
+            .enumerate()
+            .filter(move |(_, node)| node.indent == indent)
+            .map(move |(i, _)| start + i)
+    }
+
+    /// Find a child node by name
+    pub(crate) fn child(
+        &self,
+        parent: Option<usize>,
+        name: &str,
+    ) -> Option<usize> {
+        self.children(parent).find(|n| self.nodes[*n].name == name)
+    }
+
+    /// Get names of child nodes of a record, by key path
+    ///
+    /// Returns `None` if the path was not found, or is not a record.
+    pub(crate) fn record_names(
+        &self,
+        path: &[&str],
+    ) -> Option<Vec<Cow<'a, str>>> {
+        let mut node = None;
+        for name in path {
+            node = Some(self.child(node, name)?);
+        }
+        match node.map(|n| self.node(n).node_type()) {
+            None | Some(Type::Record) => Some(self.child_names(node)),
+            _ => None,
+        }
+    }
+
+    /// Get names of all child nodes
+    pub(crate) fn child_names(
+        &self,
+        parent: Option<usize>,
+    ) -> Vec<Cow<'a, str>> {
+        self.children(parent)
+            .map(|n| self.nodes[n].name.clone())
+            .collect()
+    }
+
+    /// Get the key path of a node
+    fn node_path(&self, n: usize) -> String {
+        let mut names = vec![&self.nodes[n].name[..]];
+        let mut indent = self.nodes[n].indent;
+        for node in self.nodes[..n].iter().rev() {
+            if node.indent < indent {
+                names.push(&node.name);
+                indent = node.indent;
+            }
+        }
+        names.reverse();
+        names.join(".")
+    }
+}
+
+impl Default for Schema<'_> {
+    fn default() -> Self {
+        Schema::new()
+    }
+}
+
+impl fmt::Display for Schema<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(&self.to_text(2))
+    }
+}
+
+/// Rust type which can be described by a [`Schema`]
+///
+/// It is implemented for primitive types, [`String`], dates and times,
+/// [`Value`], maps, and [`Option`] / [`Vec`] of those.  Structs can
+/// implement it with `#[derive(MuonSchema)]`, using the `derive` feature.
+///
+/// ```rust
+/// # #[cfg(feature = "derive")]
+/// # fn main() -> Result<(), muon_rs::Error> {
+/// use muon_rs::MuonSchema;
+///
+/// #[derive(MuonSchema)]
+/// struct Book {
+///     title: String,
+///     /// Year first published
+///     year: Option<i16>,
+///     #[muon(default = "paperback")]
+///     binding: String,
+///     character: Vec<Character>,
+/// }
+///
+/// #[derive(MuonSchema)]
+/// struct Character {
+///     name: String,
+/// }
+///
+/// let schema = Book::schema()?;
+/// assert_eq!(
+///     schema.to_string(),
+///     "title: text\n# Year first published\nyear: optional int\n\
+///      binding: text paperback\ncharacter: list record\n  name: text\n",
+/// );
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "derive"))]
+/// # fn main() {}
+/// ```
+pub trait MuonSchema {
+    /// Node type
+    const NODE_TYPE: Type;
+
+    /// Type modifier
+    const MODIFIER: Option<Modifier> = None;
+
+    /// Add nodes describing the keys of a record
+    ///
+    /// * `schema`: Schema to add nodes to
+    /// * `depth`: Nesting depth of the keys
+    fn add_nodes(
+        _schema: &mut Schema<'static>,
+        _depth: usize,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Create a schema describing the type
+    ///
+    /// # Errors
+    ///
+    /// An error will be returned if a default value does not match its type.
+    fn schema() -> Result<Schema<'static>, Error> {
+        let mut schema = Schema::new();
+        Self::add_nodes(&mut schema, 0)?;
+        Ok(schema)
+    }
+}
+
+macro_rules! impl_muon_schema {
+    ($tp:ident) => {};
+    ($tp:ident $i:ident $($more:ident)*) => {
+        impl MuonSchema for $i {
+            const NODE_TYPE: Type = Type::$tp;
+        }
+        impl_muon_schema!($tp $($more)*);
+    };
+}
+
+impl_muon_schema!(Text String str char);
+impl_muon_schema!(Bool bool);
+impl_muon_schema!(Int i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+impl_muon_schema!(Number f32 f64);
+impl_muon_schema!(DateTime DateTime);
+impl_muon_schema!(Date Date);
+impl_muon_schema!(Time Time);
+impl_muon_schema!(Any Value);
+
+impl<T: MuonSchema + ?Sized> MuonSchema for Box<T> {
+    const NODE_TYPE: Type = T::NODE_TYPE;
+    const MODIFIER: Option<Modifier> = T::MODIFIER;
+
+    fn add_nodes(
+        schema: &mut Schema<'static>,
+        depth: usize,
+    ) -> Result<(), Error> {
+        T::add_nodes(schema, depth)
+    }
+}
+
+impl<T: MuonSchema> MuonSchema for Option<T> {
+    const NODE_TYPE: Type = T::NODE_TYPE;
+    // Lists may already be empty
+    const MODIFIER: Option<Modifier> = match T::MODIFIER {
+        Some(Modifier::List) => Some(Modifier::List),
+        _ => Some(Modifier::Optional),
+    };
+
+    fn add_nodes(
+        schema: &mut Schema<'static>,
+        depth: usize,
+    ) -> Result<(), Error> {
+        T::add_nodes(schema, depth)
+    }
+}
+
+impl<T: MuonSchema> MuonSchema for Vec<T> {
+    const NODE_TYPE: Type = T::NODE_TYPE;
+    const MODIFIER: Option<Modifier> = Some(Modifier::List);
+
+    fn add_nodes(
+        schema: &mut Schema<'static>,
+        depth: usize,
+    ) -> Result<(), Error> {
+        T::add_nodes(schema, depth)
+    }
+}
+
+impl<K, V, S> MuonSchema for HashMap<K, V, S> {
+    const NODE_TYPE: Type = Type::Dictionary;
+}
+
+impl<K, V> MuonSchema for BTreeMap<K, V> {
+    const NODE_TYPE: Type = Type::Dictionary;
+}
+
+/// Error found while validating a document against a [`Schema`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationError {
+    /// Key path in document
+    key: Vec<String>,
+    /// Key path of schema node
+    node: Option<String>,
+    /// Parse error
+    error: ParseError,
+    /// Location in document
+    location: Location,
+}
+
+impl ValidationError {
+    /// Get the key path in the document which violated the schema
+    pub fn key(&self) -> &[String] {
+        &self.key
+    }
+
+    /// Get the dotted key path of the violated schema node, if any
+    pub fn node(&self) -> Option<&str> {
+        self.node.as_deref()
+    }
+
+    /// Get the parse error
+    pub fn error(&self) -> ParseError {
+        self.error
+    }
+
+    /// Get the location in the document
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.error)?;
+        if !self.key.is_empty() {
+            write!(formatter, " at key `{}`", self.key.join("."))?;
+        }
+        if let Some(node) = &self.node {
+            write!(formatter, " (schema node `{node}`)")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Nesting level of a document being validated
+struct Level<'p> {
+    /// Key path
+    key: Vec<String>,
+    /// Position of key
+    position: Position<'p>,
+    /// Schema node (`None` for root)
+    node: Option<usize>,
+    /// Flag indicating child keys are described by the schema
+    checked: bool,
+    /// Child nodes which have been seen
+    seen: Vec<usize>,
+}
+
+/// Validator for checking a document against a schema
+pub(crate) struct Validator<'s, 'a, 'p> {
+    /// Schema to check
+    schema: &'s Schema<'a>,
+    /// Stack of nesting levels
+    stack: Vec<Level<'p>>,
+    /// Errors found
+    errors: Vec<ValidationError>,
+}
+
+impl<'s, 'a, 'p> Validator<'s, 'a, 'p> {
+    /// Create a new validator
+    pub(crate) fn new(schema: &'s Schema<'a>) -> Self {
+        let root = Level {
+            key: Vec::new(),
+            position: Position::default(),
+            node: None,
+            checked: true,
+            seen: Vec::new(),
+        };
+        Validator {
+            schema,
+            stack: vec![root],
+            errors: Vec::new(),
+        }
+    }
+
+    /// Add an error at a byte offset into a line
+    fn push_error(
+        &mut self,
+        key: Vec<String>,
+        node: Option<usize>,
+        e: ParseError,
+        position: &Position,
+        offset: usize,
+    ) {
+        let node = node.map(|n| self.schema.node_path(n));
+        let location = position.location(offset, key.join("."));
+        self.errors.push(ValidationError {
+            key,
+            node,
+            error: e,
+            location,
+        });
+    }
+
+    /// Pop nesting levels until the stack has `len` levels
+    fn pop_levels(&mut self, len: usize) {
+        while self.stack.len() > len {
+            if let Some(level) = self.stack.pop() {
+                self.check_missing(level);
+            }
+        }
+    }
+
+    /// Check for required children missing from a level
+    ///
+    /// Missing root fields are reported at the last line read.
+    fn check_missing(&mut self, level: Level) {
+        if !level.checked {
+            return;
+        }
+        for n in self.schema.children(level.node) {
+            let node = self.schema.node(n);
+            if node.is_required() && !level.seen.contains(&n) {
+                let mut key = level.key.clone();
+                key.push(node.name.to_string());
+                let e = ParseError::MissingField;
+                let position = &level.position;
+                let offset = position.key_offset();
+                self.push_error(key, Some(n), e, position, offset);
+            }
+        }
+    }
+
+    /// Check one definition at a line position
+    pub(crate) fn check_define(
+        &mut self,
+        define: &Define,
+        position: &Position<'p>,
+    ) {
+        // Root level position is the last line read
+        self.stack[0].position = position.clone();
+        // Appended text continues the previous definition
+        if define.separator == Separator::TextAppend {
+            return;
+        }
+        let offset = position.key_offset();
+        if define.indent >= self.stack.len() {
+            let e = ParseError::InvalidIndent;
+            self.push_error(Vec::new(), None, e, position, offset);
+            return;
+        }
+        self.pop_levels(define.indent + 1);
+        let parent = &self.stack[define.indent];
+        let name = unquoted_key(&define.key);
+        let mut key = parent.key.clone();
+        key.push(name.to_string());
+        let mut level = Level {
+            key,
+            position: position.clone(),
+            node: None,
+            checked: false,
+            seen: Vec::new(),
+        };
+        if parent.checked {
+            match self.schema.child(parent.node, &name) {
+                Some(n) => {
+                    let seen = &mut self.stack[define.indent].seen;
+                    let list = self.schema.node(n).modifier;
+                    if seen.contains(&n) && list != Some(Modifier::List) {
+                        let key = level.key.clone();
+                        let e = ParseError::DuplicateKey;
+                        self.push_error(key, Some(n), e, position, offset);
+                    } else {
+                        seen.push(n);
+                    }
+                    self.check_node(&mut level, n, define, position);
+                }
+                None => {
+                    let key = level.key.clone();
+                    let e = ParseError::UnexpectedKey;
+                    self.push_error(key, None, e, position, offset);
+                }
+            }
+        }
+        self.stack.push(level);
+    }
+
+    /// Check a definition described by a schema node
+    fn check_node(
+        &mut self,
+        level: &mut Level,
+        n: usize,
+        define: &Define,
+        position: &Position,
+    ) {
+        let node = self.schema.node(n);
+        level.node = Some(n);
+        level.checked = !matches!(node.node_type, Type::Dictionary | Type::Any);
+        let (n, node) = match node.node_type {
+            // Substitute value for first field of record
+            Type::Record if !define.value.is_empty() => {
+                match self.schema.children(Some(n)).next() {
+                    Some(first) => {
+                        level.seen.push(first);
+                        (first, self.schema.node(first))
+                    }
+                    None => {
+                        let key = level.key.clone();
+                        let e = ParseError::InvalidSubstitute;
+                        let offset = position.value_offset(&define.value);
+                        self.push_error(key, Some(n), e, position, offset);
+                        return;
+                    }
+                }
+            }
+            _ => (n, node),
+        };
+        if let Err(e) = node.check_value(define.separator, &define.value) {
+            let offset = position.value_offset(&define.value);
+            self.push_error(level.key.clone(), Some(n), e, position, offset);
+        }
+    }
+
+    /// Finish validation, returning all errors found
+    pub(crate) fn finish(mut self) -> Vec<ValidationError> {
+        self.pop_levels(0);
+        self.errors
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    const SCHEMA: &str = "name: text\nscore: int\nflag: optional bool\n\
+                          tag: list text\nwhen: date\nitem: list record\n  \
+                          id: int\n  note: optional text\n  size: number 1.0\n";
+
+    #[test]
+    fn parse() -> Result<(), Error> {
+        let schema = Schema::parse(SCHEMA)?;
+        assert_eq!(schema.nodes().count(), 9);
+        let node = schema.find(&["item", "size"]).unwrap();
+        assert_eq!(node.name(), "size");
+        assert_eq!(node.depth(), 1);
+        assert_eq!(node.modifier(), None);
+        assert_eq!(node.node_type(), Type::Number);
+        assert_eq!(node.default(), Some(&Value::Number(NumValue::Num32(1.0))));
+        let node = schema.find(&["item"]).unwrap();
+        assert_eq!(node.modifier(), Some(Modifier::List));
+        assert_eq!(node.node_type(), Type::Record);
+        assert!(schema.find(&["size"]).is_none());
+        let doc = format!("# Comment\n:::\n{SCHEMA}:::\nname: first\n");
+        let schema = Schema::parse(&doc)?;
+        assert_eq!(schema.nodes().count(), 9);
+        match Schema::parse("a: text\nb: whatever\n").unwrap_err().kind() {
+            ErrorKind::FailedParse(ParseError::InvalidType) => (),
+            r => panic!("bad error {r:?}"),
+        }
+        for bad in ["a: int x\n", "a: date 2020\n", "a: list int 1\n"] {
+            match Schema::parse(bad).unwrap_err().kind() {
+                ErrorKind::FailedParse(ParseError::InvalidDefault) => (),
+                r => panic!("bad error {r:?}"),
+            }
+        }
+        for (tp, dflt) in [(Type::Bool, "x"), (Type::Record, "")] {
+            let node = Node::new(0, "a", None, tp);
+            match node.with_default(dflt) {
+                Err(e) => match e.kind() {
+                    ErrorKind::FailedParse(ParseError::InvalidDefault) => (),
+                    r => panic!("bad error {r:?}"),
+                },
+                Ok(_) => panic!("bad default {dflt:?}"),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn validate() -> Result<(), Error> {
+        let schema = Schema::parse(SCHEMA)?;
+        assert_eq!(
+            schema.validate(
+                "name: first\nscore: 5\ntag: a b\ntag: c\n\
+                 when: 2020-02-20\nitem: 1\n  note: one\nitem:\n  id: 2\n"
+            ),
+            Ok(())
+        );
+        let errors = schema
+            .validate(
+                "name: first\nscore: five\nflag: true\nflag: maybe\n\
+                 item: x\nitem:\n  note: text\n  other: 1.5\n",
+            )
+            .unwrap_err();
+        let lines: Vec<_> =
+            errors.iter().map(|e| e.location().line()).collect();
+        assert_eq!(lines, [2, 4, 4, 5, 8, 6, 8]);
+        assert_eq!(errors[6].location().snippet(), "  other: 1.5");
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|e| (e.key().join("."), e.node(), e.error()))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                ("score".into(), Some("score"), ParseError::ExpectedInt),
+                ("flag".into(), Some("flag"), ParseError::DuplicateKey),
+                ("flag".into(), Some("flag"), ParseError::ExpectedBool),
+                ("item".into(), Some("item.id"), ParseError::ExpectedInt),
+                ("item.other".into(), None, ParseError::UnexpectedKey),
+                ("item.id".into(), Some("item.id"), ParseError::MissingField),
+                ("when".into(), Some("when"), ParseError::MissingField),
+            ]
+        );
+        let errors = schema.validate("").unwrap_err();
+        assert_eq!(errors[0].location().line(), 1);
+        let schema = Schema::parse("num: list int\n")?;
+        assert_eq!(schema.validate("num: 1  2\n"), Ok(()));
+        Ok(())
+    }
 }
@@ -3,16 +3,23 @@
 // Copyright (c) 2019-2020  Douglas Lau
 //
 use crate::common::Separator;
-use crate::error::{Error, Result};
+use crate::error::{Error, ErrorKind, Location, Result};
+use crate::schema::{Schema, Type, RECORD_NAME};
+use crate::shape::{self, Shape};
 use serde::{ser, Serialize};
-use std::io::Write;
+use std::io::{self, Write};
 
 /// Item which can be serialized to a writer
 trait Item {
+    /// Schema type of item
+    const NODE_TYPE: Type;
+
     fn write<W: Write>(&self, writer: &mut W) -> Result<()>;
 }
 
 impl Item for bool {
+    const NODE_TYPE: Type = Type::Bool;
+
     fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
         if *self {
             write!(writer, "true")?;
@@ -24,20 +31,26 @@ impl Item for bool {
 }
 
 macro_rules! impl_item {
-    () => {};
-    ($i:ident $($more:ident)*) => {
+    ($tp:ident) => {};
+    ($tp:ident $i:ident $($more:ident)*) => {
         impl Item for $i {
+            const NODE_TYPE: Type = Type::$tp;
+
             fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
                 Ok(write!(writer, "{}", *self)?)
             }
         }
-        impl_item!($($more)*);
+        impl_item!($tp $($more)*);
     };
 }
 
-impl_item!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize f32 f64 char);
+impl_item!(Int i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+impl_item!(Number f32 f64);
+impl_item!(Text char);
 
 impl Item for &str {
+    const NODE_TYPE: Type = Type::Text;
+
     fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
         Ok(write!(writer, "{}", *self)?)
     }
@@ -68,20 +81,356 @@ enum Modifier {
 struct Branch {
     /// Current field key
     key: Option<String>,
+    /// Blank key flag (for repeated keys)
+    blank: bool,
     /// Current field number
     n_field: u32,
     /// Current field modifier
     modifier: Modifier,
     /// Fields visited flag
     visited: bool,
+    /// Enum variant flag
+    variant: bool,
+    /// Dictionary flag
+    dictionary: bool,
+    /// Name of struct
+    name: Option<&'static str>,
+    /// Flag indicating struct is an adjacently tagged enum
+    adjacent: bool,
+    /// Flag indicating branch is the content of an adjacently tagged enum
+    content: bool,
+    /// Names of keys in branch (unquoted)
+    names: Vec<String>,
+    /// List flag for current key
+    list: bool,
+    /// Flag indicating current key is not text
+    typed_key: bool,
+    /// Flag indicating current value is an enum tag
+    tag: bool,
+    /// Flag indicating current value has multiple lines
+    multiline: bool,
+    /// Key of a record value, which its first field must match to be
+    /// substituted
+    record_key: Option<String>,
+}
+
+/// Style of list output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ListStyle {
+    /// Merge items onto one line where possible, such as `list: 1 2 3`
+    #[default]
+    Merge,
+    /// Write each item on its own line, repeating the key
+    Repeat,
+}
+
+/// Style of text output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextStyle {
+    /// Use `: ` where possible, and `:=` only when required
+    #[default]
+    Normal,
+    /// Use `:=` for every text value
+    Value,
+    /// Use `:>` on the first line of multi-line text (outside of lists)
+    Append,
+}
+
+/// Line ending of output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// Line feed (`\n`)
+    #[default]
+    Lf,
+    /// Carriage return and line feed (`\r\n`)
+    CrLf,
+}
+
+/// Writer which translates line feeds to a line ending
+struct LineWriter<W: Write> {
+    /// Inner writer
+    writer: W,
+    /// Line ending
+    line_ending: LineEnding,
+}
+
+impl<W: Write> Write for LineWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.line_ending {
+            LineEnding::Lf => self.writer.write(buf),
+            LineEnding::CrLf => {
+                for (i, part) in buf.split(|b| *b == b'\n').enumerate() {
+                    if i > 0 {
+                        self.writer.write_all(b"\r\n")?;
+                    }
+                    self.writer.write_all(part)?;
+                }
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Options for serializing MuON
+///
+/// # Example
+/// ```
+/// # use serde::Serialize;
+/// use muon_rs::{ListStyle, SerializerOptions};
+///
+/// #[derive(Serialize)]
+/// struct Person {
+///     name: String,
+///     scores: Vec<u32>,
+/// }
+/// let person = Person {
+///     name: "Arthur".to_string(),
+///     scores: vec![7, 9],
+/// };
+/// let options = SerializerOptions::new()
+///     .indent(4)
+///     .list_style(ListStyle::Repeat);
+/// let muon = options.to_string(&person).unwrap();
+/// assert_eq!(muon, "name: Arthur\nscores: 7\nscores: 9\n");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SerializerOptions {
+    /// Number of spaces per indent
+    indent: usize,
+    /// Record substitution flag
+    substitute: bool,
+    /// List style
+    list_style: ListStyle,
+    /// Text style
+    text_style: TextStyle,
+    /// Schema flag
+    schema: bool,
+    /// Line ending
+    line_ending: LineEnding,
+    /// Tag names of internally tagged enums
+    enum_tags: &'static [&'static str],
+}
+
+impl Default for SerializerOptions {
+    fn default() -> Self {
+        SerializerOptions {
+            indent: 2,
+            substitute: true,
+            list_style: ListStyle::default(),
+            text_style: TextStyle::default(),
+            schema: false,
+            line_ending: LineEnding::default(),
+            enum_tags: &["type"],
+        }
+    }
+}
+
+impl SerializerOptions {
+    /// Create default serializer options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of spaces per indent (2, 3 or 4)
+    ///
+    /// **Note:** MuON only allows indents of 2, 3 or 4 spaces, so other
+    /// values are clamped to that range: `1` becomes `2`, and anything
+    /// above `4` becomes `4`.
+    pub fn indent(mut self, n_indent: usize) -> Self {
+        self.indent = n_indent.clamp(2, 4);
+        self
+    }
+
+    /// Set whether the first field of a record is substituted onto the
+    /// record key line
+    ///
+    /// Substituted struct variants of untagged enums may be read back as a
+    /// different variant, so turn this off if that is ambiguous.
+    pub fn substitute(mut self, substitute: bool) -> Self {
+        self.substitute = substitute;
+        self
+    }
+
+    /// Set the list style
+    pub fn list_style(mut self, list_style: ListStyle) -> Self {
+        self.list_style = list_style;
+        self
+    }
+
+    /// Set the text style
+    pub fn text_style(mut self, text_style: TextStyle) -> Self {
+        self.text_style = text_style;
+        self
+    }
+
+    /// Set the line ending
+    ///
+    /// # Example
+    /// ```
+    /// use muon_rs::{LineEnding, SerializerOptions};
+    /// use std::collections::BTreeMap;
+    ///
+    /// let map = BTreeMap::from([("a", 1), ("b", 2)]);
+    /// let options = SerializerOptions::new().line_ending(LineEnding::CrLf);
+    /// assert_eq!(options.to_string(&map).unwrap(), "a: 1\r\nb: 2\r\n");
+    /// ```
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Set the tag names of internally tagged enums
+    ///
+    /// serde writes the tag of an internally tagged enum
+    /// (`#[serde(tag = "...")]`) as the first field of a record.  Tags are
+    /// read back without knowing field names, so a first field with one of
+    /// these names is never substituted onto the record key line.  The
+    /// default is `["type"]`.
+    ///
+    /// # Example
+    /// ```
+    /// # use serde::Serialize;
+    /// use muon_rs::SerializerOptions;
+    ///
+    /// #[derive(Serialize)]
+    /// #[serde(tag = "kind")]
+    /// enum Shape {
+    ///     Circle { radius: f64 },
+    /// }
+    /// #[derive(Serialize)]
+    /// struct Drawing {
+    ///     shape: Shape,
+    /// }
+    /// let drawing = Drawing {
+    ///     shape: Shape::Circle { radius: 2.5 },
+    /// };
+    /// let options = SerializerOptions::new().enum_tags(&["kind"]);
+    /// assert_eq!(
+    ///     options.to_string(&drawing).unwrap(),
+    ///     "shape:\n  kind: Circle\n  radius: 2.5\n",
+    /// );
+    /// ```
+    pub fn enum_tags(mut self, enum_tags: &'static [&'static str]) -> Self {
+        self.enum_tags = enum_tags;
+        self
+    }
+
+    /// Set whether a schema block (`:::`) is written ahead of the data
+    ///
+    /// The schema records key names, types, and `optional` or `list`
+    /// modifiers of the serialized data.  Enum variants are described as
+    /// `any`, and maps other than the root as `dictionary`.  Keys which are
+    /// sometimes lists, such as the content of adjacently tagged enums, are
+    /// described as `optional any`, and may not be read back.
+    ///
+    /// Types are recorded from the serialized values, not from the Rust
+    /// types:
+    ///
+    /// * Text which is always a valid date or time, such as a
+    ///   [`Date`](crate::Date), is described as that type.
+    /// * A key which is always `None` has no values to record a type from,
+    ///   so it is described as `optional any`.
+    ///
+    /// The schema is only written by the `to_` methods of
+    /// `SerializerOptions`, since the data must be buffered until it is
+    /// complete.
+    ///
+    /// # Example
+    /// ```
+    /// # use serde::Serialize;
+    /// use muon_rs::SerializerOptions;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Person {
+    ///     name: String,
+    ///     born: Option<u32>,
+    /// }
+    /// let person = Person {
+    ///     name: "Arthur".to_string(),
+    ///     born: Some(1788),
+    /// };
+    /// let muon = SerializerOptions::new().schema(true).to_string(&person);
+    /// assert_eq!(
+    ///     muon.unwrap(),
+    ///     ":::\nname: text\nborn: optional int\n:::\nname: Arthur\nborn: 1788\n",
+    /// );
+    /// ```
+    pub fn schema(mut self, schema: bool) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// Serialize `T` to a String in MuON format
+    ///
+    /// # Errors
+    ///
+    /// Serialization can fail if the serializer for `T` returns an error, or
+    /// if a map key is not a scalar value.
+    pub fn to_string<T>(self, value: &T) -> Result<String>
+    where
+        T: Serialize,
+    {
+        Ok(String::from_utf8(self.to_vec(value)?)?)
+    }
+
+    /// Serialize `T` to a Vec of bytes in MuON format
+    ///
+    /// # Errors
+    ///
+    /// Serialization can fail if the serializer for `T` returns an error, or
+    /// if a map key is not a scalar value.
+    pub fn to_vec<T>(self, value: &T) -> Result<Vec<u8>>
+    where
+        T: Serialize,
+    {
+        let mut writer = vec![];
+        self.to_writer(&mut writer, value)?;
+        Ok(writer)
+    }
+
+    /// Serialize `T` to an IO writer in MuON format
+    ///
+    /// # Errors
+    ///
+    /// Serialization can fail if the serializer for `T` returns an error, or
+    /// if a map key is not a scalar value.
+    pub fn to_writer<W, T>(self, writer: W, value: &T) -> Result<()>
+    where
+        W: Write,
+        T: Serialize,
+    {
+        if !self.schema {
+            let mut serializer = Serializer::with_options(writer, self);
+            return value.serialize(&mut serializer);
+        }
+        // Line endings are translated when the buffer is written out
+        let options = self.line_ending(LineEnding::Lf);
+        let mut serializer = Serializer::with_options(vec![], options);
+        value.serialize(&mut serializer)?;
+        let mut writer = LineWriter {
+            writer,
+            line_ending: self.line_ending,
+        };
+        if let Some(schema) = serializer.schema().filter(|s| !s.is_empty()) {
+            writer.write_all(b":::\n")?;
+            writer.write_all(schema.to_text(self.indent).as_bytes())?;
+            writer.write_all(b":::\n")?;
+        }
+        writer.write_all(&serializer.into_inner())?;
+        Ok(())
+    }
 }
 
 /// Structure that can serialize values into MuON.
 pub struct Serializer<W: Write> {
-    /// Number of spaces per indent
-    n_indent: usize,
+    /// Style options
+    options: SerializerOptions,
     /// Writer for output
-    writer: W,
+    writer: LineWriter<W>,
     /// Stack of branch values
     stack: Vec<Branch>,
     /// Flag if current item is a key
@@ -92,43 +441,105 @@ pub struct Serializer<W: Write> {
     line: LinePos,
     /// Current key / value separator
     separator: Separator,
+    /// Shape recorded for schema
+    shape: Option<Shape>,
+    /// Type of date / time being serialized
+    date_type: Option<Type>,
+    /// Flag indicating the next map is a record value
+    value_record: bool,
 }
 
 impl Branch {
     /// Check if substitute is allowed
     fn is_substitute_allowed(&self) -> bool {
-        self.modifier == Modifier::No && self.n_field == 1
+        // Variant keys and enum tags must always be written, map keys are
+        // data which would be lost in place of the record key, appended
+        // lines cannot follow a record key, and the content of an
+        // adjacently tagged enum is read back without knowing field names
+        !self.variant
+            && !self.tag
+            && !self.dictionary
+            && !self.content
+            && !self.typed_key
+            && !self.multiline
+            && self.modifier == Modifier::No
+            && self.n_field == 1
+            // A record value only names its first field by the record key
+            && self
+                .record_key
+                .as_ref()
+                .is_none_or(|key| self.names.first() == Some(key))
     }
 }
 
 impl<W: Write> Serializer<W> {
-    /// Create a new MuON Serializer
-    fn new(n_indent: usize, writer: W) -> Self {
-        // Indents must be at least 1 space
-        let n_indent = n_indent.max(1);
+    /// Create a new MuON Serializer with default options
+    pub fn new(writer: W) -> Self {
+        Serializer::with_options(writer, SerializerOptions::default())
+    }
+
+    /// Create a new MuON Serializer with style options
+    pub fn with_options(writer: W, options: SerializerOptions) -> Self {
         Serializer {
-            n_indent,
-            writer,
+            options,
+            writer: LineWriter {
+                writer,
+                line_ending: options.line_ending,
+            },
             stack: vec![],
             is_key: false,
             indent: 0,
             line: LinePos::Start,
             separator: Separator::Normal,
+            shape: options.schema.then(Shape::default),
+            date_type: None,
+            value_record: false,
         }
     }
 
+    /// Unwrap the writer
+    pub fn into_inner(self) -> W {
+        self.writer.writer
+    }
+
     /// Push a new branch onto stack
-    fn push_stack(&mut self) {
+    pub(crate) fn push_stack(&mut self) {
+        // Nested branches cannot follow a blank key
+        if let Some(branch) = self.stack.last_mut() {
+            branch.blank = false;
+        }
         self.stack.push(Branch {
             key: None,
+            blank: false,
             n_field: 0,
             modifier: Modifier::No,
             visited: false,
+            variant: false,
+            dictionary: false,
+            name: None,
+            adjacent: false,
+            content: false,
+            names: vec![],
+            list: false,
+            typed_key: false,
+            tag: false,
+            multiline: false,
+            record_key: None,
         });
     }
 
+    /// Push a branch for an enum variant onto stack
+    fn push_variant(&mut self, variant: &str) -> Result<()> {
+        self.push_stack();
+        if let Some(branch) = self.stack.last_mut() {
+            branch.variant = true;
+        }
+        self.ser_key(variant)
+    }
+
     /// Pop a branch from stack
     fn pop_stack(&mut self) -> Result<()> {
+        self.check_missing();
         if let Some(branch) = self.stack.pop() {
             if !branch.visited {
                 self.write_unvisited_key()?;
@@ -141,11 +552,95 @@ impl<W: Write> Serializer<W> {
 
     /// Set modifier for the top branch of stack
     fn set_modifier(&mut self, modifier: Modifier) {
+        match modifier {
+            Modifier::Optional => self.record_optional(),
+            Modifier::List => self.record_list(),
+            Modifier::No => (),
+        }
         if let Some(branch) = self.stack.last_mut() {
             branch.modifier = modifier;
         }
     }
 
+    /// Set dictionary flag for the top branch of stack
+    fn set_dictionary(&mut self) {
+        if let Some(branch) = self.stack.last_mut() {
+            branch.dictionary = true;
+        }
+    }
+
+    /// Get the shape of the current key
+    ///
+    /// Returns `None` if not recording a schema, or within a dictionary.
+    fn shape(&mut self) -> Option<&mut Shape> {
+        let mut shape = self.shape.as_mut()?;
+        for branch in &self.stack {
+            if branch.variant {
+                // Enum variants are not described by the schema
+                shape.record_type(Type::Any);
+                return None;
+            }
+            if branch.dictionary {
+                return None;
+            }
+            shape = shape.child(branch.names.last()?);
+        }
+        Some(shape)
+    }
+
+    /// Record the type of the current key
+    fn record_type(&mut self, node_type: Type) {
+        let date_type = self.date_type;
+        if let Some(shape) = self.shape() {
+            match node_type {
+                Type::Text => shape.record_text(date_type),
+                _ => shape.record_type(node_type),
+            }
+        }
+    }
+
+    /// Record a value for the current key
+    fn record_value(&mut self) {
+        if let Some(shape) = self.shape() {
+            shape.record_value();
+        }
+    }
+
+    /// Record that the current key is optional
+    fn record_optional(&mut self) {
+        if let Some(shape) = self.shape() {
+            shape.record_optional();
+        }
+    }
+
+    /// Record that the value of the current key is a list
+    fn record_list(&mut self) {
+        match self.stack.last_mut() {
+            Some(branch) if !branch.list => branch.list = true,
+            _ => return,
+        }
+        if let Some(shape) = self.shape() {
+            shape.record_list();
+        }
+    }
+
+    /// Check for keys missing from the top branch of stack
+    fn check_missing(&mut self) {
+        if let Some(branch) = self.stack.pop() {
+            if !branch.variant && !branch.dictionary {
+                if let Some(shape) = self.shape() {
+                    shape.check_missing(&branch.names);
+                }
+            }
+            self.stack.push(branch);
+        }
+    }
+
+    /// Get the recorded schema
+    fn schema(&self) -> Option<Schema<'static>> {
+        self.shape.as_ref().map(Shape::to_schema)
+    }
+
     /// Check if the current define is a list
     fn is_list(&self) -> bool {
         match self.stack.last() {
@@ -157,22 +652,23 @@ impl<W: Write> Serializer<W> {
     /// Set the current key
     fn set_key(&mut self, key: &str) {
         if let Some(branch) = self.stack.last_mut() {
+            branch.names.push(key.to_string());
             branch.key = Some(quoted_key(key));
+            branch.blank = false;
             branch.n_field += 1;
+            branch.modifier = Modifier::No;
+            branch.list = false;
+            branch.typed_key = false;
+            branch.tag = false;
+            branch.multiline = false;
         }
+        self.record_value();
     }
 
     /// Set the key to blank (for repeated keys)
     fn set_key_blank(&mut self) {
         if let Some(branch) = self.stack.last_mut() {
-            if let Some(mut key) = branch.key.take() {
-                let len = key.chars().count();
-                key.clear();
-                for _ in 0..len {
-                    key.push(' ');
-                }
-                branch.key = Some(key);
-            }
+            branch.blank = true;
         }
     }
 
@@ -189,7 +685,9 @@ impl<W: Write> Serializer<W> {
     /// Check if line should be merged
     fn is_merge_line(&self) -> bool {
         match (self.line, self.separator) {
-            (LinePos::AfterValue, Separator::Normal) => true,
+            (LinePos::AfterValue, Separator::Normal) => {
+                self.options.list_style == ListStyle::Merge
+            }
             (_, _) => false,
         }
     }
@@ -213,11 +711,26 @@ impl<W: Write> Serializer<W> {
         t.serialize(&mut *self)
     }
 
+    /// Set the tag flag if the current field is an enum tag
+    fn set_tag(&mut self, key: &str) {
+        // serde passes the tag of an internally tagged enum as the first
+        // field.  It is read back without knowing field names, so it can
+        // never be substituted.
+        let enum_tags = self.options.enum_tags;
+        if let Some(branch) = self.stack.last_mut() {
+            branch.tag = branch.n_field == 1 && enum_tags.contains(&key);
+        }
+    }
+
     /// Serialize an item
     fn ser_item<I: Item>(&mut self, item: I) -> Result<()> {
         if self.is_key {
-            return Err(Error::InvalidKey);
+            // Keys which are not text are written like values
+            let mut key = vec![];
+            item.write(&mut key)?;
+            return self.write_map_key(&String::from_utf8(key)?, true);
         }
+        self.record_type(I::NODE_TYPE);
         if self.is_merge_line() {
             write!(self.writer, " ")?;
         } else {
@@ -228,6 +741,24 @@ impl<W: Write> Serializer<W> {
         Ok(())
     }
 
+    /// Set the key of a map entry
+    fn write_map_key(&mut self, key: &str, typed: bool) -> Result<()> {
+        self.set_key(key);
+        if let Some(branch) = self.stack.last_mut() {
+            branch.typed_key = typed;
+        }
+        self.write_linefeed()
+    }
+
+    /// Check that a compound value is not being serialized as a key
+    fn check_not_key(&self) -> Result<()> {
+        if self.is_key {
+            Err(ErrorKind::InvalidKey.into())
+        } else {
+            Ok(())
+        }
+    }
+
     /// Write all necessary keys
     fn write_keys(&mut self) -> Result<()> {
         let n0 = self.indent.max(1) - 1;
@@ -251,8 +782,10 @@ impl<W: Write> Serializer<W> {
     /// Check if substitute allowed for current field
     fn is_substitute_allowed(&self) -> bool {
         match self.stack.last() {
-            Some(branch) => branch.is_substitute_allowed(),
-            _ => true,
+            Some(branch) => {
+                self.options.substitute && branch.is_substitute_allowed()
+            }
+            _ => self.options.substitute,
         }
     }
 
@@ -267,9 +800,18 @@ impl<W: Write> Serializer<W> {
     fn write_key(&mut self, n: usize) -> Result<()> {
         self.write_linefeed()?;
         self.write_indent(n)?;
+        // Text append lines always have a blank key
+        let repeat = self.options.list_style == ListStyle::Repeat
+            && self.separator != Separator::TextAppend;
         if let Some(branch) = self.stack.get_mut(n) {
             if let Some(key) = &branch.key {
-                write!(self.writer, "{}", key)?;
+                if branch.blank && !repeat {
+                    for _ in key.chars() {
+                        write!(self.writer, " ")?;
+                    }
+                } else {
+                    write!(self.writer, "{}", key)?;
+                }
             }
             self.visit_branch(n);
         }
@@ -277,7 +819,11 @@ impl<W: Write> Serializer<W> {
     }
 
     /// Write key for unvisited branch
+    ///
+    /// Keys of records containing the branch are written first, if they have
+    /// not been written yet.
     fn write_unvisited_key(&mut self) -> Result<()> {
+        self.write_record_keys()?;
         let indent = self.nesting();
         if indent > 0 {
             self.write_key(indent - 1)?;
@@ -297,17 +843,32 @@ impl<W: Write> Serializer<W> {
 
     /// Write an indentation
     fn write_indent(&mut self, n: usize) -> Result<()> {
-        for _ in 0..n * self.n_indent {
+        for _ in 0..n * self.options.indent {
             write!(self.writer, " ")?;
         }
         Ok(())
     }
 
     /// Write a text item
+    ///
+    /// Carriage returns cannot be written, since they would be read back as
+    /// part of a line ending.
     fn write_text(&mut self, v: &str) -> Result<()> {
-        if self.is_list() && v.contains(' ') {
-            self.separator = Separator::TextValue;
+        check_text(v)?;
+        if v.contains('\n') {
+            if let Some(branch) = self.stack.last_mut() {
+                branch.multiline = true;
+            }
         }
+        let list = self.is_list();
+        self.separator = match self.options.text_style {
+            TextStyle::Value => Separator::TextValue,
+            TextStyle::Append if !list && v.contains('\n') => {
+                Separator::TextAppend
+            }
+            _ if list && v.contains(' ') => Separator::TextValue,
+            _ => Separator::Normal,
+        };
         for val in v.split('\n') {
             self.ser_item(val)?;
             match self.separator {
@@ -321,8 +882,144 @@ impl<W: Write> Serializer<W> {
     }
 }
 
+/// Methods for [`Writer`](crate::Writer)
+impl<W: Write> Serializer<W> {
+    /// Begin a record with a key
+    pub(crate) fn begin_record(&mut self, key: &str) -> Result<()> {
+        self.set_key(key);
+        self.write_linefeed()?;
+        self.push_stack();
+        Ok(())
+    }
+
+    /// End the current record
+    pub(crate) fn end_record(&mut self) -> Result<()> {
+        if self.nesting() > 1 {
+            self.pop_stack()
+        } else {
+            Err(ErrorKind::Serialize("no record to end".into()).into())
+        }
+    }
+
+    /// End all records and the final line
+    pub(crate) fn end_all(&mut self) -> Result<()> {
+        while self.nesting() > 1 {
+            self.pop_stack()?;
+        }
+        self.write_linefeed()
+    }
+
+    /// Write a text value with a key
+    pub(crate) fn text(&mut self, key: &str, v: &str) -> Result<()> {
+        self.set_key(key);
+        self.write_linefeed()?;
+        self.write_text(v)
+    }
+
+    /// Write a list of values with a key
+    pub(crate) fn list<I>(&mut self, key: &str, items: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: std::fmt::Display,
+    {
+        self.set_key(key);
+        self.write_linefeed()?;
+        self.set_modifier(Modifier::List);
+        for item in items {
+            self.write_text(&item.to_string())?;
+        }
+        self.set_modifier(Modifier::No);
+        Ok(())
+    }
+
+    /// Write a comment, indented to the current record
+    pub(crate) fn comment(&mut self, text: &str) -> Result<()> {
+        self.write_record_keys()?;
+        self.write_linefeed()?;
+        let n = self.nesting().max(1) - 1;
+        for line in text.split('\n') {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            self.write_indent(n)?;
+            if line.is_empty() {
+                writeln!(self.writer, "#")?;
+            } else {
+                writeln!(self.writer, "# {line}")?;
+            }
+        }
+        // Items after a comment cannot have a blank key
+        if let Some(branch) = self.stack.last_mut() {
+            branch.blank = false;
+        }
+        Ok(())
+    }
+
+    /// Write keys of records which have not been written yet
+    fn write_record_keys(&mut self) -> Result<()> {
+        let n0 = self.indent.max(1) - 1;
+        let n1 = self.nesting();
+        for n in n0..n1.max(1) - 1 {
+            self.write_key(n)?;
+            writeln!(self.writer, ":")?;
+            self.visit_branch(n + 1);
+        }
+        self.set_indent();
+        Ok(())
+    }
+
+    /// Write a schema block
+    pub(crate) fn write_schema(&mut self, schema: &Schema) -> Result<()> {
+        if !schema.is_empty() {
+            self.writer.write_all(b":::\n")?;
+            let text = schema.to_text(self.options.indent);
+            self.writer.write_all(text.as_bytes())?;
+            self.writer.write_all(b":::\n")?;
+        }
+        Ok(())
+    }
+}
+
+/// Number of bytes written on each line
+const BYTES_PER_LINE: usize = 32;
+
+/// Get bytes as hexadecimal text, split into lines
+pub(crate) fn bytes_text(v: &[u8]) -> String {
+    use std::fmt::Write as _;
+    let mut text =
+        String::with_capacity(2 + v.len() * 2 + v.len() / BYTES_PER_LINE);
+    text.push_str("0x");
+    for (i, chunk) in v.chunks(BYTES_PER_LINE).enumerate() {
+        if i > 0 {
+            text.push('\n');
+        }
+        for b in chunk {
+            let _ = write!(text, "{b:02x}");
+        }
+    }
+    text
+}
+
+/// Check that text contains no carriage returns
+///
+/// The error is located at the first carriage return within the text.
+fn check_text(v: &str) -> Result<()> {
+    match v.find('\r') {
+        Some(cr) => {
+            let start = v[..cr].rfind('\n').map_or(0, |lf| lf + 1);
+            let end = v[cr..].find('\n').map_or(v.len(), |lf| cr + lf);
+            let line = v[..start].matches('\n').count() + 1;
+            let location =
+                Location::new(line, &v[start..end], cr - start, "".into());
+            Err(Error::from(ErrorKind::Serialize(
+                "carriage return in text".into(),
+            ))
+            .located(location))
+        }
+        None => Ok(()),
+    }
+}
+
 /// Create a quoted key
-fn quoted_key(k: &str) -> String {
+pub(crate) fn quoted_key(k: &str) -> String {
     if is_quoting_required(k) || is_quoting_suggested(k) {
         let mut s = String::new();
         s.push('"');
@@ -367,7 +1064,7 @@ fn contains_problematic_characters(k: &str) -> bool {
 }
 
 /// Check if a character is a homoglyph of colon
-fn is_colon_homoglyph(c: char) -> bool {
+pub(crate) fn is_colon_homoglyph(c: char) -> bool {
     // If this was a performance problem, we could use phf crate
     c == '\u{02D0}' || // ː Modifier Letter Triangular Colon
     c == '\u{02F8}' || // ˸ Modifier Letter Raised Colon
@@ -390,7 +1087,7 @@ fn is_colon_homoglyph(c: char) -> bool {
     c == '\u{E003A}' // 󠀺 Tag Colon
 }
 
-impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
+impl<W: Write> ser::Serializer for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
     type SerializeSeq = Self;
@@ -441,24 +1138,37 @@ impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
         self.ser_item(v)
     }
     fn serialize_char(self, v: char) -> Result<()> {
+        check_text(v.encode_utf8(&mut [0; 4]))?;
         self.ser_item(v)
     }
 
     fn serialize_str(self, v: &str) -> Result<()> {
         if self.is_key {
-            self.set_key(v);
-            self.write_linefeed()?;
+            // Date and time keys cannot be read back from the record key
+            self.write_map_key(v, shape::date_type(v).is_some())
         } else {
-            self.write_text(v)?;
+            // Dates and times are serialized as text, so check for them
+            // while recording a schema
+            if self.shape.is_some() {
+                self.date_type = shape::date_type(v);
+            }
+            let result = self.write_text(v);
+            self.date_type = None;
+            result
         }
-        Ok(())
     }
 
-    fn serialize_bytes(self, _v: &[u8]) -> Result<()> {
-        Err(Error::UnsupportedType("bytes"))
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        if self.is_key {
+            self.write_map_key(&bytes_text(v).replace('\n', ""), true)
+        } else {
+            self.write_text(&bytes_text(v))
+        }
     }
 
     fn serialize_none(self) -> Result<()> {
+        self.check_not_key()?;
+        self.record_optional();
         Ok(())
     }
 
@@ -466,35 +1176,48 @@ impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
     where
         V: ?Sized + Serialize,
     {
+        self.check_not_key()?;
         self.set_modifier(Modifier::Optional);
         value.serialize(self)
     }
 
     fn serialize_unit(self) -> Result<()> {
-        Ok(())
+        self.check_not_key()
     }
 
     fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
-        Ok(())
+        self.check_not_key()
     }
 
     fn serialize_unit_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         _index: u32,
         variant: &'static str,
     ) -> Result<()> {
-        self.serialize_str(variant)
+        if self.is_key {
+            self.write_map_key(variant, true)
+        } else {
+            // The tag of an adjacently tagged enum is a unit variant of the
+            // same name as its struct
+            if let Some(branch) = self.stack.last_mut() {
+                if branch.name == Some(name) && branch.n_field == 1 {
+                    branch.adjacent = true;
+                }
+            }
+            self.serialize_str(variant)
+        }
     }
 
     fn serialize_newtype_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         value: &V,
     ) -> Result<()>
     where
         V: ?Sized + Serialize,
     {
+        self.value_record = name == RECORD_NAME;
         value.serialize(self)
     }
 
@@ -508,11 +1231,14 @@ impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
     where
         V: ?Sized + Serialize,
     {
-        self.ser_key(variant)?;
-        self.ser_value(value)
+        self.check_not_key()?;
+        self.push_variant(variant)?;
+        self.ser_value(value)?;
+        self.pop_stack()
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        self.check_not_key()?;
         self.set_modifier(Modifier::List);
         Ok(self)
     }
@@ -536,22 +1262,50 @@ impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
+        self.check_not_key()?;
+        self.push_variant(variant)?;
         self.set_modifier(Modifier::List);
-        self.ser_key(variant)?;
         Ok(self)
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        self.push_stack();
+        self.check_not_key()?;
+        let record = std::mem::take(&mut self.value_record);
+        // Keys of root map are described as a record
+        if self.stack.is_empty() {
+            self.record_type(Type::Record);
+            self.push_stack();
+        } else if record {
+            let key = self.stack.last().and_then(|b| b.names.last()).cloned();
+            self.record_type(Type::Record);
+            self.push_stack();
+            if let Some(branch) = self.stack.last_mut() {
+                branch.record_key = key;
+            }
+        } else {
+            self.record_type(Type::Dictionary);
+            self.push_stack();
+            self.set_dictionary();
+        }
         Ok(self)
     }
 
     fn serialize_struct(
         self,
-        _name: &'static str,
+        name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStruct> {
+        self.check_not_key()?;
+        self.record_type(Type::Record);
+        let content = self
+            .stack
+            .last()
+            .is_some_and(|branch| branch.adjacent && branch.n_field == 2);
         self.push_stack();
+        if let Some(branch) = self.stack.last_mut() {
+            branch.name = Some(name);
+            branch.content = content;
+        }
         Ok(self)
     }
 
@@ -559,14 +1313,17 @@ impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
         self,
         _name: &'static str,
         _index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        Err(Error::UnsupportedType("struct variant"))
+        self.check_not_key()?;
+        self.push_variant(variant)?;
+        self.push_stack();
+        Ok(self)
     }
 }
 
-impl<'a, W: Write> ser::SerializeSeq for &'a mut Serializer<W> {
+impl<W: Write> ser::SerializeSeq for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -582,7 +1339,7 @@ impl<'a, W: Write> ser::SerializeSeq for &'a mut Serializer<W> {
     }
 }
 
-impl<'a, W: Write> ser::SerializeTuple for &'a mut Serializer<W> {
+impl<W: Write> ser::SerializeTuple for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -598,7 +1355,7 @@ impl<'a, W: Write> ser::SerializeTuple for &'a mut Serializer<W> {
     }
 }
 
-impl<'a, W: Write> ser::SerializeTupleStruct for &'a mut Serializer<W> {
+impl<W: Write> ser::SerializeTupleStruct for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -614,7 +1371,7 @@ impl<'a, W: Write> ser::SerializeTupleStruct for &'a mut Serializer<W> {
     }
 }
 
-impl<'a, W: Write> ser::SerializeTupleVariant for &'a mut Serializer<W> {
+impl<W: Write> ser::SerializeTupleVariant for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -626,11 +1383,11 @@ impl<'a, W: Write> ser::SerializeTupleVariant for &'a mut Serializer<W> {
     }
 
     fn end(self) -> Result<()> {
-        Ok(())
+        self.pop_stack()
     }
 }
 
-impl<'a, W: Write> ser::SerializeMap for &'a mut Serializer<W> {
+impl<W: Write> ser::SerializeMap for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -653,7 +1410,7 @@ impl<'a, W: Write> ser::SerializeMap for &'a mut Serializer<W> {
     }
 }
 
-impl<'a, W: Write> ser::SerializeStruct for &'a mut Serializer<W> {
+impl<W: Write> ser::SerializeStruct for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -662,6 +1419,7 @@ impl<'a, W: Write> ser::SerializeStruct for &'a mut Serializer<W> {
         V: ?Sized + Serialize,
     {
         self.ser_key(key)?;
+        self.set_tag(key);
         self.ser_value(value)
     }
 
@@ -670,22 +1428,21 @@ impl<'a, W: Write> ser::SerializeStruct for &'a mut Serializer<W> {
     }
 }
 
-impl<'a, W: Write> ser::SerializeStructVariant for &'a mut Serializer<W> {
+impl<W: Write> ser::SerializeStructVariant for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<V>(
-        &mut self,
-        _key: &'static str,
-        _value: &V,
-    ) -> Result<()>
+    fn serialize_field<V>(&mut self, key: &'static str, value: &V) -> Result<()>
     where
         V: ?Sized + Serialize,
     {
-        Err(Error::UnsupportedType("struct variant"))
+        self.ser_key(key)?;
+        self.ser_value(value)
     }
+
     fn end(self) -> Result<()> {
-        Err(Error::UnsupportedType("struct variant"))
+        self.pop_stack()?;
+        self.pop_stack()
     }
 }
 
@@ -693,56 +1450,50 @@ impl<'a, W: Write> ser::SerializeStructVariant for &'a mut Serializer<W> {
 ///
 /// # Errors
 ///
-/// Serialization can fail if the serializer for `T` returns an error.
-/// Also, some types are not supported, such as enums and byte arrays.
+/// Serialization can fail if the serializer for `T` returns an error, or
+/// if a map key is not a scalar value.
 pub fn to_string<T>(value: &T) -> Result<String>
 where
     T: Serialize,
 {
-    let mut serializer = Serializer::new(2, vec![]);
-    value.serialize(&mut serializer)?;
-    Ok(String::from_utf8(serializer.writer)?)
+    SerializerOptions::default().to_string(value)
 }
 
 /// Serialize `T` to a Vec of bytes in MuON format
 ///
 /// # Errors
 ///
-/// Serialization can fail if the serializer for `T` returns an error.
-/// Also, some types are not supported, such as enums and byte arrays.
+/// Serialization can fail if the serializer for `T` returns an error, or
+/// if a map key is not a scalar value.
 pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
 where
     T: Serialize,
 {
-    let mut serializer = Serializer::new(2, vec![]);
-    value.serialize(&mut serializer)?;
-    Ok(serializer.writer)
+    SerializerOptions::default().to_vec(value)
 }
 
 /// Serialize `T` to an IO writer in MuON format
 ///
 /// # Errors
 ///
-/// Serialization can fail if the serializer for `T` returns an error.
-/// Also, some types are not supported, such as enums and byte arrays.
+/// Serialization can fail if the serializer for `T` returns an error, or
+/// if a map key is not a scalar value.
 pub fn to_writer<W, T>(writer: W, value: &T) -> Result<()>
 where
     W: Write,
     T: Serialize,
 {
-    let mut serializer = Serializer::new(2, writer);
-    value.serialize(&mut serializer)?;
-    Ok(())
+    SerializerOptions::default().to_writer(writer, value)
 }
 
-// FIXME: add a to_value function
-
 #[cfg(test)]
 mod test {
     use super::super::datetime::*;
-    use super::{to_string, Error};
+    use super::{
+        to_string, Error, LineEnding, ListStyle, SerializerOptions, TextStyle,
+    };
     use serde::Serialize;
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
 
     #[derive(Serialize)]
     struct P {
@@ -779,8 +1530,8 @@ mod test {
             ll: 23456789012345678901,
             float: -123.456,
             double: 789.012,
-            ninf: -std::f32::INFINITY,
-            nan: std::f64::NAN,
+            ninf: -f32::INFINITY,
+            nan: f64::NAN,
         };
         assert_eq!(
             to_string(&s)?,
@@ -955,6 +1706,28 @@ string_c:=first item
         Ok(())
     }
 
+    #[derive(Serialize)]
+    struct Unit {}
+    #[derive(Serialize)]
+    struct Outer {
+        inner: Unit,
+    }
+    #[derive(Serialize)]
+    struct EmptyNested {
+        z: u8,
+        a: Outer,
+    }
+    #[test]
+    fn empty_nested() -> Result<(), Box<Error>> {
+        let text = to_string(&EmptyNested {
+            z: 0,
+            a: Outer { inner: Unit {} },
+        })?;
+        assert_eq!(text, "z: 0\na:\n  inner:\n");
+        assert!(crate::Parser::new(&text).all(|e| e.is_ok()));
+        Ok(())
+    }
+
     #[derive(Serialize)]
     struct Q {
         name: E,
@@ -1021,6 +1794,24 @@ string_c:=first item
         Ok(())
     }
 
+    #[derive(Serialize)]
+    struct O {
+        t: T,
+    }
+    #[test]
+    fn no_substitute_multiline() -> Result<(), Box<Error>> {
+        assert_eq!(
+            to_string(&O {
+                t: T {
+                    name: String::from("Your\nName"),
+                    other: None,
+                }
+            })?,
+            "t:\n  name: Your\n      :>Name\n",
+        );
+        Ok(())
+    }
+
     #[derive(Serialize)]
     struct H {
         list_g: Vec<G>,
@@ -1117,11 +1908,11 @@ string_c:=first item
     }
     #[test]
     fn date() -> Result<(), Box<Error>> {
-        let date = "2019-08-07".parse().map_err(Error::FailedParse)?;
-        let time = "12:34:56.789".parse().map_err(Error::FailedParse)?;
+        let date = "2019-08-07".parse().map_err(Error::from)?;
+        let time = "12:34:56.789".parse().map_err(Error::from)?;
         let datetime = "1999-12-31T23:59:59.999-00:00"
             .parse()
-            .map_err(Error::FailedParse)?;
+            .map_err(Error::from)?;
         assert_eq!(
             to_string(&N { name: "one day".to_string(), date, time, datetime })?,
             "name: one day\ndate: 2019-08-07\ntime: 12:34:56.789\ndatetime: 1999-12-31T23:59:59.999-00:00\n",
@@ -1159,4 +1950,241 @@ string_c:=first item
         assert_eq!(to_string(&m)?, "\"key：fake value, \": value\n");
         Ok(())
     }
+
+    #[derive(Serialize)]
+    enum V {
+        Unit,
+        Newtype(u32),
+        Tuple(i32, i32),
+        Struct { x: u8, y: Option<String> },
+    }
+    #[derive(Serialize)]
+    struct U {
+        a: V,
+        b: V,
+        c: V,
+        d: V,
+        list: Vec<V>,
+    }
+    #[test]
+    fn enums() -> Result<(), Box<Error>> {
+        assert_eq!(
+            to_string(&U {
+                a: V::Unit,
+                b: V::Newtype(5),
+                c: V::Tuple(1, -2),
+                d: V::Struct { x: 3, y: None },
+                list: vec![V::Unit, V::Unit, V::Newtype(7), V::Unit],
+            })?,
+            "a: Unit\nb:\n  Newtype: 5\nc:\n  Tuple: 1 -2\nd:\n  Struct: 3\nlist: Unit Unit\nlist:\n  Newtype: 7\nlist: Unit\n",
+        );
+        assert_eq!(
+            to_string(&V::Struct {
+                x: 1,
+                y: Some("text".to_string())
+            })?,
+            "Struct: 1\n  y: text\n",
+        );
+        Ok(())
+    }
+
+    struct Label<'a> {
+        text: &'a str,
+        size: u32,
+    }
+    impl Serialize for Label<'_> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            use serde::ser::SerializeStruct;
+            let mut state = serializer.serialize_struct("Label", 2)?;
+            state.serialize_field("text", self.text)?;
+            state.serialize_field("size", &self.size)?;
+            state.end()
+        }
+    }
+    #[derive(Serialize)]
+    struct LabelBox {
+        label: Label<'static>,
+    }
+    #[derive(Serialize)]
+    #[serde(tag = "kind")]
+    enum Kind {
+        Text { size: u32 },
+    }
+    #[derive(Serialize)]
+    struct KindBox {
+        tagged: Kind,
+    }
+    #[test]
+    fn enum_tags() -> Result<(), Box<Error>> {
+        let label = Label {
+            text: "box",
+            size: 3,
+        };
+        assert_eq!(to_string(&LabelBox { label })?, "label: box\n  size: 3\n");
+        let tagged = Kind::Text { size: 3 };
+        let options = SerializerOptions::new().enum_tags(&["kind"]);
+        assert_eq!(
+            options.to_string(&KindBox { tagged })?,
+            "tagged:\n  kind: Text\n  size: 3\n"
+        );
+        Ok(())
+    }
+
+    #[derive(Serialize)]
+    struct W {
+        name: &'static str,
+        notes: &'static str,
+        tags: Vec<&'static str>,
+    }
+    #[derive(Serialize)]
+    struct X {
+        w: W,
+    }
+    #[test]
+    fn options() -> Result<(), Box<Error>> {
+        let x = X {
+            w: W {
+                name: "Arthur",
+                notes: "one\ntwo",
+                tags: vec!["a", "b c", "d"],
+            },
+        };
+        assert_eq!(
+            to_string(&x)?,
+            "w: Arthur\n  notes: one\n       :>two\n  tags: a\n      :=b c\n      : d\n",
+        );
+        let options = SerializerOptions::new().indent(4).substitute(false);
+        assert_eq!(
+            options.to_string(&x)?,
+            "w:\n    name: Arthur\n    notes: one\n         :>two\n    tags: a\n        :=b c\n        : d\n",
+        );
+        let options = SerializerOptions::new().list_style(ListStyle::Repeat);
+        assert_eq!(
+            options.to_string(&x)?,
+            "w: Arthur\n  notes: one\n       :>two\n  tags: a\n  tags:=b c\n  tags: d\n",
+        );
+        let options = SerializerOptions::new().text_style(TextStyle::Value);
+        assert_eq!(
+            options.to_string(&x)?,
+            "w:=Arthur\n  notes:=one\n       :>two\n  tags:=a\n      :=b c\n      :=d\n",
+        );
+        let options = SerializerOptions::new().text_style(TextStyle::Append);
+        assert_eq!(
+            options.to_string(&x)?,
+            "w: Arthur\n  notes:>one\n       :>two\n  tags: a\n      :=b c\n      : d\n",
+        );
+        Ok(())
+    }
+
+    #[derive(Serialize)]
+    struct Tagged {
+        tags: Vec<&'static str>,
+        note: &'static str,
+    }
+    #[test]
+    fn text_after_list() -> Result<(), Box<Error>> {
+        let y = Tagged {
+            tags: vec!["a", "b"],
+            note: "one\ntwo",
+        };
+        let options = SerializerOptions::new().text_style(TextStyle::Append);
+        assert_eq!(options.to_string(&y)?, "tags: a b\nnote:>one\n    :>two\n",);
+        Ok(())
+    }
+
+    #[test]
+    fn indent_clamped() -> Result<(), Box<Error>> {
+        let x = BTreeMap::from([("a", BTreeMap::from([("b", 1)]))]);
+        let options = SerializerOptions::new().substitute(false);
+        assert_eq!(options.indent(0).to_string(&x)?, "a:\n  b: 1\n");
+        assert_eq!(options.indent(1).to_string(&x)?, "a:\n  b: 1\n");
+        assert_eq!(options.indent(3).to_string(&x)?, "a:\n   b: 1\n");
+        assert_eq!(options.indent(8).to_string(&x)?, "a:\n    b: 1\n");
+        Ok(())
+    }
+
+    #[derive(Serialize)]
+    struct Y {
+        name: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        score: Option<f32>,
+        date: Date,
+        tags: Vec<&'static str>,
+        shape: V,
+        extra: HashMap<&'static str, u8>,
+    }
+    #[derive(Serialize)]
+    struct Z {
+        y: Vec<Y>,
+        flag: Option<bool>,
+    }
+    #[test]
+    fn schema() -> Result<(), Box<Error>> {
+        let date = "2020-01-02".parse().map_err(Error::from)?;
+        let z = Z {
+            y: vec![
+                Y {
+                    name: "one",
+                    score: None,
+                    date,
+                    tags: vec![],
+                    shape: V::Unit,
+                    extra: HashMap::new(),
+                },
+                Y {
+                    name: "two",
+                    score: Some(1.5),
+                    date,
+                    tags: vec!["a"],
+                    shape: V::Newtype(1),
+                    extra: HashMap::new(),
+                },
+            ],
+            flag: None,
+        };
+        let options = SerializerOptions::new().schema(true);
+        assert_eq!(
+            options.to_string(&z)?,
+            ":::\ny: list record\n  name: text\n  date: date\n  \
+             tags: list text\n  shape: any\n  extra: dictionary\n  \
+             score: optional number\nflag: optional any\n:::\n\
+             y: one\n  date: 2020-01-02\n  shape: Unit\n  extra:\n\
+             y: two\n  score: 1.5\n  date: 2020-01-02\n  tags: a\n  \
+             shape:\n    Newtype: 1\n  extra:\n",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn line_ending() -> Result<(), Box<Error>> {
+        let mut map = HashMap::new();
+        map.insert("a", "b\nc");
+        let options = SerializerOptions::new().line_ending(LineEnding::CrLf);
+        assert_eq!(options.to_string(&map)?, "a: b\r\n :>c\r\n");
+        assert_eq!(
+            options.schema(true).to_string(&map)?,
+            ":::\r\na: text\r\n:::\r\na: b\r\n :>c\r\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn schema_dates() -> Result<(), Box<Error>> {
+        let dates = BTreeMap::from([
+            ("a", vec!["2020-01-02", "2020-01-03"]),
+            ("b", vec!["12:34:56", "noon"]),
+            ("c", vec!["text", "2020-01-02T12:00:00Z"]),
+        ]);
+        let options = SerializerOptions::new().schema(true);
+        assert_eq!(
+            options.to_string(&dates)?,
+            ":::\na: list date\nb: list text\nc: list text\n:::\n\
+             a: 2020-01-02 2020-01-03\nb: 12:34:56 noon\n\
+             c: text 2020-01-02T12:00:00Z\n",
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,446 @@
+// shape.rs
+//
+// Copyright (c) 2019-2020  Douglas Lau
+//
+use crate::common::{Define, Separator};
+use crate::datetime::{Date, DateTime, Time};
+use crate::error::{Error, ParseError};
+use crate::lines::{unquoted_key, DefIter};
+use crate::parse;
+use crate::schema::{Modifier, Node, Schema, Type};
+
+/// Shape of MuON data, recorded for a schema
+#[derive(Debug, Default)]
+pub(crate) struct Shape {
+    /// Key name
+    name: String,
+    /// Optional flag
+    optional: bool,
+    /// Repeated flag (key repeated within one record)
+    repeated: bool,
+    /// Number of values for key
+    values: usize,
+    /// Number of values which were lists
+    lists: usize,
+    /// Node type (`None` if no value has been seen)
+    node_type: Option<Type>,
+    /// Number of record instances
+    instances: usize,
+    /// Child shapes
+    children: Vec<Shape>,
+}
+
+impl Shape {
+    /// Create a new child shape
+    fn new_child(&self, name: &str) -> Shape {
+        // Keys missing from earlier records are optional
+        Shape {
+            name: name.to_string(),
+            optional: self.instances > 1,
+            ..Default::default()
+        }
+    }
+
+    /// Get a child shape, inserting it if necessary
+    pub(crate) fn child(&mut self, name: &str) -> &mut Shape {
+        match self.children.iter().position(|c| c.name == name) {
+            Some(i) => &mut self.children[i],
+            None => {
+                self.children.push(self.new_child(name));
+                self.children.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// Get the first child shape, inserting it if necessary
+    fn first_child(&mut self, name: &str) -> &mut Shape {
+        if self.children.first().map(|c| c.name.as_str()) != Some(name) {
+            self.children.retain(|c| c.name != name);
+            self.children.insert(0, self.new_child(name));
+        }
+        &mut self.children[0]
+    }
+
+    /// Record a node type
+    pub(crate) fn record_type(&mut self, node_type: Type) {
+        self.node_type = match self.node_type {
+            None => Some(node_type),
+            Some(tp) if tp == node_type => Some(tp),
+            Some(_) => Some(Type::Any),
+        };
+        if node_type == Type::Record {
+            self.instances += 1;
+        }
+    }
+
+    /// Record the type of a text value, which may be a date / time
+    pub(crate) fn record_text(&mut self, date_type: Option<Type>) {
+        match (self.node_type, date_type) {
+            (_, Some(tp)) => self.record_guess(tp),
+            (Some(Type::DateTime | Type::Date | Type::Time), None) => {
+                self.node_type = Some(Type::Text)
+            }
+            _ => self.record_type(Type::Text),
+        }
+    }
+
+    /// Record a guessed node type, widening where possible
+    fn record_guess(&mut self, node_type: Type) {
+        match (self.node_type, node_type) {
+            (Some(Type::Number), Type::Int) => (),
+            (Some(Type::Int), Type::Number) => {
+                self.node_type = Some(Type::Number)
+            }
+            // Any scalar value is also valid text
+            (Some(Type::Text), tp) | (Some(tp), Type::Text)
+                if is_scalar(tp) =>
+            {
+                self.node_type = Some(Type::Text)
+            }
+            _ => self.record_type(node_type),
+        }
+    }
+
+    /// Record a value substituted for the first field of a record
+    fn record_substitute(&mut self, guess: Guess) {
+        self.record_value();
+        self.record_guess(guess.node_type);
+        self.repeated |= guess.list;
+    }
+
+    /// Record a value
+    pub(crate) fn record_value(&mut self) {
+        self.values += 1;
+    }
+
+    /// Record that the key is optional
+    pub(crate) fn record_optional(&mut self) {
+        self.optional = true;
+    }
+
+    /// Record that a value was a list
+    pub(crate) fn record_list(&mut self) {
+        self.lists += 1;
+    }
+
+    /// Mark children not in a list of names as optional
+    pub(crate) fn check_missing(&mut self, names: &[String]) {
+        for child in &mut self.children {
+            if !names.contains(&child.name) {
+                child.optional = true;
+            }
+        }
+    }
+
+    /// Get the modifier and node type
+    fn modifier_type(&self) -> (Option<Modifier>, Type) {
+        let node_type = self.node_type.unwrap_or(Type::Any);
+        if self.repeated || (self.lists > 0 && self.lists == self.values) {
+            // List values may also be missing, so it takes precedence
+            (Some(Modifier::List), node_type)
+        } else if self.lists > 0 {
+            // Lists mixed with other values can only be `any`
+            (Some(Modifier::Optional), Type::Any)
+        } else if self.optional {
+            (Some(Modifier::Optional), node_type)
+        } else {
+            (None, node_type)
+        }
+    }
+
+    /// Add child nodes to a schema
+    fn add_nodes(&self, schema: &mut Schema, indent: usize) {
+        for child in &self.children {
+            let (modifier, node_type) = child.modifier_type();
+            let name = child.name.clone();
+            schema.push_node(Node::new(indent, name, modifier, node_type));
+            child.add_nodes(schema, indent + 1);
+        }
+    }
+
+    /// Make a schema with child nodes at the root
+    pub(crate) fn to_schema(&self) -> Schema<'static> {
+        let mut schema = Schema::new();
+        self.add_nodes(&mut schema, 0);
+        schema
+    }
+
+    /// Get a descendant shape by key path
+    fn path(&mut self, path: &[String]) -> &mut Shape {
+        path.iter().fold(self, |shape, name| shape.child(name))
+    }
+}
+
+/// Guess the type of a single value
+fn guess_type(value: &str) -> Type {
+    if parse::bool(value).is_some() {
+        Type::Bool
+    } else if parse::int::<i128>(value).is_some() {
+        Type::Int
+    } else if parse::number::<f64>(value).is_some() {
+        Type::Number
+    } else {
+        date_type(value).unwrap_or(Type::Text)
+    }
+}
+
+/// Get the date / time type of a text value, if any
+pub(crate) fn date_type(value: &str) -> Option<Type> {
+    if value.parse::<DateTime>().is_ok() {
+        Some(Type::DateTime)
+    } else if value.parse::<Date>().is_ok() {
+        Some(Type::Date)
+    } else if value.parse::<Time>().is_ok() {
+        Some(Type::Time)
+    } else {
+        None
+    }
+}
+
+/// Guessed value of a definition
+#[derive(Clone, Copy)]
+struct Guess {
+    /// Node type
+    node_type: Type,
+    /// List flag
+    list: bool,
+    /// Empty value flag
+    empty: bool,
+}
+
+impl Guess {
+    /// Guess the type of a definition value
+    fn new(define: &Define) -> Self {
+        let empty = define.value.is_empty();
+        let (node_type, list) = match define.separator {
+            Separator::Normal => guess_list(&define.value),
+            _ => (Type::Text, false),
+        };
+        Guess {
+            node_type,
+            list,
+            empty,
+        }
+    }
+}
+
+/// Guess the type of a value, and whether it is a list
+fn guess_list(value: &str) -> (Type, bool) {
+    let tp = guess_type(value);
+    if tp != Type::Text || !value.contains(' ') {
+        return (tp, false);
+    }
+    // Space-separated items of one type (other than text) are a list
+    let tp = value
+        .split(' ')
+        .map(guess_type)
+        .reduce(|a, b| match (a, b) {
+            (Type::Int, Type::Number) | (Type::Number, Type::Int) => {
+                Type::Number
+            }
+            (a, b) if a == b => a,
+            _ => Type::Text,
+        });
+    match tp {
+        Some(tp) if tp != Type::Text => (tp, true),
+        _ => (Type::Text, false),
+    }
+}
+
+/// Check if a type is a scalar (valid as text)
+fn is_scalar(tp: Type) -> bool {
+    !matches!(tp, Type::Record | Type::Dictionary | Type::Any)
+}
+
+/// Schema inference state
+struct Inference {
+    /// Root shape
+    root: Shape,
+    /// Key path of the most recent definition
+    path: Vec<String>,
+    /// Names of keys seen at each nesting level
+    levels: Vec<Vec<String>>,
+    /// Guessed value of the most recent definition
+    pending: Option<Guess>,
+}
+
+impl Inference {
+    /// Create a new inference
+    fn new() -> Self {
+        Inference {
+            root: Shape::default(),
+            path: Vec::new(),
+            levels: vec![Vec::new()],
+            pending: None,
+        }
+    }
+
+    /// Record the guessed value of the most recent definition
+    fn flush(&mut self) {
+        if let Some(guess) = self.pending.take() {
+            let key = self.path.last().cloned().unwrap_or_default();
+            let shape = self.root.path(&self.path);
+            if shape.node_type == Some(Type::Record) && !guess.empty {
+                // Record with only a substituted value
+                shape.record_type(Type::Record);
+                shape.first_child(&key).record_substitute(guess);
+                shape.check_missing(&[key]);
+            } else {
+                shape.record_guess(guess.node_type);
+                shape.repeated |= guess.list;
+            }
+        }
+    }
+
+    /// Pop nesting levels until `len` remain
+    fn pop_levels(&mut self, len: usize) {
+        while self.levels.len() > len {
+            if let Some(names) = self.levels.pop() {
+                let depth = self.levels.len();
+                self.root.path(&self.path[..depth]).check_missing(&names);
+            }
+        }
+    }
+
+    /// Start a record for the most recent definition
+    fn start_record(&mut self) {
+        let key = self.path.last().cloned().unwrap_or_default();
+        let shape = self.root.path(&self.path);
+        if let Some(node_type) = shape.node_type.filter(|tp| is_scalar(*tp)) {
+            // Earlier values were substituted for the first field
+            shape.node_type = None;
+            shape.record_type(Type::Record);
+            let first = shape.first_child(&key);
+            first.record_value();
+            first.record_guess(node_type);
+        }
+        shape.record_type(Type::Record);
+        let mut names = Vec::new();
+        // Value was substituted for the first field, which is not named
+        if let Some(guess) = self.pending.take().filter(|g| !g.empty) {
+            shape.first_child(&key).record_substitute(guess);
+            names.push(key);
+        }
+        self.levels.push(names);
+    }
+
+    /// Add a definition
+    fn define(&mut self, define: &Define) -> Result<(), ParseError> {
+        if define.separator == Separator::TextAppend {
+            // Appended text continues the previous definition
+            if let Some(guess) = &mut self.pending {
+                guess.node_type = Type::Text;
+                guess.list = false;
+            }
+            return Ok(());
+        }
+        let depth = define.indent;
+        if depth > self.path.len() {
+            return Err(ParseError::InvalidIndent);
+        }
+        if depth == self.path.len() && depth > 0 {
+            self.start_record();
+        } else {
+            self.flush();
+            self.pop_levels(depth + 1);
+            self.path.truncate(depth);
+        }
+        let key = unquoted_key(&define.key).into_owned();
+        let names = &mut self.levels[depth];
+        let repeated = names.contains(&key);
+        if !repeated {
+            names.push(key.clone());
+        }
+        self.path.push(key);
+        let shape = self.root.path(&self.path);
+        if repeated {
+            shape.repeated = true;
+        } else {
+            shape.record_value();
+        }
+        self.pending = Some(Guess::new(define));
+        Ok(())
+    }
+
+    /// Finish inference
+    fn finish(mut self) -> Schema<'static> {
+        self.flush();
+        self.pop_levels(0);
+        self.root.to_schema()
+    }
+}
+
+/// Infer a schema from a MuON document
+pub(crate) fn infer(text: &str) -> Result<Schema<'static>, Error> {
+    let mut inference = Inference::new();
+    let mut defs = DefIter::new(text);
+    while let Some(define) = defs.next() {
+        if let Err(e) = define.and_then(|d| inference.define(&d)) {
+            let position = defs.position();
+            let location = position.error_location(e, "".into());
+            return Err(Error::from(e).located(location));
+        }
+    }
+    Ok(inference.finish())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::check::check;
+
+    /// Infer a schema, and check the document against it
+    fn infer_check(text: &str) -> Result<String, Error> {
+        let schema = infer(text)?.to_string();
+        let doc = format!(":::\n{schema}:::\n{text}");
+        assert_eq!(check(&doc), []);
+        Ok(schema)
+    }
+
+    #[test]
+    fn books() -> Result<(), Error> {
+        assert_eq!(
+            infer_check(include_str!("../tests/books.muon"))?,
+            "book: list record\n  book: text\n  author: text\n  \
+             year: int\n  character: list record\n    character: text\n    \
+             location: optional text\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn types() -> Result<(), Error> {
+        let text = "a: true\nb: 1\nc: 2.5\nd: 2020-02-20T20:20:20Z\n\
+                    e: 2020-02-20\nf: 20:20:20\ng: words here\nh: 1 2 3\n\
+                    i:=5\nj: 5\nj: 6\nk: 7\n :>lines\nl: x\n : y\n\
+                    m:\n  n: 1\n  o: false\nm:\n  n: 2.5\n  p: 1 two\n\
+                    q: 1\nq: 2.0 3\n";
+        assert_eq!(
+            infer_check(text)?,
+            "a: bool\nb: int\nc: number\nd: datetime\ne: date\nf: time\n\
+             g: text\nh: list int\ni: text\nj: list int\nk: text\n\
+             l: list text\nm: list record\n  n: number\n  \
+             o: optional bool\n  p: optional text\nq: list number\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn quoted_keys() -> Result<(), Error> {
+        let text = "\"quoted key\": 1\n\"a\":\n  \"\"\"b\"\"\": x\n\
+                    \"a\": y\n  \"b\": z\n";
+        assert_eq!(
+            infer_check(text)?,
+            "quoted key: int\na: list record\n  a: optional text\n  \
+             \"\"\"b\"\"\": optional text\n  b: optional text\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        let e = infer("a: 1\n  b:\n      c: 2\n").unwrap_err();
+        assert_eq!(e.parse_error(), Some(ParseError::InvalidIndent));
+        assert_eq!(e.location().unwrap().line(), 3);
+    }
+}
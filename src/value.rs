@@ -0,0 +1,2143 @@
+// value.rs
+//
+// Copyright (c) 2019-2020  Douglas Lau
+//
+use crate::datetime;
+use crate::error::{Error, ErrorKind, ParseError, Result};
+use crate::parse;
+use crate::schema::{IntValue, NumValue, Value};
+use crate::ser::bytes_text;
+use serde::de::value::{
+    BorrowedStrDeserializer, MapDeserializer, SeqDeserializer,
+};
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer,
+    VariantAccess, Visitor,
+};
+use serde::ser::{self, Serialize};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::iter;
+
+/// Serializer for building a [`Value`] tree
+struct ValueSerializer;
+
+/// Serializer for sequences and tuples
+struct SerializeList {
+    /// Variant name (tuple variants only)
+    variant: Option<&'static str>,
+    /// List items
+    list: Vec<Value>,
+}
+
+/// Serializer for maps
+struct SerializeDict {
+    /// Key waiting for its value
+    key: Option<Value>,
+    /// Dictionary entries
+    entries: Vec<(Value, Value)>,
+}
+
+/// Serializer for structs
+struct SerializeRecord {
+    /// Variant name (struct variants only)
+    variant: Option<&'static str>,
+    /// Record fields
+    fields: Vec<(String, Value)>,
+}
+
+/// Wrap a value in a single-field record naming an enum variant
+fn variant_record(variant: &str, value: Value) -> Value {
+    Value::Record(vec![(variant.to_string(), value)])
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeDict;
+    type SerializeStruct = SerializeRecord;
+    type SerializeStructVariant = SerializeRecord;
+
+    fn serialize_bool(self, v: bool) -> Result<Value> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value> {
+        Ok(Value::Int(IntValue::Signed(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value> {
+        self.serialize_u128(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value> {
+        self.serialize_u128(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value> {
+        self.serialize_u128(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value> {
+        self.serialize_u128(v.into())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value> {
+        Ok(Value::Int(IntValue::Unsigned(v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value> {
+        Ok(Value::Number(NumValue::Num32(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value> {
+        Ok(Value::Number(NumValue::Num64(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value> {
+        Ok(Value::Text(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value> {
+        Ok(Value::Text(bytes_text(v)))
+    }
+
+    fn serialize_none(self) -> Result<Value> {
+        Ok(Value::Optional(None))
+    }
+
+    fn serialize_some<V>(self, value: &V) -> Result<Value>
+    where
+        V: ?Sized + Serialize,
+    {
+        let value = value.serialize(self)?;
+        Ok(Value::Optional(Some(Box::new(value))))
+    }
+
+    fn serialize_unit(self) -> Result<Value> {
+        Ok(Value::Optional(None))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        value: &V,
+    ) -> Result<Value>
+    where
+        V: ?Sized + Serialize,
+    {
+        let value = value.serialize(self)?;
+        // Dates and times are serialized as text in a marker newtype
+        if name == datetime::VALUE_NAME {
+            if let Value::Text(v) = &value {
+                return Value::from_datetime(v)
+                    .ok_or(Error::from(ParseError::ExpectedDateTime));
+            }
+        }
+        Ok(value)
+    }
+
+    fn serialize_newtype_variant<V>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &V,
+    ) -> Result<Value>
+    where
+        V: ?Sized + Serialize,
+    {
+        Ok(variant_record(variant, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList> {
+        Ok(SerializeList {
+            variant: None,
+            list: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeList> {
+        Ok(SerializeList {
+            variant: Some(variant),
+            list: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeDict> {
+        Ok(SerializeDict {
+            key: None,
+            entries: Vec::new(),
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeRecord> {
+        Ok(SerializeRecord {
+            variant: None,
+            fields: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeRecord> {
+        Ok(SerializeRecord {
+            variant: Some(variant),
+            fields: Vec::with_capacity(len),
+        })
+    }
+}
+
+impl SerializeList {
+    fn push<V>(&mut self, value: &V) -> Result<()>
+    where
+        V: ?Sized + Serialize,
+    {
+        self.list.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        let list = Value::List(self.list);
+        Ok(match self.variant {
+            Some(variant) => variant_record(variant, list),
+            None => list,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<V>(&mut self, value: &V) -> Result<()>
+    where
+        V: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        SerializeList::end(self)
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<V>(&mut self, value: &V) -> Result<()>
+    where
+        V: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        SerializeList::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<V>(&mut self, value: &V) -> Result<()>
+    where
+        V: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        SerializeList::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<V>(&mut self, value: &V) -> Result<()>
+    where
+        V: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        SerializeList::end(self)
+    }
+}
+
+impl ser::SerializeMap for SerializeDict {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<K>(&mut self, key: &K) -> Result<()>
+    where
+        K: ?Sized + Serialize,
+    {
+        self.key = Some(key.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<V>(&mut self, value: &V) -> Result<()>
+    where
+        V: ?Sized + Serialize,
+    {
+        let key = self.key.take().ok_or(ErrorKind::InvalidKey)?;
+        self.entries.push((key, value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::Dictionary(self.entries))
+    }
+}
+
+impl SerializeRecord {
+    fn push<V>(&mut self, key: &'static str, value: &V) -> Result<()>
+    where
+        V: ?Sized + Serialize,
+    {
+        let value = value.serialize(ValueSerializer)?;
+        self.fields.push((key.to_string(), value));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        let record = Value::Record(self.fields);
+        Ok(match self.variant {
+            Some(variant) => variant_record(variant, record),
+            None => record,
+        })
+    }
+}
+
+impl ser::SerializeStruct for SerializeRecord {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<V>(&mut self, key: &'static str, value: &V) -> Result<()>
+    where
+        V: ?Sized + Serialize,
+    {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        SerializeRecord::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeRecord {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<V>(&mut self, key: &'static str, value: &V) -> Result<()>
+    where
+        V: ?Sized + Serialize,
+    {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        SerializeRecord::end(self)
+    }
+}
+
+/// Convert `T` to a MuON [`Value`]
+///
+/// # Errors
+///
+/// Conversion can fail if the serializer for `T` returns an error.
+pub fn to_value<T>(value: &T) -> Result<Value>
+where
+    T: ?Sized + Serialize,
+{
+    value.serialize(ValueSerializer)
+}
+
+/// Convert a MuON [`Value`] to `T`
+///
+/// Repeated record keys are combined into a list, and records which were
+/// parsed without a schema may have their first field substituted, just like
+/// when deserializing MuON text.  To borrow from a value instead, use
+/// `T::deserialize(&value)`.
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// #[derive(Debug, Deserialize)]
+/// struct Book {
+///     title: String,
+///     year: u16,
+/// }
+///
+/// # fn main() -> Result<(), muon_rs::Error> {
+/// let muon = "title: Hamlet\nyear: 1603\n";
+/// let value: muon_rs::Value = muon_rs::from_str(muon)?;
+/// let book: Book = muon_rs::from_value(value)?;
+/// assert_eq!(book.title, "Hamlet");
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// An error will be returned if the structure of the value does not match
+/// the structure of `T`.
+pub fn from_value<T>(value: Value) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    T::deserialize(value)
+}
+
+/// Visit an integer value
+fn visit_int<'de, V>(v: IntValue, visitor: V) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    match v {
+        IntValue::Signed(v) => match i64::try_from(v) {
+            Ok(v) => visitor.visit_i64(v),
+            Err(_) => visitor.visit_i128(v),
+        },
+        IntValue::Unsigned(v) => match u64::try_from(v) {
+            Ok(v) => visitor.visit_u64(v),
+            Err(_) => visitor.visit_u128(v),
+        },
+    }
+}
+
+/// Group the values of repeated record keys
+fn group_record<K, V>(
+    record: impl IntoIterator<Item = (K, V)>,
+) -> Vec<(K, Vec<V>)>
+where
+    K: Clone + Eq + Hash,
+{
+    let mut groups: Vec<(K, Vec<V>)> = Vec::new();
+    let mut index: HashMap<K, usize> = HashMap::new();
+    for (key, value) in record {
+        match index.get(&key) {
+            Some(&i) => groups[i].1.push(value),
+            None => {
+                index.insert(key.clone(), groups.len());
+                groups.push((key, vec![value]));
+            }
+        }
+    }
+    groups
+}
+
+/// Combine repeated keys of a record into lists
+fn combine_record(record: Vec<(String, Value)>) -> Vec<(String, Value)> {
+    group_record(record)
+        .into_iter()
+        .map(|(key, mut values)| {
+            if values.len() == 1 {
+                (key, values.pop().unwrap())
+            } else {
+                (key, Value::List(values))
+            }
+        })
+        .collect()
+}
+
+impl Value {
+    /// Get the text of a scalar value
+    pub(crate) fn into_text(self) -> Option<String> {
+        match self {
+            Value::Text(v) => Some(v),
+            Value::Bool(v) => Some(v.to_string()),
+            Value::Int(IntValue::Signed(v)) => Some(v.to_string()),
+            Value::Int(IntValue::Unsigned(v)) => Some(v.to_string()),
+            Value::Number(NumValue::Num32(v)) => Some(v.to_string()),
+            Value::Number(NumValue::Num64(v)) => Some(v.to_string()),
+            Value::DateTime(v) => Some(v.to_string()),
+            Value::Date(v) => Some(v.to_string()),
+            Value::Time(v) => Some(v.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Deserialize a record as a map
+    fn visit_record<'de, V>(
+        record: Vec<(String, Value)>,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let fields = combine_record(record).into_iter().map(|(key, value)| {
+            (key.clone(), FieldDeserializer { key, value })
+        });
+        let mut map = MapDeserializer::<_, Error>::new(fields);
+        let value = visitor.visit_map(&mut map)?;
+        map.end()?;
+        Ok(value)
+    }
+
+    /// Deserialize a record as a struct
+    ///
+    /// Without a schema, a substituted first field is keyed by the parent
+    /// record key, which is renamed to the first field name.
+    fn visit_struct<'de, V>(
+        self,
+        parent: Option<&str>,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match (self, fields.first()) {
+            (Value::Any(v), _) => v.visit_struct(parent, fields, visitor),
+            (Value::Record(mut record), Some(first)) => {
+                if let Some((key, _)) = record.first_mut() {
+                    if parent == Some(key.as_str())
+                        && !fields.contains(&key.as_str())
+                        && !record.iter().any(|(k, _)| k == first)
+                    {
+                        record[0].0 = first.to_string();
+                    }
+                }
+                Value::visit_record(record, visitor)
+            }
+            (value @ Value::Record(_), None)
+            | (value @ Value::Dictionary(_), _)
+            | (value @ Value::List(_), _)
+            | (value @ Value::Optional(_), _) => {
+                de::Deserializer::deserialize_map(value, visitor)
+            }
+            // Substituted first field value
+            (value, Some(first)) => {
+                Value::visit_record(vec![(first.to_string(), value)], visitor)
+            }
+            (value, None) => de::Deserializer::deserialize_any(value, visitor),
+        }
+    }
+
+    /// Deserialize a dictionary as a map
+    fn visit_dictionary<'de, V>(
+        dict: Vec<(Value, Value)>,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let mut map = MapDeserializer::<_, Error>::new(dict.into_iter());
+        let value = visitor.visit_map(&mut map)?;
+        map.end()?;
+        Ok(value)
+    }
+
+    /// Deserialize a list as a sequence
+    fn visit_list<'de, V>(list: Vec<Value>, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let mut seq = SeqDeserializer::<_, Error>::new(list.into_iter());
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+
+    /// Deserialize a borrowed record as a map
+    ///
+    /// The key of the first field is replaced by `first`, if given.
+    fn visit_record_ref<'de, V>(
+        record: &'de [(String, Value)],
+        first: Option<&'static str>,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let entries =
+            record
+                .iter()
+                .enumerate()
+                .map(|(i, (key, value))| match first {
+                    Some(first) if i == 0 => (first, value),
+                    _ => (key.as_str(), value),
+                });
+        let fields = group_record(entries).into_iter().map(|(key, values)| {
+            let values = if values.len() == 1 {
+                FieldValues::One(values[0])
+            } else {
+                FieldValues::Many(values)
+            };
+            (
+                BorrowedStrDeserializer::new(key),
+                FieldRefDeserializer { key, values },
+            )
+        });
+        let mut map = MapDeserializer::<_, Error>::new(fields);
+        let value = visitor.visit_map(&mut map)?;
+        map.end()?;
+        Ok(value)
+    }
+
+    /// Deserialize a borrowed record as a struct
+    ///
+    /// Substituted first fields are handled as in [`Value::visit_struct`].
+    fn visit_struct_ref<'de, V>(
+        &'de self,
+        parent: Option<&str>,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match (self, fields.first()) {
+            (Value::Any(v), _) => v.visit_struct_ref(parent, fields, visitor),
+            (Value::Record(record), Some(first)) => {
+                let first = match record.first() {
+                    Some((key, _))
+                        if parent == Some(key.as_str())
+                            && !fields.contains(&key.as_str())
+                            && !record.iter().any(|(k, _)| k == first) =>
+                    {
+                        Some(*first)
+                    }
+                    _ => None,
+                };
+                Value::visit_record_ref(record, first, visitor)
+            }
+            (Value::Record(_), None)
+            | (Value::Dictionary(_), _)
+            | (Value::List(_), _)
+            | (Value::Optional(_), _) => {
+                de::Deserializer::deserialize_map(self, visitor)
+            }
+            // Substituted first field value
+            (value, Some(first)) => {
+                let field = FieldRefDeserializer {
+                    key: first,
+                    values: FieldValues::One(value),
+                };
+                let mut map = MapDeserializer::<_, Error>::new(iter::once((
+                    BorrowedStrDeserializer::new(first),
+                    field,
+                )));
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            (value, None) => de::Deserializer::deserialize_any(value, visitor),
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Text(v) => visitor.visit_string(v),
+            Value::Bool(v) => visitor.visit_bool(v),
+            Value::Int(v) => visit_int(v, visitor),
+            Value::Number(NumValue::Num32(v)) => visitor.visit_f32(v),
+            Value::Number(NumValue::Num64(v)) => visitor.visit_f64(v),
+            Value::DateTime(_) | Value::Date(_) | Value::Time(_) => {
+                visitor.visit_string(self.into_text().unwrap_or_default())
+            }
+            Value::Record(record) => {
+                let mut map = MapDeserializer::<_, Error>::new(
+                    record.into_iter().map(|(k, v)| (Value::Text(k), v)),
+                );
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            Value::Dictionary(dict) => Value::visit_dictionary(dict, visitor),
+            Value::Any(v) => v.deserialize_any(visitor),
+            Value::Optional(None) => visitor.visit_none(),
+            Value::Optional(Some(v)) => visitor.visit_some(*v),
+            Value::List(list) => Value::visit_list(list, visitor),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Text(v) => visitor.visit_bool(
+                parse::bool(&v).ok_or(Error::from(ParseError::ExpectedBool))?,
+            ),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Text(v) => {
+                visit_int(v.parse().map_err(Error::from)?, visitor)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Text(v) => visitor.visit_f64(
+                parse::number(&v)
+                    .ok_or(Error::from(ParseError::ExpectedNumber))?,
+            ),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Any(v) => v.deserialize_string(visitor),
+            Value::Optional(_) | Value::Record(_) => {
+                self.deserialize_any(visitor)
+            }
+            Value::Dictionary(_) | Value::List(_) => {
+                self.deserialize_any(visitor)
+            }
+            _ => visitor.visit_string(self.into_text().unwrap_or_default()),
+        }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Any(v) => v.deserialize_byte_buf(visitor),
+            Value::Text(v) => visitor.visit_byte_buf(
+                parse::bytes(&v)
+                    .ok_or(Error::from(ParseError::ExpectedBytes))?,
+            ),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Any(v) => v.deserialize_option(visitor),
+            Value::Optional(None) => visitor.visit_none(),
+            Value::Optional(Some(v)) => visitor.visit_some(*v),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Any(v) => v.deserialize_unit(visitor),
+            Value::Optional(None) => visitor.visit_unit(),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if name != datetime::VALUE_NAME {
+            return visitor.visit_newtype_struct(self);
+        }
+        match self {
+            Value::DateTime(_) | Value::Date(_) | Value::Time(_) => {
+                // Dates and times are wrapped in a single-entry marker map
+                let v = self.into_text().unwrap_or_default();
+                let mut map = MapDeserializer::<_, Error>::new(iter::once((
+                    datetime::MARKER,
+                    v,
+                )));
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            Value::Any(v) => v.deserialize_newtype_struct(name, visitor),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Any(v) => v.deserialize_seq(visitor),
+            Value::List(list) => Value::visit_list(list, visitor),
+            Value::Optional(None) => Value::visit_list(vec![], visitor),
+            // Text lists are separated by spaces; appended lines continue
+            // the last item, as when deserializing MuON text
+            Value::Text(v) => Value::visit_list(
+                v.split(' ')
+                    .filter(|item| !item.is_empty())
+                    .map(|item| Value::Text(item.to_string()))
+                    .collect(),
+                visitor,
+            ),
+            _ => Value::visit_list(vec![self], visitor),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Any(v) => v.deserialize_map(visitor),
+            Value::Record(record) => Value::visit_record(record, visitor),
+            Value::Dictionary(dict) => Value::visit_dictionary(dict, visitor),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.visit_struct(None, fields, visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Any(v) => v.deserialize_enum(name, variants, visitor),
+            Value::Text(variant) => {
+                visitor.visit_enum(variant.into_deserializer())
+            }
+            Value::Record(mut record) if record.len() == 1 => {
+                let (variant, value) = record.pop().unwrap();
+                visitor.visit_enum(VariantDeserializer { variant, value })
+            }
+            _ => Err(Error::from(ParseError::InvalidType)),
+        }
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+}
+
+/// Deserializer for a record field value
+///
+/// The field key is kept to recognize a substituted first field of a nested
+/// record, which is keyed by the same key.
+struct FieldDeserializer {
+    /// Field key
+    key: String,
+    /// Field value
+    value: Value,
+}
+
+impl<'de> IntoDeserializer<'de, Error> for FieldDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl FieldDeserializer {
+    /// Deserialize field values as a sequence
+    fn visit_list<'de, V>(
+        key: String,
+        list: Vec<Value>,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let items = list.into_iter().map(|value| FieldDeserializer {
+            key: key.clone(),
+            value,
+        });
+        let mut seq = SeqDeserializer::<_, Error>::new(items);
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+}
+
+macro_rules! forward_to_value {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                self.value.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for FieldDeserializer {
+    type Error = Error;
+
+    forward_to_value! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16
+        deserialize_i32 deserialize_i64 deserialize_i128 deserialize_u8
+        deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str
+        deserialize_string deserialize_bytes deserialize_byte_buf
+        deserialize_unit deserialize_map deserialize_identifier
+        deserialize_ignored_any
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let key = self.key;
+        match self.value {
+            Value::Any(value) => FieldDeserializer { key, value: *value }
+                .deserialize_option(visitor),
+            Value::Optional(Some(value)) => {
+                visitor.visit_some(FieldDeserializer { key, value: *value })
+            }
+            value => value.deserialize_option(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.value.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if name == datetime::VALUE_NAME {
+            self.value.deserialize_newtype_struct(name, visitor)
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let key = self.key;
+        match self.value {
+            Value::Any(value) => FieldDeserializer { key, value: *value }
+                .deserialize_seq(visitor),
+            Value::List(list) => {
+                FieldDeserializer::visit_list(key, list, visitor)
+            }
+            // A single record may have a substituted first field
+            value @ Value::Record(_) => {
+                FieldDeserializer::visit_list(key, vec![value], visitor)
+            }
+            value => value.deserialize_seq(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.value.visit_struct(Some(&self.key), fields, visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.value.deserialize_enum(name, variants, visitor)
+    }
+}
+
+/// Deserializer for enum variants
+struct VariantDeserializer {
+    /// Variant name
+    variant: String,
+    /// Variant value
+    value: Value,
+}
+
+impl<'de> EnumAccess<'de> for VariantDeserializer {
+    type Error = Error;
+    type Variant = Value;
+
+    fn variant_seed<S>(self, seed: S) -> Result<(S::Value, Value)>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(Value::Text(self.variant))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Value {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<S>(self, seed: S) -> Result<S::Value>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for &'de Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &'de Value {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Text(v) => visitor.visit_borrowed_str(v),
+            Value::Bool(v) => visitor.visit_bool(*v),
+            Value::Int(v) => visit_int(v.clone(), visitor),
+            Value::Number(NumValue::Num32(v)) => visitor.visit_f32(*v),
+            Value::Number(NumValue::Num64(v)) => visitor.visit_f64(*v),
+            Value::DateTime(_) | Value::Date(_) | Value::Time(_) => visitor
+                .visit_string(self.clone().into_text().unwrap_or_default()),
+            Value::Record(record) => {
+                let mut map = MapDeserializer::<_, Error>::new(
+                    record
+                        .iter()
+                        .map(|(k, v)| (BorrowedStrDeserializer::new(k), v)),
+                );
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            Value::Dictionary(dict) => {
+                let mut map = MapDeserializer::<_, Error>::new(
+                    dict.iter().map(|(k, v)| (k, v)),
+                );
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            Value::Any(v) => (&**v).deserialize_any(visitor),
+            Value::Optional(None) => visitor.visit_none(),
+            Value::Optional(Some(v)) => visitor.visit_some(&**v),
+            Value::List(list) => {
+                let mut seq = SeqDeserializer::<_, Error>::new(list.iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Text(v) => visitor.visit_bool(
+                parse::bool(v).ok_or(Error::from(ParseError::ExpectedBool))?,
+            ),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Text(v) => {
+                visit_int(v.parse().map_err(Error::from)?, visitor)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_i128(visitor)
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Text(v) => visitor.visit_f64(
+                parse::number(v)
+                    .ok_or(Error::from(ParseError::ExpectedNumber))?,
+            ),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Any(v) => (&**v).deserialize_string(visitor),
+            Value::Text(v) => visitor.visit_borrowed_str(v),
+            Value::Optional(_) | Value::Record(_) => {
+                self.deserialize_any(visitor)
+            }
+            Value::Dictionary(_) | Value::List(_) => {
+                self.deserialize_any(visitor)
+            }
+            _ => visitor
+                .visit_string(self.clone().into_text().unwrap_or_default()),
+        }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Any(v) => (&**v).deserialize_byte_buf(visitor),
+            Value::Text(v) => visitor.visit_byte_buf(
+                parse::bytes(v)
+                    .ok_or(Error::from(ParseError::ExpectedBytes))?,
+            ),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Any(v) => (&**v).deserialize_option(visitor),
+            Value::Optional(None) => visitor.visit_none(),
+            Value::Optional(Some(v)) => visitor.visit_some(&**v),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Any(v) => (&**v).deserialize_unit(visitor),
+            Value::Optional(None) => visitor.visit_unit(),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if name != datetime::VALUE_NAME {
+            return visitor.visit_newtype_struct(self);
+        }
+        match self {
+            Value::DateTime(_) | Value::Date(_) | Value::Time(_) => {
+                // Dates and times are wrapped in a single-entry marker map
+                let v = self.clone().into_text().unwrap_or_default();
+                let mut map = MapDeserializer::<_, Error>::new(iter::once((
+                    datetime::MARKER,
+                    v,
+                )));
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            Value::Any(v) => (&**v).deserialize_newtype_struct(name, visitor),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Any(v) => (&**v).deserialize_seq(visitor),
+            Value::Optional(None) => Value::visit_list(vec![], visitor),
+            // Text lists are separated by spaces
+            Value::Text(v) => Value::visit_list(
+                v.split(' ')
+                    .filter(|item| !item.is_empty())
+                    .map(|item| Value::Text(item.to_string()))
+                    .collect(),
+                visitor,
+            ),
+            _ => {
+                let list = match self {
+                    Value::List(list) => &list[..],
+                    _ => std::slice::from_ref(self),
+                };
+                let mut seq = SeqDeserializer::<_, Error>::new(list.iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Any(v) => (&**v).deserialize_map(visitor),
+            Value::Record(record) => {
+                Value::visit_record_ref(record, None, visitor)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.visit_struct_ref(None, fields, visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Any(v) => (&**v).deserialize_enum(name, variants, visitor),
+            Value::Text(variant) => {
+                visitor.visit_enum(BorrowedStrDeserializer::new(variant))
+            }
+            Value::Record(record) if record.len() == 1 => {
+                let (variant, value) = &record[0];
+                visitor.visit_enum(VariantRefDeserializer { variant, value })
+            }
+            _ => Err(Error::from(ParseError::InvalidType)),
+        }
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+}
+
+/// Values of a borrowed record field
+enum FieldValues<'de> {
+    /// Value of a single key
+    One(&'de Value),
+    /// Values of a repeated key
+    Many(Vec<&'de Value>),
+}
+
+/// Deserializer for a borrowed record field value
+///
+/// Values of repeated keys are deserialized as a list.
+struct FieldRefDeserializer<'de> {
+    /// Field key
+    key: &'de str,
+    /// Field values
+    values: FieldValues<'de>,
+}
+
+impl<'de> IntoDeserializer<'de, Error> for FieldRefDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> FieldRefDeserializer<'de> {
+    /// Deserialize field values as a sequence
+    fn visit_list<V>(
+        key: &'de str,
+        values: impl Iterator<Item = &'de Value>,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let items = values.map(|value| FieldRefDeserializer {
+            key,
+            values: FieldValues::One(value),
+        });
+        let mut seq = SeqDeserializer::<_, Error>::new(items);
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+}
+
+macro_rules! forward_to_values {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                match self.values {
+                    FieldValues::One(value) => value.$method(visitor),
+                    FieldValues::Many(values) => FieldRefDeserializer::visit_list(
+                        self.key,
+                        values.into_iter(),
+                        visitor,
+                    ),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for FieldRefDeserializer<'de> {
+    type Error = Error;
+
+    forward_to_values! {
+        deserialize_any deserialize_bool deserialize_i8 deserialize_i16
+        deserialize_i32 deserialize_i64 deserialize_i128 deserialize_u8
+        deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str
+        deserialize_string deserialize_bytes deserialize_byte_buf
+        deserialize_unit deserialize_map deserialize_identifier
+        deserialize_ignored_any
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let key = self.key;
+        match self.values {
+            FieldValues::One(Value::Any(value)) => FieldRefDeserializer {
+                key,
+                values: FieldValues::One(value),
+            }
+            .deserialize_option(visitor),
+            FieldValues::One(Value::Optional(Some(value))) => visitor
+                .visit_some(FieldRefDeserializer {
+                    key,
+                    values: FieldValues::One(value),
+                }),
+            FieldValues::One(value) => value.deserialize_option(visitor),
+            FieldValues::Many(_) => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.values {
+            FieldValues::One(value) => {
+                value.deserialize_unit_struct(name, visitor)
+            }
+            FieldValues::Many(_) => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.values {
+            FieldValues::One(value) if name == datetime::VALUE_NAME => {
+                value.deserialize_newtype_struct(name, visitor)
+            }
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let key = self.key;
+        match self.values {
+            FieldValues::One(Value::Any(value)) => FieldRefDeserializer {
+                key,
+                values: FieldValues::One(value),
+            }
+            .deserialize_seq(visitor),
+            FieldValues::One(Value::List(list)) => {
+                FieldRefDeserializer::visit_list(key, list.iter(), visitor)
+            }
+            // A single record may have a substituted first field
+            FieldValues::One(value @ Value::Record(_)) => {
+                FieldRefDeserializer::visit_list(
+                    key,
+                    iter::once(value),
+                    visitor,
+                )
+            }
+            FieldValues::One(value) => value.deserialize_seq(visitor),
+            FieldValues::Many(values) => FieldRefDeserializer::visit_list(
+                key,
+                values.into_iter(),
+                visitor,
+            ),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.values {
+            FieldValues::One(value) => {
+                value.visit_struct_ref(Some(self.key), fields, visitor)
+            }
+            FieldValues::Many(_) => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.values {
+            FieldValues::One(value) => {
+                value.deserialize_enum(name, variants, visitor)
+            }
+            FieldValues::Many(_) => Err(Error::from(ParseError::InvalidType)),
+        }
+    }
+}
+
+/// Deserializer for borrowed enum variants
+struct VariantRefDeserializer<'de> {
+    /// Variant name
+    variant: &'de str,
+    /// Variant value
+    value: &'de Value,
+}
+
+impl<'de> EnumAccess<'de> for VariantRefDeserializer<'de> {
+    type Error = Error;
+    type Variant = &'de Value;
+
+    fn variant_seed<S>(self, seed: S) -> Result<(S::Value, &'de Value)>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        let variant = seed
+            .deserialize(BorrowedStrDeserializer::<Error>::new(self.variant))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> VariantAccess<'de> for &'de Value {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<S>(self, seed: S) -> Result<S::Value>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::de::from_str;
+    use super::super::schema::{IntValue, NumValue, Value};
+    use super::{from_value, to_value, Error};
+    use crate::{Date, DateTime, Time};
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    fn text(v: &str) -> Value {
+        Value::Text(v.to_string())
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    enum Shape {
+        Point,
+        Circle(f64),
+        Line(i32, i32),
+        Rect { width: u32, height: Option<u32> },
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Data {
+        name: String,
+        flag: bool,
+        count: u8,
+        shapes: Vec<Shape>,
+        map: BTreeMap<u32, String>,
+    }
+
+    #[test]
+    fn to() -> Result<(), Box<Error>> {
+        let data = Data {
+            name: "first".to_string(),
+            flag: true,
+            count: 3,
+            shapes: vec![
+                Shape::Point,
+                Shape::Circle(0.5),
+                Shape::Line(1, -1),
+                Shape::Rect {
+                    width: 2,
+                    height: None,
+                },
+            ],
+            map: BTreeMap::from([(4, "four".to_string())]),
+        };
+        let value = to_value(&data)?;
+        assert_eq!(
+            value,
+            Value::Record(vec![
+                ("name".to_string(), text("first")),
+                ("flag".to_string(), Value::Bool(true)),
+                ("count".to_string(), Value::Int(IntValue::Unsigned(3))),
+                (
+                    "shapes".to_string(),
+                    Value::List(vec![
+                        text("Point"),
+                        Value::Record(vec![(
+                            "Circle".to_string(),
+                            Value::Number(NumValue::Num64(0.5))
+                        )]),
+                        Value::Record(vec![(
+                            "Line".to_string(),
+                            Value::List(vec![
+                                Value::Int(IntValue::Signed(1)),
+                                Value::Int(IntValue::Signed(-1)),
+                            ])
+                        )]),
+                        Value::Record(vec![(
+                            "Rect".to_string(),
+                            Value::Record(vec![
+                                (
+                                    "width".to_string(),
+                                    Value::Int(IntValue::Unsigned(2))
+                                ),
+                                ("height".to_string(), Value::Optional(None)),
+                            ])
+                        )]),
+                    ])
+                ),
+                (
+                    "map".to_string(),
+                    Value::Dictionary(vec![(
+                        Value::Int(IntValue::Unsigned(4)),
+                        text("four")
+                    )])
+                ),
+            ])
+        );
+        assert_eq!(data, from_value(value)?);
+        Ok(())
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Book {
+        title: String,
+        author: String,
+        year: Option<i16>,
+        character: Vec<Character>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Character {
+        name: String,
+        location: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct BookList {
+        book: Vec<Book>,
+    }
+
+    #[test]
+    fn from() -> Result<(), Box<Error>> {
+        let value: Value = from_str(
+            "book: Pale Fire\n  author: Vladimir Nabokov\n  year: 1962\n  \
+             character: John Shade\n    location: New Wye\n  \
+             character: Charles Kinbote\n    location: Zembla\n\
+             book: The Curious Incident of the Dog in the Night-Time\n  \
+             author: Mark Haddon\n  year: 2003\n  \
+             character: Christopher Boone\n    location: Swindon\n  \
+             character: Siobhan\n",
+        )?;
+        let books: BookList = from_value(value.clone())?;
+        assert_eq!(books.book.len(), 2);
+        assert_eq!(books.book[0].title, "Pale Fire");
+        assert_eq!(books.book[0].year, Some(1962));
+        assert_eq!(books.book[0].character[1].name, "Charles Kinbote");
+        assert_eq!(
+            books.book[1].character[1],
+            Character {
+                name: "Siobhan".to_string(),
+                location: None,
+            }
+        );
+        assert_eq!(value, from_value::<Value>(value.clone())?);
+        let value: Value = from_str("character: Siobhan\n")?;
+        let one: BTreeMap<String, Vec<Character>> = from_value(value)?;
+        assert_eq!(one["character"][0].name, "Siobhan");
+        let value = Value::Record(vec![("alias".to_string(), text("Al"))]);
+        assert!(from_value::<Character>(value).is_err());
+        let nums: Vec<u16> = from_value(text("1 2 x3"))?;
+        assert_eq!(nums, [1, 2, 3]);
+        let date = Value::Date("2020-02-29".parse().map_err(Error::from)?);
+        assert_eq!(from_value::<String>(date.clone())?, "2020-02-29");
+        assert_eq!(from_value::<Value>(date.clone())?, date);
+        Ok(())
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Person<'a> {
+        name: &'a str,
+        nickname: Vec<&'a str>,
+        born: Date,
+    }
+
+    #[test]
+    fn from_ref() -> Result<(), Box<Error>> {
+        let value: Value = from_str(
+            "person: Alexander Hamilton\n  nickname: Ham\n  \
+             nickname: Alex\n  born: 1755-01-11\n",
+        )?;
+        let record: BTreeMap<&str, Vec<Person>> =
+            Deserialize::deserialize(&value)?;
+        let person = &record["person"][0];
+        assert_eq!(person.name, "Alexander Hamilton");
+        assert_eq!(person.nickname, ["Ham", "Alex"]);
+        assert_eq!(person.born.to_string(), "1755-01-11");
+        assert_eq!(Value::deserialize(&value)?, value);
+        let nums = Vec::<u16>::deserialize(&text("1 2 x3"))?;
+        assert_eq!(nums, [1, 2, 3]);
+        let data = Data {
+            name: "second".to_string(),
+            flag: false,
+            count: 0,
+            shapes: vec![Shape::Circle(1.5), Shape::Line(0, 2)],
+            map: BTreeMap::new(),
+        };
+        assert_eq!(data, Data::deserialize(&to_value(&data)?)?);
+        Ok(())
+    }
+
+    #[test]
+    fn dates() -> Result<(), Box<Error>> {
+        let date: Date = "2020-02-29".parse().map_err(Error::from)?;
+        assert_eq!(to_value(&date)?, Value::Date(date));
+        let time: Time = "12:34:56".parse().map_err(Error::from)?;
+        assert_eq!(to_value(&time)?, Value::Time(time));
+        let datetime: DateTime =
+            "2020-02-29T12:34:56Z".parse().map_err(Error::from)?;
+        let value = to_value(&Some(datetime))?;
+        assert_eq!(
+            value,
+            Value::Optional(Some(Box::new(Value::DateTime(datetime))))
+        );
+        assert_eq!(from_value::<Option<DateTime>>(value)?, Some(datetime));
+        let value = Value::Date(date);
+        assert_eq!(to_value(&value)?, value);
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+// writer.rs
+//
+// Copyright (c) 2019-2020  Douglas Lau
+//
+use crate::error::{ErrorKind, Result};
+use crate::schema::Schema;
+use crate::ser::{Serializer, SerializerOptions};
+use std::fmt::Display;
+use std::io::Write;
+
+/// Writer for MuON text, without serde
+///
+/// Records, values and comments are written in order, using the same
+/// indentation, key quoting and list style as a [`Serializer`].  Without a
+/// schema, a value substituted onto the record key line cannot be read back
+/// with its key, so [`Writer::new`] does not substitute.
+///
+/// # Example
+/// ```
+/// use muon_rs::Writer;
+///
+/// let mut writer = Writer::new(vec![]);
+/// writer.comment("Books")?;
+/// writer.begin_record("book")?;
+/// writer.text("title", "Pale Fire")?;
+/// writer.value("year", 1962)?;
+/// writer.list("character", ["John Shade", "Charles Kinbote"])?;
+/// writer.end_record()?;
+/// let muon = String::from_utf8(writer.finish()?).unwrap();
+/// assert_eq!(
+///     muon,
+///     "# Books\nbook:\n  title: Pale Fire\n  year: 1962\n  \
+///      character:=John Shade\n           :=Charles Kinbote\n",
+/// );
+/// # Ok::<(), muon_rs::Error>(())
+/// ```
+pub struct Writer<W: Write> {
+    /// Serializer for output
+    ser: Serializer<W>,
+    /// Flag indicating anything has been written
+    started: bool,
+}
+
+/// Check that a key can be written
+///
+/// Keys must not be empty, and cannot contain line breaks.
+fn check_key(key: &str) -> Result<()> {
+    if key.is_empty() || key.contains(['\n', '\r']) {
+        Err(ErrorKind::InvalidKey.into())
+    } else {
+        Ok(())
+    }
+}
+
+impl<W: Write> Writer<W> {
+    /// Create a new writer with default options, without substitution
+    pub fn new(writer: W) -> Self {
+        let options = SerializerOptions::default().substitute(false);
+        Writer::with_options(writer, options)
+    }
+
+    /// Create a new writer with style options
+    ///
+    /// The [`schema`](SerializerOptions::schema) option is ignored; use
+    /// [`Writer::schema`] instead.  The first value of a record is
+    /// substituted onto the record key line if
+    /// [`substitute`](SerializerOptions::substitute) is enabled, unless it
+    /// has multiple lines.
+    pub fn with_options(writer: W, options: SerializerOptions) -> Self {
+        let mut ser = Serializer::with_options(writer, options.schema(false));
+        ser.push_stack();
+        Writer {
+            ser,
+            started: false,
+        }
+    }
+
+    /// Write a schema block (`:::`)
+    ///
+    /// # Errors
+    ///
+    /// The schema must be written before anything else.
+    pub fn schema(&mut self, schema: &Schema) -> Result<()> {
+        if self.started {
+            return Err(ErrorKind::Serialize(
+                "schema must be written first".into(),
+            )
+            .into());
+        }
+        self.started = true;
+        self.ser.write_schema(schema)
+    }
+
+    /// Begin a record
+    ///
+    /// Following values are fields of the record, until
+    /// [`end_record`](Writer::end_record) is called.
+    pub fn begin_record(&mut self, key: &str) -> Result<()> {
+        check_key(key)?;
+        self.started = true;
+        self.ser.begin_record(key)
+    }
+
+    /// End the current record
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if no record has been begun.
+    pub fn end_record(&mut self) -> Result<()> {
+        self.ser.end_record()
+    }
+
+    /// Write a value, formatted with [`Display`]
+    ///
+    /// The formatted value is written as [`text`](Writer::text).
+    pub fn value(&mut self, key: &str, value: impl Display) -> Result<()> {
+        self.text(key, &value.to_string())
+    }
+
+    /// Write a text value
+    ///
+    /// Multi-line text is continued with `:>`, and `:=` is used when needed.
+    /// Text containing a carriage return (`\r`) is a
+    /// [`Serialize`](crate::ErrorKind::Serialize) error.
+    pub fn text(&mut self, key: &str, text: &str) -> Result<()> {
+        check_key(key)?;
+        self.started = true;
+        self.ser.text(key, text)
+    }
+
+    /// Write a list of values, formatted with [`Display`]
+    pub fn list<I>(&mut self, key: &str, items: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: Display,
+    {
+        check_key(key)?;
+        self.started = true;
+        self.ser.list(key, items)
+    }
+
+    /// Write a comment
+    ///
+    /// Each line of the text is written as a separate comment.
+    pub fn comment(&mut self, text: &str) -> Result<()> {
+        self.started = true;
+        self.ser.comment(text)
+    }
+
+    /// End all records and unwrap the writer
+    pub fn finish(mut self) -> Result<W> {
+        self.ser.end_all()?;
+        Ok(self.ser.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ser::ListStyle;
+
+    fn finish(writer: Writer<Vec<u8>>) -> Result<String> {
+        Ok(String::from_utf8(writer.finish()?)?)
+    }
+
+    #[test]
+    fn records() -> Result<()> {
+        let mut w = Writer::new(vec![]);
+        w.value("a", 1)?;
+        w.begin_record("b")?;
+        w.begin_record("c")?;
+        w.value("d", true)?;
+        w.end_record()?;
+        w.text("e", "x\n y")?;
+        w.end_record()?;
+        w.begin_record("f")?;
+        w.end_record()?;
+        w.value("g", "")?;
+        let text = finish(w)?;
+        assert_eq!(
+            text,
+            "a: 1\nb:\n  c:\n    d: true\n  e: x\n   :> y\nf:\ng: \n"
+        );
+        assert!(crate::Parser::new(&text).all(|e| e.is_ok()));
+        Ok(())
+    }
+
+    #[test]
+    fn empty_records() -> Result<()> {
+        let mut w = Writer::new(vec![]);
+        w.begin_record("a")?;
+        w.begin_record("b")?;
+        w.end_record()?;
+        w.end_record()?;
+        assert_eq!(finish(w)?, "a:\n  b:\n");
+        let mut w = Writer::new(vec![]);
+        w.begin_record("a")?;
+        w.begin_record("b")?;
+        w.end_record()?;
+        w.value("c", 1)?;
+        assert_eq!(finish(w)?, "a:\n  b:\n  c: 1\n");
+        let mut w = Writer::new(vec![]);
+        w.value("z", 0)?;
+        w.begin_record("a")?;
+        w.begin_record("b")?;
+        w.begin_record("c")?;
+        w.end_record()?;
+        w.end_record()?;
+        w.end_record()?;
+        w.begin_record("d")?;
+        w.begin_record("e")?;
+        let text = finish(w)?;
+        assert_eq!(text, "z: 0\na:\n  b:\n    c:\nd:\n  e:\n");
+        assert!(crate::Parser::new(&text).all(|e| e.is_ok()));
+        Ok(())
+    }
+
+    #[test]
+    fn substitute() -> Result<()> {
+        let options = SerializerOptions::new().substitute(true);
+        let mut w = Writer::with_options(vec![], options);
+        w.begin_record("a")?;
+        w.text("b", "x")?;
+        w.value("c", 1)?;
+        w.end_record()?;
+        w.begin_record("r")?;
+        w.text("x", "a\nb")?;
+        w.value("y", 2)?;
+        let text = finish(w)?;
+        assert_eq!(text, "a: x\n  c: 1\nr:\n  x: a\n   :>b\n  y: 2\n");
+        assert!(crate::Parser::new(&text).all(|e| e.is_ok()));
+        Ok(())
+    }
+
+    #[test]
+    fn lists() -> Result<()> {
+        let mut w = Writer::new(vec![]);
+        w.list("a", [1, 2, 3])?;
+        w.list("b", ["x y", "z"])?;
+        w.list("c", Vec::<u8>::new())?;
+        w.comment("d")?;
+        w.list("e", [4, 5])?;
+        assert_eq!(finish(w)?, "a: 1 2 3\nb:=x y\n : z\n# d\ne: 4 5\n");
+        let options = SerializerOptions::new().list_style(ListStyle::Repeat);
+        let mut w = Writer::with_options(vec![], options);
+        w.list("a", [1, 2])?;
+        assert_eq!(finish(w)?, "a: 1\na: 2\n");
+        Ok(())
+    }
+
+    #[test]
+    fn comments() -> Result<()> {
+        let mut w = Writer::new(vec![]);
+        w.comment("top\n\nlevel")?;
+        w.begin_record("a")?;
+        w.begin_record("b c")?;
+        w.comment("inner")?;
+        w.value("d:", 1)?;
+        w.value("e", 2)?;
+        assert_eq!(
+            finish(w)?,
+            "# top\n#\n# level\na:\n  b c:\n    # inner\n    \"d:\": 1\n    \
+             e: 2\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn schema() -> Result<()> {
+        let schema = Schema::parse("a: int\nb: optional text\n")?;
+        let mut w = Writer::new(vec![]);
+        w.schema(&schema)?;
+        w.value("a", 5)?;
+        assert!(w.schema(&schema).is_err());
+        assert_eq!(finish(w)?, ":::\na: int\nb: optional text\n:::\na: 5\n");
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        let mut w = Writer::new(vec![]);
+        assert!(w.end_record().is_err());
+        let err = w.value("", 1).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidKey));
+        let err = w.value("a\nb", 1).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidKey));
+        let err = w.begin_record("a\rb").unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidKey));
+    }
+}
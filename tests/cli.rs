@@ -0,0 +1,85 @@
+#![cfg(feature = "cli")]
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn muon(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_muon"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(args: &[&str], input: &str) -> String {
+    let output = muon(args, input);
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn check() {
+    assert!(muon(&["check", "tests/books.muon"], "").status.success());
+    let output = muon(&["check", "-"], "a: 1\nb 2\n");
+    assert_eq!(output.status.code(), Some(1));
+    let out = String::from_utf8(output.stdout).unwrap();
+    assert!(out.starts_with("-: missing separator at line 2, column 1"));
+    assert_eq!(muon(&["check"], "").status.code(), Some(2));
+}
+
+#[test]
+fn fmt() {
+    assert_eq!(stdout(&["fmt"], "a:\n    \"b\": 1\n"), "a:\n  b: 1\n");
+}
+
+#[test]
+fn json() {
+    let json = stdout(&["to-json", "tests/people.muon"], "");
+    let muon = stdout(&["from-json"], &json);
+    assert!(muon.starts_with(":::\ngroup: list record\n  group: text\n"));
+    // Keys missing from some objects are read back as `null`
+    let json = stdout(&["to-json"], &muon);
+    assert!(json.contains("\"birthplace\": null"));
+    assert_eq!(stdout(&["from-json"], &json), muon);
+}
+
+#[test]
+fn json_objects() {
+    let json =
+        "{\"a\": null, \"b\": [], \"c\": {\"d\": {\"e\": 1}, \"f\": {}}}";
+    let text = stdout(&["from-json"], json);
+    assert_eq!(
+        text,
+        ":::\na: optional any\nb: list any\nc: record\n  d: record\n    \
+         e: int\n  f: record\n:::\nc:\n  d:\n    e: 1\n  f:\n"
+    );
+    let value: serde_json::Value = serde_json::from_str(json).unwrap();
+    let out = stdout(&["to-json"], &text);
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&out).unwrap(),
+        value
+    );
+    for json in ["{\"a\": [[1]]}", "{\"a\": [1, null]}"] {
+        assert_eq!(muon(&["from-json"], json).status.code(), Some(1));
+    }
+}
+
+#[test]
+fn get() {
+    let path = "group[1].person.born";
+    let out = stdout(&["get", path, "tests/people.muon"], "");
+    assert_eq!(out, "1724\n1788\n1596\n");
+    let out = stdout(&["get", "book[1].author", "tests/books.muon"], "");
+    assert_eq!(out, "Mark Haddon\n");
+    let output = muon(&["get", "group.age", "tests/people.muon"], "");
+    assert_eq!(output.status.code(), Some(1));
+}
@@ -1,5 +1,6 @@
 use muon_rs as muon;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
 struct A {
@@ -54,3 +55,371 @@ fn people() -> muon::Result<()> {
     assert_eq!(muon::to_string(&g)?, include_str!("people.muon"));
     Ok(())
 }
+
+#[test]
+fn people_schema() -> muon::Result<()> {
+    let g: Groups = muon::from_str(include_str!("people.muon"))?;
+    let s = muon::SerializerOptions::new().schema(true).to_string(&g)?;
+    assert!(s.starts_with(
+        ":::\ngroup: list record\n  label: text\n  person: list record\n    \
+         name: text\n    born: int\n    birthplace: optional text\n:::\n"
+    ));
+    assert_eq!(muon::check(&s), []);
+    assert_eq!(g, muon::from_str(&s)?);
+    Ok(())
+}
+
+#[test]
+fn people_options() -> muon::Result<()> {
+    use muon::{ListStyle, SerializerOptions, TextStyle};
+    let g: Groups = muon::from_str(include_str!("people.muon"))?;
+    for indent in 2..=4 {
+        for substitute in [false, true] {
+            for list_style in [ListStyle::Merge, ListStyle::Repeat] {
+                for text_style in
+                    [TextStyle::Normal, TextStyle::Value, TextStyle::Append]
+                {
+                    let s = SerializerOptions::new()
+                        .indent(indent)
+                        .substitute(substitute)
+                        .list_style(list_style)
+                        .text_style(text_style)
+                        .to_string(&g)?;
+                    assert_eq!(g, muon::from_str(&s)?);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+enum External {
+    Unit,
+    Newtype(i32),
+    Tuple(u8, u8),
+    Struct { label: String, size: u32 },
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type")]
+enum Internal {
+    Circle { radius: f64 },
+    Square { side: f64, label: String },
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "tag", content = "content")]
+enum Adjacent {
+    Unit,
+    Newtype(i32),
+    Tuple(u8, u8),
+    Struct { label: Option<String>, size: u32 },
+    Single { name: String },
+    Word(String),
+    Pair(String, i8),
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(untagged)]
+enum Untagged {
+    Int(i64),
+    Text(String),
+    Struct { label: Option<String>, size: u32 },
+    Named { name: String, count: u8 },
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Enums {
+    external: Vec<External>,
+    internal: Vec<Internal>,
+    adjacent: Vec<Adjacent>,
+    untagged: Vec<Untagged>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Nested {
+    shape: Internal,
+    setting: Untagged,
+}
+
+#[test]
+fn enums() -> muon::Result<()> {
+    let e = Enums {
+        external: vec![
+            External::Unit,
+            External::Newtype(-5),
+            External::Tuple(1, 2),
+            External::Struct {
+                label: "a label".to_string(),
+                size: 15,
+            },
+        ],
+        internal: vec![
+            Internal::Circle { radius: 2.5 },
+            Internal::Square {
+                side: 1.0,
+                label: "square".to_string(),
+            },
+        ],
+        adjacent: vec![
+            Adjacent::Unit,
+            Adjacent::Newtype(7),
+            Adjacent::Tuple(3, 4),
+            Adjacent::Struct {
+                label: Some("text".to_string()),
+                size: 8,
+            },
+            Adjacent::Single {
+                name: "a name".to_string(),
+            },
+            Adjacent::Word("a word".to_string()),
+            Adjacent::Pair("pair".to_string(), -1),
+        ],
+        untagged: vec![
+            Untagged::Int(99),
+            Untagged::Text("some text".to_string()),
+            Untagged::Struct {
+                label: None,
+                size: 5,
+            },
+            Untagged::Named {
+                name: "a name".to_string(),
+                count: 3,
+            },
+        ],
+    };
+    let s = muon::to_string(&e)?;
+    assert_eq!(e, muon::from_str(&s)?);
+    let s = muon::SerializerOptions::new().schema(true).to_string(&e)?;
+    assert_eq!(muon::check(&s), []);
+    for i in [
+        Internal::Circle { radius: 2.5 },
+        Internal::Square {
+            side: 1.0,
+            label: "square".to_string(),
+        },
+    ] {
+        let s = muon::to_string(&i)?;
+        assert_eq!(i, muon::from_str(&s)?);
+    }
+    let n = Nested {
+        shape: Internal::Circle { radius: 2.5 },
+        setting: Untagged::Named {
+            name: "a name".to_string(),
+            count: 3,
+        },
+    };
+    let s = muon::to_string(&n)?;
+    assert_eq!(
+        s,
+        "shape:\n  type: Circle\n  radius: 2.5\nsetting: a name\n  count: 3\n"
+    );
+    assert_eq!(n, muon::from_str(&s)?);
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(untagged)]
+enum Ambiguous {
+    Number(i64),
+    Record { x: i64 },
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Setting {
+    value: Ambiguous,
+}
+
+#[test]
+fn untagged_substitute() -> muon::Result<()> {
+    let setting = Setting {
+        value: Ambiguous::Record { x: 4 },
+    };
+    // substituted record is read back as the earlier newtype variant
+    let s = muon::to_string(&setting)?;
+    assert_eq!(s, "value: 4\n");
+    assert_eq!(
+        muon::from_str::<Setting>(&s)?,
+        Setting {
+            value: Ambiguous::Number(4),
+        }
+    );
+    let s = muon::SerializerOptions::new()
+        .substitute(false)
+        .to_string(&setting)?;
+    assert_eq!(s, "value:\n  x: 4\n");
+    assert_eq!(muon::from_str::<Setting>(&s)?, setting);
+    Ok(())
+}
+
+#[test]
+fn value_roundtrip() -> muon::Result<()> {
+    for muon in [
+        include_str!("books.muon"),
+        include_str!("people.muon"),
+        "name: Some Name\nscore: -5\nratio: 0.5\nflag: true\n",
+        "d:\n  e: true\n",
+        "d:\n  e: 1\n  f: 2\n",
+        "d: 1\n  f: 2\n",
+    ] {
+        let value: muon::Value = muon::from_str(muon)?;
+        assert_eq!(muon::to_string(&value)?, muon);
+    }
+    let value: muon::Value = muon::from_str(
+        ":::\nname: text\nflag: optional bool\ntag: list text\n\
+         when: date\nitem: list record\n  id: int\n  note: optional text\n\
+         :::\nname: Some Name\ntag: a b\nwhen: 2019-08-07\n\
+         item: 1\n  note: first\nitem:\n  id: 2\n",
+    )?;
+    assert_eq!(
+        muon::to_string(&value)?,
+        "name: Some Name\ntag: a b\nwhen: 2019-08-07\n\
+         item:\n  id: 1\n  note: first\nitem:\n  id: 2\n"
+    );
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Note {
+    text: String,
+}
+
+#[test]
+fn text_roundtrip() -> muon::Result<()> {
+    for text in ["a", "a b", "a\nb", "a\n\nb\n", " a ", ""] {
+        let note = Note { text: text.into() };
+        let s = muon::to_string(&note)?;
+        assert_eq!(muon::from_str::<Note>(&s)?, note);
+    }
+    for text in ["a\r\nb", "x\r", "a\rb"] {
+        let note = Note { text: text.into() };
+        let err = muon::to_string(&note).unwrap_err();
+        assert!(matches!(err.kind(), muon::ErrorKind::Serialize(_)));
+    }
+    let note = Note {
+        text: "a\nbc\rd".into(),
+    };
+    let err = muon::to_string(&note).unwrap_err();
+    let location = err.location().unwrap();
+    assert_eq!((location.line(), location.column()), (2, 3));
+    let err = muon::to_string(&'\r').unwrap_err();
+    assert!(matches!(err.kind(), muon::ErrorKind::Serialize(_)));
+    let mut writer = muon::Writer::new(Vec::new());
+    let err = writer.text("text", "a\r\nb").unwrap_err();
+    assert!(matches!(err.kind(), muon::ErrorKind::Serialize(_)));
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Words {
+    word: Vec<String>,
+}
+
+#[test]
+fn text_list_roundtrip() -> muon::Result<()> {
+    for word in [
+        vec!["", "q", ""],
+        vec!["\n", "q", "\n"],
+        vec![" ", "a b", ""],
+        vec!["", ""],
+    ] {
+        let words = Words {
+            word: word.into_iter().map(String::from).collect(),
+        };
+        let s = muon::to_string(&words)?;
+        assert_eq!(muon::from_str::<Words>(&s)?, words, "{s:?}");
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Blob {
+    #[serde(with = "serde_bytes")]
+    hash: Vec<u8>,
+    data: Vec<serde_bytes::ByteBuf>,
+}
+
+#[test]
+fn bytes() -> muon::Result<()> {
+    let blob = Blob {
+        hash: vec![0xDE, 0xAD, 0xBE, 0xEF],
+        data: vec![
+            serde_bytes::ByteBuf::new(),
+            serde_bytes::ByteBuf::from((0..40).collect::<Vec<u8>>()),
+        ],
+    };
+    let s = muon::to_string(&blob)?;
+    assert_eq!(
+        s,
+        "hash: 0xdeadbeef\ndata: 0x 0x000102030405060708090a0b0c0d0e0f\
+         101112131415161718191a1b1c1d1e1f\n    :>2021222324252627\n"
+    );
+    assert_eq!(muon::from_str::<Blob>(&s)?, blob);
+    let value = muon::to_value(&blob)?;
+    assert_eq!(muon::from_value::<Blob>(value)?, blob);
+    let value: muon::Value = muon::from_str(&s)?;
+    assert_eq!(muon::from_value::<Blob>(value)?, blob);
+    let value: muon::Value = muon::from_str("hash: 0x0001\n")?;
+    let text = muon::Value::Text("0x0001".into());
+    assert_eq!(value, muon::Value::Record(vec![("hash".into(), text)]));
+    let err = muon::from_str::<Blob>("hash: 0xabc\n").unwrap_err();
+    assert_eq!(err.parse_error(), Some(muon::ParseError::ExpectedBytes));
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+struct Id(u16);
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+enum Color {
+    Red,
+    Blue,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+struct Keyed {
+    count: BTreeMap<u32, String>,
+    day: BTreeMap<muon::Date, i8>,
+    id: BTreeMap<Id, bool>,
+    color: BTreeMap<Color, f32>,
+    flag: BTreeMap<bool, char>,
+    sign: BTreeMap<char, u8>,
+    name: BTreeMap<String, u8>,
+}
+
+#[test]
+fn non_string_keys() -> muon::Result<()> {
+    let keyed = Keyed {
+        count: BTreeMap::from([(1, "one".into()), (20, "twenty".into())]),
+        day: BTreeMap::from([("2020-02-29".parse().unwrap(), -4)]),
+        id: BTreeMap::from([(Id(7), true)]),
+        color: BTreeMap::from([(Color::Red, 0.5), (Color::Blue, 1.0)]),
+        flag: BTreeMap::from([(false, 'n'), (true, 'y')]),
+        sign: BTreeMap::from([(' ', 1), ('#', 2), ('"', 3)]),
+        name: BTreeMap::from([("j".into(), 2), ("k".into(), 1)]),
+    };
+    let s = muon::to_string(&keyed)?;
+    assert_eq!(
+        s,
+        "count:\n  1: one\n  20: twenty\nday:\n  2020-02-29: -4\n\
+         id:\n  7: true\ncolor:\n  Red: 0.5\n  Blue: 1\n\
+         flag:\n  false: n\n  true: y\nsign:\n  \" \": 1\n  \"\"\"\": 3\n  \
+         \"#\": 2\nname:\n  j: 2\n  k: 1\n"
+    );
+    assert_eq!(muon::from_str::<Keyed>(&s)?, keyed);
+    let value = muon::to_value(&keyed)?;
+    assert_eq!(muon::from_value::<Keyed>(value)?, keyed);
+    let names = BTreeMap::from([("a: b".to_string(), 4), ("\"c\"".into(), 5)]);
+    let s = muon::to_string(&names)?;
+    assert_eq!(s, "\"\"\"c\"\"\": 5\n\"a: b\": 4\n");
+    assert_eq!(muon::from_str::<BTreeMap<String, u8>>(&s)?, names);
+    let root: HashMap<i64, u8> = muon::from_str("x10: 1\n-3: 2\n")?;
+    assert_eq!(root, HashMap::from([(16, 1), (-3, 2)]));
+    let err = muon::from_str::<HashMap<u8, u8>>("a: 1\n").unwrap_err();
+    assert_eq!(err.parse_error(), Some(muon::ParseError::ExpectedInt));
+    let map = HashMap::from([(vec![1], 2)]);
+    let err = muon::to_string(&map).unwrap_err();
+    assert!(matches!(err.kind(), muon::ErrorKind::InvalidKey));
+    Ok(())
+}